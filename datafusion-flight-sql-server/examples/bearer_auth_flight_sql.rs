@@ -1,14 +1,15 @@
 //! # DataFusion Flight SQL Server with Bearer Token Authentication Example
 //!
-//! This example demonstrates how to integrate Bearer Token authentication into a
-//! DataFusion Flight SQL server.
+//! This example demonstrates how to authenticate the clients of a
+//! DataFusion Flight SQL server with Bearer Tokens.
 //!
 //! Key components:
-//! - `bearer_auth_interceptor`: A tonic interceptor that validates "Authorization: Bearer <token>"
-//!   headers. For simplicity, it uses a hardcoded list of valid tokens ("token1", "token2").
-//! - `UserData`: A simple struct holding user information (e.g., user_id) extracted from
-//!   a valid token and inserted into request extensions.
-//! - `MySessionStateProvider`: A custom `SessionStateProvider` that retrieves `UserData`
+//! - `MemoryAuthenticator`: An `Authenticator` validating the "Authorization: Bearer <token>"
+//!   header sent with every request. For simplicity, it uses a hardcoded list of valid
+//!   tokens ("token1", "token2") and a user ("admin") that can obtain a token through Handshake.
+//! - `Identity`: The authenticated user, which FlightSqlService inserts into the
+//!   request extensions.
+//! - `MySessionStateProvider`: A custom `SessionStateProvider` that retrieves the `Identity`
 //!   from request extensions. This allows tailoring the `SessionState` for the request,
 //!   although in this example, it primarily clones a base context after successful authentication.
 //!   It also registers a "test" CSV table for querying.
@@ -20,8 +21,9 @@
 //! 1. A valid token ("token1") - Expected to succeed.
 //! 2. An invalid token ("invalidtoken") - Expected to fail authentication.
 //! 3. No token - Expected to fail authentication.
+//! 4. A token obtained through Handshake with Basic credentials - Expected to succeed.
 //!
-//! Observe the server console output for messages from the interceptor and session provider,
+//! Observe the server console output for messages from the session provider,
 //! and the client output for the success/failure of each attempt.
 
 use std::time::Duration;
@@ -33,47 +35,13 @@ use async_trait::async_trait;
 use datafusion::error::{DataFusionError, Result};
 use datafusion::execution::context::SessionState; // SessionState is not in prelude
 use datafusion::prelude::*; // Covers SessionContext, CsvReadOptions, etc.
+use datafusion_flight_sql_server::auth::{Identity, MemoryAuthenticator};
 use datafusion_flight_sql_server::service::FlightSqlService;
 use datafusion_flight_sql_server::session::SessionStateProvider;
 use tokio::time::sleep;
 use tonic::transport::{Channel, Endpoint, Server};
 use tonic::{Request, Status};
 
-// UserData struct remains the same
-#[derive(Clone, Debug)]
-pub struct UserData {
-    pub user_id: u32,
-}
-
-// bearer_auth_interceptor remains the same
-async fn bearer_auth_interceptor(mut req: Request<()>) -> Result<Request<()>, Status> {
-    let auth_header = req
-        .metadata()
-        .get("authorization")
-        .ok_or_else(|| Status::unauthenticated("no authorization provided"))?;
-
-    let auth_str = auth_header
-        .to_str()
-        .map_err(|_| Status::unauthenticated("invalid authorization header encoding"))?;
-
-    if !auth_str.starts_with("Bearer ") {
-        return Err(Status::unauthenticated(
-            "invalid authorization header format",
-        ));
-    }
-
-    let token = &auth_str["Bearer ".len()..];
-
-    let user_data = match token {
-        "token1" => UserData { user_id: 1 },
-        "token2" => UserData { user_id: 2 },
-        _ => return Err(Status::unauthenticated("invalid token")),
-    };
-
-    req.extensions_mut().insert(user_data);
-    Ok(req)
-}
-
 // Updated MySessionStateProvider
 // #[derive(Debug, Clone)] // Removed Default
 pub struct MySessionStateProvider {
@@ -96,18 +64,18 @@ impl MySessionStateProvider {
 impl SessionStateProvider for MySessionStateProvider {
     async fn new_context(&self, request: &Request<()>) -> Result<SessionState, Status> {
         // tonic::Result for Status
-        if let Some(user_data) = request.extensions().get::<UserData>() {
+        if let Some(identity) = request.extensions().get::<Identity>() {
             println!(
-                "Session context for user_id: {}. Cloning base context.",
-                user_data.user_id
+                "Session context for user: {}. Cloning base context.",
+                identity.user
             );
             let state = self.base_context.state().clone();
-            // Optional: Customize state based on user_data
-            // state.set_config_option("datafusion.user_id", &user_data.user_id.to_string()).map_err(|e| Status::internal(format!("Failed to set config: {}",e)))?;
+            // Optional: Customize state based on the identity
+            // state.set_config_option("datafusion.user", &identity.user).map_err(|e| Status::internal(format!("Failed to set config: {}",e)))?;
             Ok(state)
         } else {
             Err(Status::unauthenticated(
-                "User data not found in request extensions (MySessionStateProvider)",
+                "Identity not found in request extensions (MySessionStateProvider)",
             ))
         }
     }
@@ -140,7 +108,12 @@ async fn main() -> Result<()> {
     // Server Setup
     let dsn: String = "0.0.0.0:50051".to_string();
     let state_provider = Box::new(MySessionStateProvider::try_new().await?);
-    let base_service = FlightSqlService::new_with_provider(state_provider);
+    let authenticator = MemoryAuthenticator::new()
+        .with_user("admin", "password")
+        .with_token("token1", Identity::new("1"))
+        .with_token("token2", Identity::new("2"));
+    let base_service = FlightSqlService::new_with_provider(state_provider)
+        .with_authenticator(Box::new(authenticator));
     let svc: FlightServiceServer<FlightSqlService> = FlightServiceServer::new(base_service);
     let addr: std::net::SocketAddr = dsn.parse().map_err(|e| {
         DataFusionError::External(format!("Invalid address format {}: {}", dsn, e).into())
@@ -151,14 +124,7 @@ async fn main() -> Result<()> {
             "Bearer Authentication Flight SQL server listening on {}",
             addr
         );
-        if let Err(e) = Server::builder()
-            .layer(tonic_async_interceptor::async_interceptor(
-                bearer_auth_interceptor,
-            ))
-            .add_service(svc)
-            .serve(addr)
-            .await
-        {
+        if let Err(e) = Server::builder().add_service(svc).serve(addr).await {
             eprintln!("Server error: {}", e);
         }
     });
@@ -234,5 +200,30 @@ async fn main() -> Result<()> {
         Err(e) => eprintln!("Failed to create client with no token: {}", e),
     }
 
+    // Test Case 4: Token obtained through Handshake
+    println!("\nAttempting GetTables with a token obtained through Handshake...");
+    match new_client_with_auth(client_dsn.clone(), None).await {
+        Ok(mut client) => {
+            if let Err(e) = client.handshake("admin", "password").await {
+                eprintln!("Handshake FAILED: {}", e);
+            }
+            let request = CommandGetTables {
+                catalog: None,
+                db_schema_filter_pattern: None,
+                table_name_filter_pattern: None,
+                table_types: vec![],
+                include_schema: false,
+            };
+            match client.get_tables(request).await {
+                Ok(response) => println!(
+                    "GetTables with handshake token SUCCEEDED. Response: {:?}",
+                    response
+                ),
+                Err(e) => eprintln!("GetTables with handshake token FAILED: {}", e),
+            }
+        }
+        Err(e) => eprintln!("Failed to create client for handshake: {}", e),
+    }
+
     Ok(())
 }
@@ -0,0 +1,200 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use datafusion::{
+    common::tree_node::TreeNodeRecursion,
+    error::{DataFusionError, Result as DataFusionResult},
+    logical_expr::{DdlStatement, LogicalPlan, WriteOp},
+    sql::TableReference,
+};
+use tonic::Status;
+
+use super::{auth::Identity, keys::table_matches};
+
+/// An access to a table, checked by an [`AccessPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TableAccess {
+    /// Scans the table, or lists it in the metadata commands.
+    Read,
+    Insert,
+    Update,
+    /// Deletes rows of the table or truncates it.
+    Delete,
+    /// Creates the table or the view.
+    Create,
+    /// Drops the table or the view.
+    Drop,
+}
+
+// AccessPolicy is a trait used to authorize the clients of the service to
+// access tables, checked on every plan before it is executed.
+//
+// Tables are referenced with their catalog and schema. The identity is None
+// when the service does not authenticate its clients.
+pub trait AccessPolicy: Sync + Send {
+    /// Returns true if the client can access the table.
+    fn allow_table(
+        &self,
+        identity: Option<&Identity>,
+        table: &TableReference,
+        access: TableAccess,
+    ) -> bool;
+
+    /// Returns true if the client can see the catalog in GetCatalogs.
+    fn allow_catalog(&self, _identity: Option<&Identity>, _catalog: &str) -> bool {
+        true
+    }
+
+    /// Returns true if the client can see the schema in GetDbSchemas.
+    fn allow_schema(&self, _identity: Option<&Identity>, _catalog: &str, _schema: &str) -> bool {
+        true
+    }
+}
+
+impl<T: AccessPolicy + ?Sized> AccessPolicy for Arc<T> {
+    fn allow_table(
+        &self,
+        identity: Option<&Identity>,
+        table: &TableReference,
+        access: TableAccess,
+    ) -> bool {
+        self.as_ref().allow_table(identity, table, access)
+    }
+
+    fn allow_catalog(&self, identity: Option<&Identity>, catalog: &str) -> bool {
+        self.as_ref().allow_catalog(identity, catalog)
+    }
+
+    fn allow_schema(&self, identity: Option<&Identity>, catalog: &str, schema: &str) -> bool {
+        self.as_ref().allow_schema(identity, catalog, schema)
+    }
+}
+
+// MemoryAccessPolicy is an AccessPolicy granting accesses to tables per user.
+// Clients only see the catalogs and schemas holding a table granted to them,
+// and unauthenticated clients are denied every access.
+#[derive(Debug, Default)]
+pub struct MemoryAccessPolicy {
+    grants: HashMap<String, Vec<(TableReference, HashSet<TableAccess>)>>,
+}
+
+impl MemoryAccessPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Grants the accesses to the table to the user.
+    ///
+    /// A table reference that is not fully qualified grants the access to the
+    /// tables with that name in any catalog or schema.
+    ///
+    /// ```
+    /// # use datafusion_flight_sql_server::access::{MemoryAccessPolicy, TableAccess};
+    /// let policy = MemoryAccessPolicy::new()
+    ///     .with_grant("alice", "datafusion.public.users", [TableAccess::Read]);
+    /// ```
+    pub fn with_grant(
+        mut self,
+        user: impl Into<String>,
+        table: impl Into<TableReference>,
+        accesses: impl IntoIterator<Item = TableAccess>,
+    ) -> Self {
+        self.grants
+            .entry(user.into())
+            .or_default()
+            .push((table.into(), accesses.into_iter().collect()));
+        self
+    }
+
+    fn grants(&self, identity: Option<&Identity>) -> &[(TableReference, HashSet<TableAccess>)] {
+        identity
+            .and_then(|identity| self.grants.get(&identity.user))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+}
+
+impl AccessPolicy for MemoryAccessPolicy {
+    fn allow_table(
+        &self,
+        identity: Option<&Identity>,
+        table: &TableReference,
+        access: TableAccess,
+    ) -> bool {
+        self.grants(identity).iter().any(|(grant, accesses)| {
+            accesses.contains(&access)
+                && table_matches(table, grant.catalog(), grant.schema(), grant.table())
+        })
+    }
+
+    fn allow_catalog(&self, identity: Option<&Identity>, catalog: &str) -> bool {
+        self.grants(identity)
+            .iter()
+            .any(|(grant, _)| grant.catalog().is_none_or(|c| c == catalog))
+    }
+
+    fn allow_schema(&self, identity: Option<&Identity>, catalog: &str, schema: &str) -> bool {
+        self.grants(identity).iter().any(|(grant, _)| {
+            grant.catalog().is_none_or(|c| c == catalog)
+                && grant.schema().is_none_or(|s| s == schema)
+        })
+    }
+}
+
+/// Returns the tables accessed by the plan, including its subqueries, as
+/// referenced in the plan.
+///
+/// Fails with PermissionDenied for the statements whose accesses cannot be
+/// checked table by table, such as dropping a schema with its tables or
+/// describing a table, which the plan does not name.
+pub(crate) fn table_accesses(
+    plan: &LogicalPlan,
+) -> DataFusionResult<Vec<(TableReference, TableAccess)>> {
+    let mut accesses = vec![];
+    plan.apply_with_subqueries(|plan| {
+        let access = match plan {
+            LogicalPlan::TableScan(scan) => Some((&scan.table_name, TableAccess::Read)),
+            LogicalPlan::Dml(dml) => {
+                let access = match dml.op {
+                    WriteOp::Insert(_) => TableAccess::Insert,
+                    WriteOp::Update => TableAccess::Update,
+                    WriteOp::Delete | WriteOp::Truncate => TableAccess::Delete,
+                    WriteOp::Ctas => TableAccess::Create,
+                };
+                Some((&dml.table_name, access))
+            }
+            LogicalPlan::Ddl(ddl) => match ddl {
+                DdlStatement::CreateExternalTable(create) => {
+                    Some((&create.name, TableAccess::Create))
+                }
+                DdlStatement::CreateMemoryTable(create) => {
+                    Some((&create.name, TableAccess::Create))
+                }
+                DdlStatement::CreateView(create) => Some((&create.name, TableAccess::Create)),
+                DdlStatement::DropTable(drop) => Some((&drop.name, TableAccess::Drop)),
+                DdlStatement::DropView(drop) => Some((&drop.name, TableAccess::Drop)),
+                DdlStatement::CreateCatalogSchema(_)
+                | DdlStatement::CreateCatalog(_)
+                | DdlStatement::DropCatalogSchema(_)
+                | DdlStatement::CreateIndex(_)
+                | DdlStatement::CreateFunction(_)
+                | DdlStatement::DropFunction(_) => return Err(unchecked(ddl.name())),
+            },
+            LogicalPlan::DescribeTable(_) => return Err(unchecked("DescribeTable")),
+            _ => None,
+        };
+        if let Some((table, access)) = access {
+            accesses.push((table.clone(), access));
+        }
+        Ok(TreeNodeRecursion::Continue)
+    })?;
+    Ok(accesses)
+}
+
+fn unchecked(statement: &str) -> DataFusionError {
+    DataFusionError::External(Box::new(Status::permission_denied(format!(
+        "{statement} is not allowed with an access policy"
+    ))))
+}
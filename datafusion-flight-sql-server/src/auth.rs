@@ -0,0 +1,232 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use base64::{prelude::BASE64_STANDARD, Engine as _};
+use ring::{hmac, rand::SystemRandom};
+use tonic::{metadata::MetadataMap, Status};
+
+type Result<T, E = Status> = std::result::Result<T, E>;
+
+/// The identity of an authenticated client.
+///
+/// FlightSqlService inserts it in the extensions of the requests, where
+/// [`SessionStateProvider::new_context`](crate::session::SessionStateProvider::new_context)
+/// can read it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Identity {
+    /// The name of the authenticated user.
+    pub user: String,
+    /// Additional attributes of the user, such as its roles or tenant.
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl Identity {
+    pub fn new(user: impl Into<String>) -> Self {
+        Self {
+            user: user.into(),
+            attributes: BTreeMap::new(),
+        }
+    }
+
+    /// Sets an attribute of the user.
+    pub fn with_attribute(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.attributes.insert(key.into(), value.into());
+        self
+    }
+
+    /// Returns the value of an attribute of the user.
+    pub fn attribute(&self, key: &str) -> Option<&str> {
+        self.attributes.get(key).map(String::as_str)
+    }
+}
+
+// Authenticator is a trait used to authenticate the clients of the service.
+//
+// Clients authenticate with Basic credentials through Handshake, which
+// returns a bearer token, and then send that token with every request.
+#[async_trait]
+pub trait Authenticator: Sync + Send {
+    /// Verifies the credentials sent to Handshake.
+    async fn authenticate_basic(&self, username: &str, password: &str) -> Result<Identity>;
+
+    /// Issues the bearer token Handshake returns for the identity.
+    async fn issue_token(&self, identity: &Identity) -> Result<String>;
+
+    /// Verifies a bearer token sent with a request.
+    async fn authenticate_token(&self, token: &str) -> Result<Identity>;
+}
+
+#[async_trait]
+impl<T: Authenticator + ?Sized> Authenticator for Arc<T> {
+    async fn authenticate_basic(&self, username: &str, password: &str) -> Result<Identity> {
+        self.as_ref().authenticate_basic(username, password).await
+    }
+
+    async fn issue_token(&self, identity: &Identity) -> Result<String> {
+        self.as_ref().issue_token(identity).await
+    }
+
+    async fn authenticate_token(&self, token: &str) -> Result<Identity> {
+        self.as_ref().authenticate_token(token).await
+    }
+}
+
+/// How long the tokens issued by a [`MemoryAuthenticator`] are valid by
+/// default.
+const DEFAULT_TOKEN_TTL: Duration = Duration::from_secs(60 * 60);
+
+// MemoryAuthenticator is an Authenticator holding users and tokens in memory.
+// Tokens issued by Handshake are random and expire after the token TTL, while
+// the tokens added with `with_token` are valid until the service stops.
+#[derive(Debug)]
+pub struct MemoryAuthenticator {
+    /// The HMAC of the password and the identity, by username. Passwords are
+    /// verified by comparing HMACs, which takes constant time.
+    users: HashMap<String, (hmac::Tag, Identity)>,
+    /// Keys the HMACs of the passwords
+    key: hmac::Key,
+    tokens: RwLock<HashMap<String, Token>>,
+    token_ttl: Duration,
+}
+
+#[derive(Debug)]
+struct Token {
+    identity: Identity,
+    /// When the token expires, or None when it was added with `with_token`
+    expires: Option<Instant>,
+}
+
+impl Default for MemoryAuthenticator {
+    fn default() -> Self {
+        Self {
+            users: HashMap::new(),
+            key: hmac::Key::generate(hmac::HMAC_SHA256, &SystemRandom::new())
+                .expect("Failed to generate a random key"),
+            tokens: RwLock::default(),
+            token_ttl: DEFAULT_TOKEN_TTL,
+        }
+    }
+}
+
+impl MemoryAuthenticator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how long the tokens issued by Handshake are valid, one hour by
+    /// default. Expired tokens are removed as new ones are issued.
+    pub fn with_token_ttl(self, token_ttl: Duration) -> Self {
+        Self { token_ttl, ..self }
+    }
+
+    /// Adds a user authenticating with a password.
+    pub fn with_user(self, username: impl Into<String>, password: impl Into<String>) -> Self {
+        let username = username.into();
+        let identity = Identity::new(username.clone());
+        self.with_user_identity(username, password, identity)
+    }
+
+    /// Adds a user authenticating with a password as the identity.
+    pub fn with_user_identity(
+        mut self,
+        username: impl Into<String>,
+        password: impl Into<String>,
+        identity: Identity,
+    ) -> Self {
+        let password = hmac::sign(&self.key, password.into().as_bytes());
+        self.users.insert(username.into(), (password, identity));
+        self
+    }
+
+    /// Adds a bearer token authenticating the identity, which never expires.
+    pub fn with_token(self, token: impl Into<String>, identity: Identity) -> Self {
+        let token_entry = Token {
+            identity,
+            expires: None,
+        };
+        self.tokens
+            .write()
+            .unwrap()
+            .insert(token.into(), token_entry);
+        self
+    }
+}
+
+impl Token {
+    fn is_expired(&self) -> bool {
+        self.expires
+            .is_some_and(|expires| expires <= Instant::now())
+    }
+}
+
+#[async_trait]
+impl Authenticator for MemoryAuthenticator {
+    async fn authenticate_basic(&self, username: &str, password: &str) -> Result<Identity> {
+        match self.users.get(username) {
+            Some((expected, identity))
+                if hmac::verify(&self.key, password.as_bytes(), expected.as_ref()).is_ok() =>
+            {
+                Ok(identity.clone())
+            }
+            _ => Err(Status::unauthenticated("Invalid username or password")),
+        }
+    }
+
+    async fn issue_token(&self, identity: &Identity) -> Result<String> {
+        let token = uuid::Uuid::new_v4().to_string();
+        let mut tokens = self.tokens.write().unwrap();
+        tokens.retain(|_, token| !token.is_expired());
+        tokens.insert(
+            token.clone(),
+            Token {
+                identity: identity.clone(),
+                expires: Some(Instant::now() + self.token_ttl),
+            },
+        );
+        Ok(token)
+    }
+
+    async fn authenticate_token(&self, token: &str) -> Result<Identity> {
+        self.tokens
+            .read()
+            .unwrap()
+            .get(token)
+            .filter(|token| !token.is_expired())
+            .map(|token| token.identity.clone())
+            .ok_or_else(|| Status::unauthenticated("Invalid bearer token"))
+    }
+}
+
+/// Returns the username and password of the Basic authorization header.
+pub(crate) fn basic_credentials(metadata: &MetadataMap) -> Result<(String, String)> {
+    let credentials = authorization(metadata, "Basic ")?;
+    let credentials = BASE64_STANDARD
+        .decode(credentials)
+        .ok()
+        .and_then(|credentials| String::from_utf8(credentials).ok())
+        .ok_or_else(|| Status::unauthenticated("Invalid Basic credentials encoding"))?;
+    let (username, password) = credentials
+        .split_once(':')
+        .ok_or_else(|| Status::unauthenticated("Invalid Basic credentials"))?;
+    Ok((username.to_string(), password.to_string()))
+}
+
+/// Returns the token of the Bearer authorization header.
+pub(crate) fn bearer_token(metadata: &MetadataMap) -> Result<&str> {
+    authorization(metadata, "Bearer ")
+}
+
+fn authorization<'a>(metadata: &'a MetadataMap, scheme: &str) -> Result<&'a str> {
+    let authorization = metadata
+        .get("authorization")
+        .ok_or_else(|| Status::unauthenticated("No authorization provided"))?
+        .to_str()
+        .map_err(|_| Status::unauthenticated("Invalid authorization header encoding"))?;
+    authorization.strip_prefix(scheme).ok_or_else(|| {
+        Status::unauthenticated(format!("Expected {} authorization", scheme.trim_end()))
+    })
+}
@@ -0,0 +1,455 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use datafusion::{
+    arrow::{
+        array::RecordBatch,
+        datatypes::SchemaRef,
+        ipc::{reader::FileReader, writer::FileWriter},
+    },
+    error::{DataFusionError, Result},
+    execution::context::SessionState,
+    logical_expr::LogicalPlan,
+    physical_plan::{stream::RecordBatchStreamAdapter, SendableRecordBatchStream},
+};
+use futures::StreamExt;
+use log::warn;
+use prost::bytes::Bytes;
+
+use super::auth::Identity;
+use super::substrait::serialize_plan;
+
+/// A bounded cache of query results, enabled with
+/// [`FlightSqlService::with_result_cache`], which DoGet serves the queries
+/// from when their results were fetched before.
+///
+/// Results are keyed by the resolved plan serialized as Substrait, the
+/// settings of the session, the partition fetched, the identity and the
+/// session of the client, the scope of the client supplied by
+/// [`SessionStateProvider::cache_scope`] and the version of the catalog,
+/// which changes whenever the service executes DML or DDL, or commits a
+/// transaction. Plans that Substrait cannot represent are not cached.
+/// Changes made to the catalog outside of the service must be signalled
+/// with [`ResultCache::invalidate`]. Results expire after the TTL, such as
+/// for queries calling `now()`.
+///
+/// Least recently used results are spilled to disk once the memory budget
+/// is exceeded, when spilling is enabled, and evicted otherwise. Results
+/// larger than both budgets together are not cached.
+///
+/// [`SessionStateProvider::cache_scope`]: crate::session::SessionStateProvider::cache_scope
+/// [`FlightSqlService::with_result_cache`]: crate::service::FlightSqlService::with_result_cache
+#[derive(Debug)]
+pub struct ResultCache {
+    ttl: Duration,
+    max_memory_size: usize,
+    spill: Option<Spill>,
+    version: AtomicU64,
+    entries: Mutex<Entries>,
+}
+
+#[derive(Debug)]
+struct Spill {
+    dir: PathBuf,
+    max_disk_size: usize,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    entries: HashMap<CacheKey, Entry>,
+    memory_size: usize,
+    disk_size: usize,
+    /// Incremented on every access, to find the least recently used entries
+    tick: u64,
+}
+
+/// Identifies the results of a query.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct CacheKey {
+    /// The plan serialized as Substrait
+    plan: Bytes,
+    /// The settings of the session, such as its time zone
+    options: Vec<(String, Option<String>)>,
+    partition: Option<u32>,
+    identity: Option<Identity>,
+    /// The server-side session, whose tables and views are its own
+    session_id: Option<String>,
+    scope: Option<String>,
+    version: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    schema: SchemaRef,
+    data: Data,
+    size: usize,
+    created: Instant,
+    last_used: u64,
+}
+
+#[derive(Debug)]
+enum Data {
+    Memory(Vec<RecordBatch>),
+    /// An Arrow IPC file in the spill directory
+    Disk(PathBuf),
+}
+
+impl ResultCache {
+    /// Creates a cache keeping up to `max_memory_size` bytes of results in
+    /// memory, each for up to `ttl`.
+    pub fn new(max_memory_size: usize, ttl: Duration) -> Self {
+        Self {
+            ttl,
+            max_memory_size,
+            spill: None,
+            version: AtomicU64::new(0),
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    /// Spills the results exceeding the memory budget to Arrow IPC files in
+    /// `dir`, up to `max_disk_size` bytes.
+    pub fn with_spill(mut self, dir: impl Into<PathBuf>, max_disk_size: usize) -> Self {
+        self.spill = Some(Spill {
+            dir: dir.into(),
+            max_disk_size,
+        });
+        self
+    }
+
+    /// Returns the version of the catalog the cached results were computed
+    /// with.
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::SeqCst)
+    }
+
+    /// Discards every cached result and moves to a new catalog version, so
+    /// that the queries still executing are not cached either.
+    pub fn invalidate(&self) {
+        self.version.fetch_add(1, Ordering::SeqCst);
+        let mut entries = self.entries.lock().unwrap();
+        for (_, entry) in entries.entries.drain() {
+            remove_spill_file(&entry.data);
+        }
+        entries.memory_size = 0;
+        entries.disk_size = 0;
+    }
+
+    /// Returns the key of the results of the partition of the plan, or of
+    /// every partition when None, for the client, or an error when the plan
+    /// cannot be serialized.
+    pub(crate) fn key(
+        &self,
+        state: &SessionState,
+        plan: &LogicalPlan,
+        partition: Option<u32>,
+        identity: Option<&Identity>,
+        session_id: Option<&str>,
+        scope: Option<&str>,
+    ) -> Result<CacheKey> {
+        let options = state
+            .config_options()
+            .entries()
+            .into_iter()
+            .map(|entry| (entry.key, entry.value))
+            .collect();
+        Ok(CacheKey {
+            plan: serialize_plan(state, plan)?,
+            options,
+            partition,
+            identity: identity.cloned(),
+            session_id: session_id.map(str::to_string),
+            scope: scope.map(str::to_string),
+            version: self.version(),
+        })
+    }
+
+    /// Returns the cached results, unless they expired or their spilled
+    /// file can no longer be read.
+    pub(crate) fn get(&self, key: &CacheKey) -> Option<SendableRecordBatchStream> {
+        let (schema, batches) = {
+            let mut entries = self.entries.lock().unwrap();
+            entries.tick += 1;
+            let tick = entries.tick;
+            let entry = entries.entries.get_mut(key)?;
+            if entry.created.elapsed() >= self.ttl {
+                entries.remove(key);
+                return None;
+            }
+            entry.last_used = tick;
+            let batches = match &entry.data {
+                Data::Memory(batches) => Ok(batches.clone()),
+                Data::Disk(path) => Err(path.clone()),
+            };
+            (entry.schema.clone(), batches)
+        };
+
+        let batches = match batches {
+            Ok(batches) => batches,
+            Err(path) => match read_spill_file(&path) {
+                Ok(batches) => batches,
+                Err(err) => {
+                    warn!("Failed to read spilled results {}: {err}", path.display());
+                    let mut entries = self.entries.lock().unwrap();
+                    // Unless the entry was replaced in the meantime
+                    if matches!(entries.entries.get(key), Some(entry) if entry.data.is_file(&path))
+                    {
+                        entries.remove(key);
+                    }
+                    return None;
+                }
+            },
+        };
+        let stream = futures::stream::iter(batches.into_iter().map(Ok));
+        Some(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
+    }
+
+    /// Caches the results of the stream once it completes successfully.
+    /// Results stop being recorded once they exceed the budgets of the
+    /// cache.
+    pub(crate) fn record(
+        self: &Arc<Self>,
+        key: CacheKey,
+        stream: SendableRecordBatchStream,
+    ) -> SendableRecordBatchStream {
+        let schema = stream.schema();
+        let capacity =
+            self.max_memory_size + self.spill.as_ref().map_or(0, |spill| spill.max_disk_size);
+        let recorded = Arc::new(Mutex::new(Some((vec![], 0))));
+
+        let cache = self.clone();
+        let results = recorded.clone();
+        let stream = stream
+            .inspect(move |batch| {
+                let mut recorded = recorded.lock().unwrap();
+                let Some((batches, size)) = recorded.as_mut() else {
+                    return;
+                };
+                match batch {
+                    Ok(batch) => {
+                        *size += batch.get_array_memory_size();
+                        batches.push(batch.clone());
+                        if *size > capacity {
+                            *recorded = None;
+                        }
+                    }
+                    Err(_) => *recorded = None,
+                }
+            })
+            .chain(
+                futures::stream::once({
+                    let schema = schema.clone();
+                    async move {
+                        let results = results.lock().unwrap().take();
+                        if let Some((batches, _)) = results {
+                            cache.insert(key, schema, batches).await;
+                        }
+                        None
+                    }
+                })
+                .filter_map(futures::future::ready),
+            );
+        Box::pin(RecordBatchStreamAdapter::new(schema, stream))
+    }
+
+    async fn insert(&self, key: CacheKey, schema: SchemaRef, batches: Vec<RecordBatch>) {
+        // The catalog changed while the query was executing
+        if key.version != self.version() {
+            return;
+        }
+        let size = batches.iter().map(RecordBatch::get_array_memory_size).sum();
+
+        let victims = {
+            let mut entries = self.entries.lock().unwrap();
+            let expired: Vec<_> = entries
+                .entries
+                .iter()
+                .filter(|(_, entry)| entry.created.elapsed() >= self.ttl)
+                .map(|(key, _)| key.clone())
+                .collect();
+            for key in &expired {
+                entries.remove(key);
+            }
+
+            entries.remove(&key);
+            entries.tick += 1;
+            let entry = Entry {
+                schema,
+                data: Data::Memory(batches),
+                size,
+                created: Instant::now(),
+                last_used: entries.tick,
+            };
+            entries.memory_size += size;
+            entries.entries.insert(key, entry);
+
+            self.shrink(&mut entries)
+        };
+
+        // The results are written without holding the lock, and are missing
+        // from the cache meanwhile
+        if let Some(spill) = &self.spill {
+            for (key, entry) in victims {
+                self.spill(spill, key, entry).await;
+            }
+        }
+    }
+
+    /// Evicts the least recently used results until the cache fits its
+    /// budgets, and returns the results taken out of memory to be spilled.
+    fn shrink(&self, entries: &mut Entries) -> Vec<(CacheKey, Entry)> {
+        let mut victims = vec![];
+        while entries.memory_size > self.max_memory_size {
+            let Some(key) = entries.least_recently_used(|data| matches!(data, Data::Memory(_)))
+            else {
+                break;
+            };
+            match &self.spill {
+                Some(_) => victims.extend(entries.take(&key).map(|entry| (key, entry))),
+                None => entries.remove(&key),
+            }
+        }
+        self.shrink_disk(entries);
+        victims
+    }
+
+    /// Evicts the least recently used spilled results until they fit the
+    /// disk budget.
+    fn shrink_disk(&self, entries: &mut Entries) {
+        let Some(spill) = &self.spill else {
+            return;
+        };
+        while entries.disk_size > spill.max_disk_size {
+            let Some(key) = entries.least_recently_used(|data| matches!(data, Data::Disk(_)))
+            else {
+                break;
+            };
+            entries.remove(&key);
+        }
+    }
+
+    /// Writes the results taken out of memory to a file in the spill
+    /// directory, and puts them back in the cache as spilled. The results are
+    /// evicted when they cannot be written, or were invalidated or replaced
+    /// while being written.
+    async fn spill(&self, spill: &Spill, key: CacheKey, entry: Entry) {
+        let Data::Memory(batches) = entry.data else {
+            return;
+        };
+        let path = spill.dir.join(format!("{}.arrow", uuid::Uuid::new_v4()));
+        let written = tokio::task::spawn_blocking({
+            let path = path.clone();
+            let schema = entry.schema.clone();
+            move || write_spill_file(&path, &schema, &batches)
+        })
+        .await
+        .map_err(|err| DataFusionError::External(Box::new(err)))
+        .and_then(|written| written);
+        let size = match written {
+            Ok(size) => size,
+            Err(err) => {
+                warn!(
+                    "Failed to spill cached results to {}: {err}",
+                    path.display()
+                );
+                let _ = std::fs::remove_file(&path);
+                return;
+            }
+        };
+
+        let data = Data::Disk(path);
+        let mut entries = self.entries.lock().unwrap();
+        if key.version != self.version() || entries.entries.contains_key(&key) {
+            drop(entries);
+            remove_spill_file(&data);
+            return;
+        }
+        entries.disk_size += size;
+        entries.entries.insert(
+            key,
+            Entry {
+                data,
+                size,
+                ..entry
+            },
+        );
+        self.shrink_disk(&mut entries);
+    }
+}
+
+impl Drop for ResultCache {
+    fn drop(&mut self) {
+        let entries = self.entries.get_mut().unwrap();
+        for entry in entries.entries.values() {
+            remove_spill_file(&entry.data);
+        }
+    }
+}
+
+impl Entries {
+    fn least_recently_used(&self, filter: impl Fn(&Data) -> bool) -> Option<CacheKey> {
+        self.entries
+            .iter()
+            .filter(|(_, entry)| filter(&entry.data))
+            .min_by_key(|(_, entry)| entry.last_used)
+            .map(|(key, _)| key.clone())
+    }
+
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some(entry) = self.take(key) {
+            remove_spill_file(&entry.data);
+        }
+    }
+
+    /// Removes the results from the cache without removing their spilled
+    /// file, if any.
+    fn take(&mut self, key: &CacheKey) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        match &entry.data {
+            Data::Memory(_) => self.memory_size -= entry.size,
+            Data::Disk(_) => self.disk_size -= entry.size,
+        }
+        Some(entry)
+    }
+}
+
+impl Data {
+    /// Returns true if the results are spilled to the file.
+    fn is_file(&self, file: &PathBuf) -> bool {
+        matches!(self, Data::Disk(path) if path == file)
+    }
+}
+
+fn read_spill_file(path: &PathBuf) -> Result<Vec<RecordBatch>> {
+    let reader = FileReader::try_new(File::open(path)?, None)?;
+    Ok(reader.collect::<std::result::Result<Vec<_>, _>>()?)
+}
+
+/// Writes the batches to an Arrow IPC file and returns its size.
+fn write_spill_file(path: &PathBuf, schema: &SchemaRef, batches: &[RecordBatch]) -> Result<usize> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut writer = FileWriter::try_new(File::create(path)?, schema)?;
+    for batch in batches {
+        writer.write(batch)?;
+    }
+    writer.finish()?;
+    Ok(std::fs::metadata(path)?.len() as usize)
+}
+
+fn remove_spill_file(data: &Data) {
+    if let Data::Disk(path) = data {
+        if let Err(err) = std::fs::remove_file(path) {
+            warn!("Failed to remove spilled results {}: {err}", path.display());
+        }
+    }
+}
@@ -1,8 +1,35 @@
+use std::sync::Arc;
+
+use arrow_flight::sql::metadata::SqlInfoDataBuilder;
+
+/// A function adding or overriding entries of the [`SqlInfoDataBuilder`]
+/// used to answer `CommandGetSqlInfo` requests.
+pub type SqlInfoOverride = Arc<dyn Fn(&mut SqlInfoDataBuilder) + Send + Sync>;
+
 #[derive(Default)]
 pub struct FlightSqlServiceConfig {
     /// When true, includes table names in field metadata under the "table_name" key.
     /// This allows clients to identify the source table or alias for each column in query results.
     pub schema_with_metadata: bool,
+    /// When true, query FlightInfos have one endpoint per output partition of
+    /// the physical plan, which clients can fetch in parallel, instead of a
+    /// single endpoint executing the whole plan. Plans whose partitions share
+    /// their inputs, such as aggregates and joins, still have a single
+    /// endpoint, as each endpoint would compute those inputs again.
+    pub partitioned_endpoints: bool,
+    /// When true, query tickets carry the plan resolved by GetFlightInfo,
+    /// serialized as Substrait, which DoGet executes without planning the
+    /// query again. DoGet fails when the schema of the plan no longer matches
+    /// the schema of the FlightInfo, such as after the catalog changed.
+    /// Plans that Substrait cannot represent are planned again by DoGet.
+    ///
+    /// As DoGet neither plans nor rewrites the plans of tickets again, they
+    /// must be signed: GetFlightInfo fails without a TicketSigner, and only
+    /// services with this setting, or with workers, accept such tickets.
+    pub plan_in_ticket: bool,
+    /// Applied in order on top of the default SqlInfo entries derived from the
+    /// session state.
+    pub sql_info: Vec<SqlInfoOverride>,
 }
 
 impl FlightSqlServiceConfig {
@@ -11,4 +38,40 @@ impl FlightSqlServiceConfig {
             ..Default::default()
         }
     }
+
+    /// Sets [`FlightSqlServiceConfig::schema_with_metadata`].
+    pub fn with_schema_metadata(mut self, schema_with_metadata: bool) -> Self {
+        self.schema_with_metadata = schema_with_metadata;
+        self
+    }
+
+    /// Sets [`FlightSqlServiceConfig::partitioned_endpoints`].
+    pub fn with_partitioned_endpoints(mut self, partitioned_endpoints: bool) -> Self {
+        self.partitioned_endpoints = partitioned_endpoints;
+        self
+    }
+
+    /// Sets [`FlightSqlServiceConfig::plan_in_ticket`].
+    pub fn with_plan_in_ticket(mut self, plan_in_ticket: bool) -> Self {
+        self.plan_in_ticket = plan_in_ticket;
+        self
+    }
+
+    /// Registers a function that adds or overrides SqlInfo entries.
+    ///
+    /// ```
+    /// # use arrow_flight::sql::SqlInfo;
+    /// # use datafusion_flight_sql_server::config::FlightSqlServiceConfig;
+    /// let config = FlightSqlServiceConfig::new().with_sql_info(|builder| {
+    ///     builder.append(SqlInfo::FlightSqlServerName, "my-server");
+    ///     builder.append(SqlInfo::FlightSqlServerReadOnly, true);
+    /// });
+    /// ```
+    pub fn with_sql_info(
+        mut self,
+        sql_info: impl Fn(&mut SqlInfoDataBuilder) + Send + Sync + 'static,
+    ) -> Self {
+        self.sql_info.push(Arc::new(sql_info));
+        self
+    }
 }
@@ -0,0 +1,134 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        RwLock,
+    },
+    time::Duration,
+};
+
+use arrow_flight::{
+    client::FlightClient, error::FlightError, CancelFlightInfoRequest, CancelStatus, FlightInfo,
+};
+use tonic::{metadata::MetadataMap, transport::Endpoint};
+
+/// How long the coordinator waits for a worker to answer CancelFlightInfo.
+const CANCEL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The worker [`FlightSqlService`]s executing the queries planned by a
+/// coordinator FlightSqlService, registered with
+/// [`FlightSqlService::with_workers`].
+///
+/// The coordinator plans and verifies each query, then hands out one
+/// endpoint per output partition of its physical plan, whose location is a
+/// worker picked in turn and whose ticket carries the plan serialized as
+/// Substrait. Workers are FlightSqlServices with the same catalog and the
+/// same [`TicketSigner`] keys as the coordinator, configured with
+/// [`FlightSqlServiceConfig::plan_in_ticket`], each executing the partition
+/// of its tickets. As tickets are bound to the identity of the client, the
+/// workers must authenticate the clients as the coordinator does.
+///
+/// Tickets carry the whole logical plan rather than a fragment of the
+/// physical plan: each worker plans the query again and executes a single
+/// partition of it. Queries are thus only split when their partitions are
+/// computed independently of each other, such as scans, filters and
+/// projections, while aggregates, joins and sorts, whose partitions depend
+/// on each other, are executed by a single worker in one endpoint. A worker
+/// whose physical plan has another partition count than the coordinator's
+/// fails the DoGet with `FailedPrecondition` rather than returning a part of
+/// the rows. Queries whose plan Substrait cannot represent are executed by
+/// the coordinator.
+///
+/// The workers track the queries they execute. CancelFlightInfo sent to the
+/// coordinator is forwarded, with the authorization header of the client, to
+/// the registered workers of the endpoints, and answers `Cancelled` when any
+/// of them cancelled a query.
+///
+/// [`FlightSqlService`]: crate::service::FlightSqlService
+/// [`TicketSigner`]: crate::signing::TicketSigner
+/// [`FlightSqlServiceConfig::plan_in_ticket`]: crate::config::FlightSqlServiceConfig::plan_in_ticket
+/// [`FlightSqlService::with_workers`]: crate::service::FlightSqlService::with_workers
+#[derive(Debug, Default)]
+pub struct Workers {
+    locations: RwLock<Vec<String>>,
+    next: AtomicUsize,
+}
+
+impl Workers {
+    /// Creates a registry of the workers at the URIs, such as
+    /// `grpc://worker-1:50051`.
+    pub fn new(uris: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            locations: RwLock::new(uris.into_iter().map(Into::into).collect()),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Registers the worker at the URI, unless it is already registered.
+    pub fn register(&self, uri: impl Into<String>) {
+        let uri = uri.into();
+        let mut locations = self.locations.write().unwrap();
+        if !locations.contains(&uri) {
+            locations.push(uri);
+        }
+    }
+
+    /// Deregisters the worker at the URI, and returns true if it was
+    /// registered. Queries already planned keep their endpoints on it.
+    pub fn deregister(&self, uri: &str) -> bool {
+        let mut locations = self.locations.write().unwrap();
+        let len = locations.len();
+        locations.retain(|location| location != uri);
+        locations.len() != len
+    }
+
+    /// Returns the URIs of the registered workers.
+    pub fn locations(&self) -> Vec<String> {
+        self.locations.read().unwrap().clone()
+    }
+
+    /// Returns the URI of the next worker, round-robin, or None when no
+    /// worker is registered.
+    pub(crate) fn next(&self) -> Option<String> {
+        let locations = self.locations.read().unwrap();
+        if locations.is_empty() {
+            return None;
+        }
+        let next = self.next.fetch_add(1, Ordering::Relaxed);
+        Some(locations[next % locations.len()].clone())
+    }
+}
+
+/// Sends CancelFlightInfo to the worker at the URI on behalf of the client,
+/// whose authorization header is forwarded.
+pub(crate) async fn cancel_flight_info(
+    uri: &str,
+    info: &FlightInfo,
+    metadata: &MetadataMap,
+) -> Result<CancelStatus, FlightError> {
+    // Flight locations use the grpc schemes, which tonic does not know of
+    let uri = match uri.split_once("://") {
+        Some(("grpc" | "grpc+tcp", rest)) => format!("http://{rest}"),
+        _ => uri.to_string(),
+    };
+    let channel = Endpoint::new(uri)
+        .map(|endpoint| {
+            endpoint
+                .connect_timeout(CANCEL_TIMEOUT)
+                .timeout(CANCEL_TIMEOUT)
+        })
+        .map_err(|e| FlightError::ExternalError(Box::new(e)))?
+        .connect()
+        .await
+        .map_err(|e| FlightError::ExternalError(Box::new(e)))?;
+    let mut client = FlightClient::new(channel);
+    if let Some(authorization) = metadata
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+    {
+        client.add_header("authorization", authorization)?;
+    }
+    let result = client
+        .cancel_flight_info(CancelFlightInfoRequest::new(info.clone()))
+        .await?;
+    Ok(result.status())
+}
@@ -0,0 +1,344 @@
+use std::sync::{Arc, RwLock};
+
+use arrow_flight::sql::UpdateDeleteRules;
+use async_trait::async_trait;
+use datafusion::{
+    arrow::{
+        array::{Int32Builder, RecordBatch, StringBuilder, UInt8Builder},
+        datatypes::{DataType, Field, Schema, SchemaRef},
+        error::ArrowError,
+    },
+    common::TableReference,
+    execution::context::SessionState,
+};
+use log::warn;
+use once_cell::sync::Lazy;
+use tonic::Status;
+
+type Result<T, E = Status> = std::result::Result<T, E>;
+
+/// The field metadata key [`MemoryForeignKeyProvider::with_table_metadata`]
+/// reads foreign keys from.
+///
+/// The value names the referenced column as `[[catalog.]schema.]table.column`,
+/// where a missing catalog or schema is taken from the referencing table.
+pub const FOREIGN_KEY_METADATA_KEY: &str = "foreign_key";
+
+/// A foreign key from the `fk_columns` of `fk_table` to the `pk_columns` of
+/// `pk_table`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForeignKey {
+    /// The referenced table.
+    pub pk_table: TableReference,
+    /// The referenced columns, in key order.
+    pub pk_columns: Vec<String>,
+    /// The name of the referenced key, if any.
+    pub pk_key_name: Option<String>,
+    /// The referencing table.
+    pub fk_table: TableReference,
+    /// The referencing columns, in the order of `pk_columns`.
+    pub fk_columns: Vec<String>,
+    /// The name of the foreign key, if any.
+    pub fk_key_name: Option<String>,
+    pub update_rule: UpdateDeleteRules,
+    pub delete_rule: UpdateDeleteRules,
+}
+
+impl ForeignKey {
+    /// Creates an unnamed foreign key with `NO ACTION` update and delete rules.
+    ///
+    /// Table references that are not fully qualified are resolved against the
+    /// default catalog and schema of the session answering the request.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key has not as many `fk_columns` as `pk_columns`.
+    ///
+    /// ```
+    /// # use datafusion_flight_sql_server::keys::ForeignKey;
+    /// let key = ForeignKey::new("orders", ["user_id"], "users", ["id"]).with_name("fk_orders_user");
+    /// ```
+    pub fn new(
+        fk_table: impl Into<TableReference>,
+        fk_columns: impl IntoIterator<Item = impl Into<String>>,
+        pk_table: impl Into<TableReference>,
+        pk_columns: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        let key = Self {
+            pk_table: pk_table.into(),
+            pk_columns: pk_columns.into_iter().map(Into::into).collect(),
+            pk_key_name: None,
+            fk_table: fk_table.into(),
+            fk_columns: fk_columns.into_iter().map(Into::into).collect(),
+            fk_key_name: None,
+            update_rule: UpdateDeleteRules::NoAction,
+            delete_rule: UpdateDeleteRules::NoAction,
+        };
+        assert!(
+            key.has_matching_columns(),
+            "Foreign key {} references {} columns with {} columns",
+            key.fk_table,
+            key.pk_columns.len(),
+            key.fk_columns.len()
+        );
+        key
+    }
+
+    /// Returns true if the key has as many `fk_columns` as `pk_columns`.
+    fn has_matching_columns(&self) -> bool {
+        self.pk_columns.len() == self.fk_columns.len()
+    }
+
+    /// Sets the name of the foreign key.
+    pub fn with_name(self, name: impl Into<String>) -> Self {
+        Self {
+            fk_key_name: Some(name.into()),
+            ..self
+        }
+    }
+
+    /// Sets the name of the referenced key.
+    pub fn with_pk_name(self, name: impl Into<String>) -> Self {
+        Self {
+            pk_key_name: Some(name.into()),
+            ..self
+        }
+    }
+
+    pub fn with_update_rule(self, update_rule: UpdateDeleteRules) -> Self {
+        Self {
+            update_rule,
+            ..self
+        }
+    }
+
+    pub fn with_delete_rule(self, delete_rule: UpdateDeleteRules) -> Self {
+        Self {
+            delete_rule,
+            ..self
+        }
+    }
+
+    /// Returns the key with both table references fully qualified.
+    pub(crate) fn resolve(&self, default_catalog: &str, default_schema: &str) -> Self {
+        let resolve = |table: &TableReference| {
+            let resolved = table.clone().resolve(default_catalog, default_schema);
+            TableReference::full(resolved.catalog, resolved.schema, resolved.table)
+        };
+        Self {
+            pk_table: resolve(&self.pk_table),
+            fk_table: resolve(&self.fk_table),
+            ..self.clone()
+        }
+    }
+}
+
+// ForeignKeyProvider is a trait used to provide the foreign keys served by
+// GetExportedKeys, GetImportedKeys and GetCrossReference, as DataFusion has
+// no notion of foreign keys itself.
+#[async_trait]
+pub trait ForeignKeyProvider: Sync + Send {
+    async fn foreign_keys(&self, state: &SessionState) -> Result<Vec<ForeignKey>>;
+}
+
+#[async_trait]
+impl<T: ForeignKeyProvider + ?Sized> ForeignKeyProvider for Arc<T> {
+    async fn foreign_keys(&self, state: &SessionState) -> Result<Vec<ForeignKey>> {
+        self.as_ref().foreign_keys(state).await
+    }
+}
+
+// MemoryForeignKeyProvider is a ForeignKeyProvider holding keys added
+// programmatically and, optionally, the keys declared in the field metadata
+// of the registered tables.
+#[derive(Debug, Default)]
+pub struct MemoryForeignKeyProvider {
+    keys: RwLock<Vec<ForeignKey>>,
+    table_metadata: bool,
+}
+
+impl MemoryForeignKeyProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a foreign key.
+    pub fn with_foreign_key(self, key: ForeignKey) -> Self {
+        self.add_foreign_key(key);
+        self
+    }
+
+    /// Adds a foreign key to a provider that may already be serving requests.
+    pub fn add_foreign_key(&self, key: ForeignKey) {
+        self.keys.write().unwrap().push(key);
+    }
+
+    /// Also serves a single column foreign key for every field of the
+    /// registered tables carrying [`FOREIGN_KEY_METADATA_KEY`] metadata.
+    pub fn with_table_metadata(self) -> Self {
+        Self {
+            table_metadata: true,
+            ..self
+        }
+    }
+
+    async fn table_metadata_keys(&self, state: &SessionState) -> Result<Vec<ForeignKey>> {
+        let mut keys = vec![];
+        let catalog_list = state.catalog_list();
+        for catalog_name in catalog_list.catalog_names() {
+            let Some(catalog) = catalog_list.catalog(&catalog_name) else {
+                continue;
+            };
+            for schema_name in catalog.schema_names() {
+                let Some(schema) = catalog.schema(&schema_name) else {
+                    continue;
+                };
+                for table_name in schema.table_names() {
+                    let Some(table) = schema
+                        .table(&table_name)
+                        .await
+                        .map_err(|e| Status::internal(format!("{e:?}")))?
+                    else {
+                        continue;
+                    };
+                    for field in table.schema().fields() {
+                        let Some(target) = field.metadata().get(FOREIGN_KEY_METADATA_KEY) else {
+                            continue;
+                        };
+                        // A malformed value only loses its own key
+                        let Some((pk_table, pk_column)) = target.rsplit_once('.') else {
+                            warn!(
+                                "Ignoring invalid {FOREIGN_KEY_METADATA_KEY} metadata on {table_name}.{}: {target}",
+                                field.name()
+                            );
+                            continue;
+                        };
+                        let pk_table = TableReference::parse_str(pk_table)
+                            .resolve(&catalog_name, &schema_name);
+                        keys.push(ForeignKey::new(
+                            TableReference::full(
+                                catalog_name.as_str(),
+                                schema_name.as_str(),
+                                table_name.as_str(),
+                            ),
+                            [field.name().as_str()],
+                            TableReference::full(pk_table.catalog, pk_table.schema, pk_table.table),
+                            [pk_column],
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(keys)
+    }
+}
+
+#[async_trait]
+impl ForeignKeyProvider for MemoryForeignKeyProvider {
+    async fn foreign_keys(&self, state: &SessionState) -> Result<Vec<ForeignKey>> {
+        let mut keys = self.keys.read().unwrap().clone();
+        if self.table_metadata {
+            keys.extend(self.table_metadata_keys(state).await?);
+        }
+        Ok(keys)
+    }
+}
+
+/// Returns true if `table` is the table `name`, in `catalog` and `db_schema`
+/// when those are provided.
+pub(crate) fn table_matches(
+    table: &TableReference,
+    catalog: Option<&str>,
+    db_schema: Option<&str>,
+    name: &str,
+) -> bool {
+    table.table() == name
+        && catalog.is_none_or(|catalog| table.catalog() == Some(catalog))
+        && db_schema.is_none_or(|db_schema| table.schema() == Some(db_schema))
+}
+
+/// The schema for GetExportedKeys, GetImportedKeys and GetCrossReference
+pub(crate) static GET_FOREIGN_KEYS_SCHEMA: Lazy<SchemaRef> = Lazy::new(|| {
+    Arc::new(Schema::new(vec![
+        Field::new("pk_catalog_name", DataType::Utf8, true),
+        Field::new("pk_db_schema_name", DataType::Utf8, true),
+        Field::new("pk_table_name", DataType::Utf8, false),
+        Field::new("pk_column_name", DataType::Utf8, false),
+        Field::new("fk_catalog_name", DataType::Utf8, true),
+        Field::new("fk_db_schema_name", DataType::Utf8, true),
+        Field::new("fk_table_name", DataType::Utf8, false),
+        Field::new("fk_column_name", DataType::Utf8, false),
+        Field::new("key_sequence", DataType::Int32, false),
+        Field::new("fk_key_name", DataType::Utf8, true),
+        Field::new("pk_key_name", DataType::Utf8, true),
+        Field::new("update_rule", DataType::UInt8, false),
+        Field::new("delete_rule", DataType::UInt8, false),
+    ]))
+});
+
+/// Builds the rows of GetExportedKeys, GetImportedKeys and GetCrossReference,
+/// one per column of each key.
+pub(crate) fn foreign_keys_batch(keys: &[ForeignKey]) -> Result<RecordBatch, ArrowError> {
+    let mut pk_catalog_name_builder = StringBuilder::new();
+    let mut pk_db_schema_name_builder = StringBuilder::new();
+    let mut pk_table_name_builder = StringBuilder::new();
+    let mut pk_column_name_builder = StringBuilder::new();
+    let mut fk_catalog_name_builder = StringBuilder::new();
+    let mut fk_db_schema_name_builder = StringBuilder::new();
+    let mut fk_table_name_builder = StringBuilder::new();
+    let mut fk_column_name_builder = StringBuilder::new();
+    let mut key_sequence_builder = Int32Builder::new();
+    let mut fk_key_name_builder = StringBuilder::new();
+    let mut pk_key_name_builder = StringBuilder::new();
+    let mut update_rule_builder = UInt8Builder::new();
+    let mut delete_rule_builder = UInt8Builder::new();
+
+    for key in keys {
+        // Keys built without ForeignKey::new may not pair every column
+        if !key.has_matching_columns() {
+            warn!(
+                "Ignoring foreign key of {} with {} columns referencing {} columns",
+                key.fk_table,
+                key.fk_columns.len(),
+                key.pk_columns.len()
+            );
+            continue;
+        }
+        for (sequence, (pk_column, fk_column)) in
+            key.pk_columns.iter().zip(&key.fk_columns).enumerate()
+        {
+            pk_catalog_name_builder.append_option(key.pk_table.catalog());
+            pk_db_schema_name_builder.append_option(key.pk_table.schema());
+            pk_table_name_builder.append_value(key.pk_table.table());
+            pk_column_name_builder.append_value(pk_column);
+            fk_catalog_name_builder.append_option(key.fk_table.catalog());
+            fk_db_schema_name_builder.append_option(key.fk_table.schema());
+            fk_table_name_builder.append_value(key.fk_table.table());
+            fk_column_name_builder.append_value(fk_column);
+            key_sequence_builder.append_value(sequence as i32 + 1);
+            fk_key_name_builder.append_option(key.fk_key_name.as_deref());
+            pk_key_name_builder.append_option(key.pk_key_name.as_deref());
+            update_rule_builder.append_value(key.update_rule as u8);
+            delete_rule_builder.append_value(key.delete_rule as u8);
+        }
+    }
+
+    RecordBatch::try_new(
+        GET_FOREIGN_KEYS_SCHEMA.clone(),
+        vec![
+            Arc::new(pk_catalog_name_builder.finish()),
+            Arc::new(pk_db_schema_name_builder.finish()),
+            Arc::new(pk_table_name_builder.finish()),
+            Arc::new(pk_column_name_builder.finish()),
+            Arc::new(fk_catalog_name_builder.finish()),
+            Arc::new(fk_db_schema_name_builder.finish()),
+            Arc::new(fk_table_name_builder.finish()),
+            Arc::new(fk_column_name_builder.finish()),
+            Arc::new(key_sequence_builder.finish()),
+            Arc::new(fk_key_name_builder.finish()),
+            Arc::new(pk_key_name_builder.finish()),
+            Arc::new(update_rule_builder.finish()),
+            Arc::new(delete_rule_builder.finish()),
+        ],
+    )
+}
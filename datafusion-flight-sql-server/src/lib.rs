@@ -1,4 +1,17 @@
+pub mod access;
+pub mod auth;
+pub mod cache;
 pub mod config;
+pub mod distributed;
+pub mod keys;
+mod query;
+pub mod rewrite;
+pub mod server;
 pub mod service;
 pub mod session;
+pub mod signing;
+pub mod sql_info;
 pub mod state;
+mod substrait;
+pub mod transaction;
+pub mod xdbc_info;
@@ -0,0 +1,143 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use arrow_flight::{CancelStatus, FlightData};
+use futures::{
+    stream::{AbortHandle, Abortable},
+    Stream, StreamExt,
+};
+use prost::bytes::Bytes;
+use tonic::Status;
+
+type Result<T, E = Status> = std::result::Result<T, E>;
+
+type FlightDataStream = std::pin::Pin<Box<dyn Stream<Item = Result<FlightData>> + Send>>;
+
+/// How long a query that was planned but not fetched yet can be cancelled.
+const PENDING_QUERY_TTL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug)]
+enum QueryState {
+    /// Planned by GetFlightInfo and not fetched yet
+    Pending(Instant),
+    /// Being fetched by DoGet, by as many streams as the ticket was
+    /// fetched with, by execution id
+    Running(HashMap<u64, AbortHandle>),
+    /// Cancelled before being fetched
+    Cancelled(Instant),
+}
+
+/// The queries planned or executed by the service, by query id, so that
+/// they can be cancelled by CancelQuery and CancelFlightInfo.
+///
+/// Cancelling a running query aborts its DataFusion stream, which releases
+/// the resources held by its execution.
+#[derive(Debug, Default)]
+pub(crate) struct QueryRegistry {
+    queries: Mutex<HashMap<Bytes, QueryState>>,
+    /// Identifies the streams of a running query
+    next_execution: AtomicU64,
+}
+
+impl QueryRegistry {
+    /// Returns a new query id, without registering the query.
+    pub fn new_query_id() -> Bytes {
+        Bytes::from(uuid::Uuid::new_v4().to_string())
+    }
+
+    /// Registers a new query that is yet to be fetched and returns its id.
+    pub fn register(&self) -> Bytes {
+        let query_id = Self::new_query_id();
+        let mut queries = self.queries.lock().unwrap();
+        queries.retain(|_, state| match state {
+            QueryState::Pending(created) | QueryState::Cancelled(created) => {
+                created.elapsed() < PENDING_QUERY_TTL
+            }
+            QueryState::Running(_) => true,
+        });
+        queries.insert(query_id.clone(), QueryState::Pending(Instant::now()));
+        query_id
+    }
+
+    /// Tracks the execution of the query, which ends with a cancelled status
+    /// when the query is cancelled.
+    pub fn execute(
+        self: &Arc<Self>,
+        query_id: Bytes,
+        stream: FlightDataStream,
+    ) -> Result<FlightDataStream> {
+        let (handle, registration) = AbortHandle::new_pair();
+        let execution = self.next_execution.fetch_add(1, Ordering::Relaxed);
+        {
+            let mut queries = self.queries.lock().unwrap();
+            match queries.get_mut(&query_id) {
+                Some(QueryState::Cancelled(_)) => {
+                    return Err(Status::cancelled("Query was cancelled"));
+                }
+                Some(QueryState::Running(handles)) => {
+                    handles.insert(execution, handle.clone());
+                }
+                Some(QueryState::Pending(_)) | None => {
+                    let handles = HashMap::from([(execution, handle.clone())]);
+                    queries.insert(query_id.clone(), QueryState::Running(handles));
+                }
+            }
+        }
+
+        let guard = QueryGuard {
+            registry: self.clone(),
+            query_id,
+            execution,
+        };
+        let stream = Abortable::new(stream, registration).chain(
+            futures::stream::once(async move {
+                let _guard = guard;
+                handle
+                    .is_aborted()
+                    .then(|| Err(Status::cancelled("Query was cancelled")))
+            })
+            .filter_map(futures::future::ready),
+        );
+        Ok(stream.boxed())
+    }
+
+    /// Cancels the query, unless it already completed.
+    pub fn cancel(&self, query_id: &[u8]) -> CancelStatus {
+        let mut queries = self.queries.lock().unwrap();
+        let Some(state) = queries.get_mut(query_id) else {
+            return CancelStatus::NotCancellable;
+        };
+        match state {
+            QueryState::Pending(_) => *state = QueryState::Cancelled(Instant::now()),
+            QueryState::Running(handles) => handles.values().for_each(AbortHandle::abort),
+            QueryState::Cancelled(_) => {}
+        }
+        CancelStatus::Cancelled
+    }
+}
+
+/// Removes a stream of a query from the registry once it is dropped, and
+/// the query once its last stream is.
+struct QueryGuard {
+    registry: Arc<QueryRegistry>,
+    query_id: Bytes,
+    execution: u64,
+}
+
+impl Drop for QueryGuard {
+    fn drop(&mut self) {
+        let mut queries = self.registry.queries.lock().unwrap();
+        if let Some(QueryState::Running(handles)) = queries.get_mut(&self.query_id) {
+            handles.remove(&self.execution);
+            if handles.is_empty() {
+                queries.remove(&self.query_id);
+            }
+        }
+    }
+}
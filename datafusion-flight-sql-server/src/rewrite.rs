@@ -0,0 +1,248 @@
+use std::{collections::HashSet, sync::Arc};
+
+use datafusion::{
+    common::{
+        tree_node::{Transformed, TreeNodeRecursion},
+        Column,
+    },
+    error::{DataFusionError, Result as DataFusionResult},
+    execution::context::SessionState,
+    logical_expr::{
+        utils::conjunction, Expr, ExprSchemable, LogicalPlan, LogicalPlanBuilder, WriteOp,
+    },
+    sql::TableReference,
+};
+use tonic::Status;
+
+use super::{auth::Identity, keys::table_matches};
+
+// PlanRewriter is a trait used to rewrite the plans of the clients of the
+// service, such as to restrict the rows and the columns of the tables they
+// see.
+//
+// Plans are rewritten after being verified and before their schema is
+// returned or they are executed. The identity is None when the service does
+// not authenticate its clients.
+pub trait PlanRewriter: Sync + Send {
+    /// Rewrites the plan of a statement of the client.
+    fn rewrite_plan(
+        &self,
+        identity: Option<&Identity>,
+        state: &SessionState,
+        plan: LogicalPlan,
+    ) -> DataFusionResult<LogicalPlan>;
+}
+
+impl<T: PlanRewriter + ?Sized> PlanRewriter for Arc<T> {
+    fn rewrite_plan(
+        &self,
+        identity: Option<&Identity>,
+        state: &SessionState,
+        plan: LogicalPlan,
+    ) -> DataFusionResult<LogicalPlan> {
+        self.as_ref().rewrite_plan(identity, state, plan)
+    }
+}
+
+type RowFilter = Box<dyn Fn(Option<&Identity>) -> Option<Expr> + Send + Sync>;
+type ColumnMask = Box<dyn Fn(Option<&Identity>, Expr) -> Option<Expr> + Send + Sync>;
+
+// MemoryRowPolicy is a PlanRewriter filtering the rows and masking the
+// columns of tables per identity. The scans of those tables are replaced
+// with the filtered and masked rows, so the results, the schemas and the
+// rows affected by DELETE and UPDATE statements all reflect the policy.
+// DELETE and UPDATE statements reading a column masked for the identity are
+// rejected, as their predicates and assignments see the unmasked table.
+#[derive(Default)]
+pub struct MemoryRowPolicy {
+    filters: Vec<(TableReference, RowFilter)>,
+    masks: Vec<(TableReference, String, ColumnMask)>,
+}
+
+impl MemoryRowPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Filters the rows of the table the client sees with the predicate
+    /// returned for its identity, if any.
+    ///
+    /// A table reference that is not fully qualified filters the tables with
+    /// that name in any catalog or schema.
+    ///
+    /// ```
+    /// # use datafusion::prelude::{col, lit};
+    /// # use datafusion_flight_sql_server::rewrite::MemoryRowPolicy;
+    /// let policy = MemoryRowPolicy::new().with_row_filter("orders", |identity| {
+    ///     let tenant = identity.and_then(|identity| identity.attribute("tenant"));
+    ///     Some(col("tenant").eq(lit(tenant.unwrap_or_default())))
+    /// });
+    /// ```
+    pub fn with_row_filter(
+        mut self,
+        table: impl Into<TableReference>,
+        filter: impl Fn(Option<&Identity>) -> Option<Expr> + Send + Sync + 'static,
+    ) -> Self {
+        self.filters.push((table.into(), Box::new(filter)));
+        self
+    }
+
+    /// Replaces the column of the table the client sees with the expression
+    /// returned for its identity, if any, given the column.
+    ///
+    /// The expression is cast to the type of the column.
+    ///
+    /// ```
+    /// # use datafusion::prelude::lit;
+    /// # use datafusion_flight_sql_server::rewrite::MemoryRowPolicy;
+    /// let policy = MemoryRowPolicy::new().with_column_mask("users", "email", |identity, _column| {
+    ///     let admin = identity.is_some_and(|identity| identity.attribute("role") == Some("admin"));
+    ///     (!admin).then(|| lit("***"))
+    /// });
+    /// ```
+    pub fn with_column_mask(
+        mut self,
+        table: impl Into<TableReference>,
+        column: impl Into<String>,
+        mask: impl Fn(Option<&Identity>, Expr) -> Option<Expr> + Send + Sync + 'static,
+    ) -> Self {
+        self.masks
+            .push((table.into(), column.into(), Box::new(mask)));
+        self
+    }
+}
+
+impl PlanRewriter for MemoryRowPolicy {
+    fn rewrite_plan(
+        &self,
+        identity: Option<&Identity>,
+        state: &SessionState,
+        plan: LogicalPlan,
+    ) -> DataFusionResult<LogicalPlan> {
+        let options = &state.config_options().catalog;
+        let resolve = |table: &TableReference| {
+            let table = table
+                .clone()
+                .resolve(&options.default_catalog, &options.default_schema);
+            TableReference::full(table.catalog, table.schema, table.table)
+        };
+        let matches = |grant: &TableReference, table: &TableReference| {
+            table_matches(table, grant.catalog(), grant.schema(), grant.table())
+        };
+        // The providers evaluate the assignments of an UPDATE against the
+        // table itself, so the scan of the modified table is only filtered
+        let modified = match &plan {
+            LogicalPlan::Dml(dml) if matches!(dml.op, WriteOp::Delete | WriteOp::Update) => {
+                Some(resolve(&dml.table_name))
+            }
+            _ => None,
+        };
+        if let (Some(table), LogicalPlan::Dml(dml)) = (&modified, &plan) {
+            let masked = self
+                .masks
+                .iter()
+                .filter(|(grant, column, mask)| {
+                    let column = Expr::Column(Column::new_unqualified(column.as_str()));
+                    matches(grant, table) && mask(identity, column).is_some()
+                })
+                .map(|(_, column, _)| column.as_str())
+                .collect::<HashSet<_>>();
+            if !masked.is_empty() {
+                verify_unmasked(&dml.op, &dml.input, &masked)?;
+            }
+        }
+
+        plan.transform_up_with_subqueries(|plan| {
+            let LogicalPlan::TableScan(scan) = &plan else {
+                return Ok(Transformed::no(plan));
+            };
+            let table = resolve(&scan.table_name);
+            let filters = self
+                .filters
+                .iter()
+                .filter(|(grant, _)| matches(grant, &table))
+                .filter_map(|(_, filter)| filter(identity))
+                .collect::<Vec<_>>();
+            let masks = self
+                .masks
+                .iter()
+                .filter(|(grant, _, _)| modified.as_ref() != Some(&table) && matches(grant, &table))
+                .map(|(_, column, mask)| (column, mask))
+                .collect::<Vec<_>>();
+            if filters.is_empty() && masks.is_empty() {
+                return Ok(Transformed::no(plan));
+            }
+
+            let mut builder = LogicalPlanBuilder::from(plan);
+            if let Some(predicate) = conjunction(filters) {
+                builder = builder.filter(predicate)?;
+            }
+            let schema = Arc::clone(builder.schema());
+            let mut masked = false;
+            let exprs = schema
+                .iter()
+                .map(|(qualifier, field)| {
+                    let column = Expr::Column(Column::from((qualifier, field)));
+                    let mask = masks
+                        .iter()
+                        .find(|(name, _)| *name == field.name())
+                        .and_then(|(_, mask)| mask(identity, column.clone()));
+                    match mask {
+                        Some(mask) => {
+                            masked = true;
+                            Ok(mask
+                                .cast_to(field.data_type(), &schema)?
+                                .alias_qualified(qualifier.cloned(), field.name()))
+                        }
+                        None => Ok(column),
+                    }
+                })
+                .collect::<DataFusionResult<Vec<_>>>()?;
+            if masked {
+                builder = builder.project(exprs)?;
+            }
+            builder.build().map(Transformed::yes)
+        })
+        .map(|transformed| transformed.data)
+    }
+}
+
+/// Fails with PermissionDenied when the input of a DELETE or an UPDATE reads
+/// a masked column, matched by name whatever table it belongs to.
+fn verify_unmasked(
+    op: &WriteOp,
+    input: &LogicalPlan,
+    masked: &HashSet<&str>,
+) -> DataFusionResult<()> {
+    let verify = |expr: &Expr| match expr
+        .column_refs()
+        .into_iter()
+        .find(|column| masked.contains(column.name.as_str()))
+    {
+        Some(column) => Err(DataFusionError::External(Box::new(
+            Status::permission_denied(format!("Masked column {} cannot be read", column.name)),
+        ))),
+        None => Ok(TreeNodeRecursion::Continue),
+    };
+
+    // The assignments of an UPDATE keep the columns it does not set as is
+    let input = match (op, input) {
+        (WriteOp::Update, LogicalPlan::Projection(projection)) => {
+            for expr in &projection.expr {
+                let kept = matches!(
+                    expr,
+                    Expr::Alias(alias)
+                        if matches!(alias.expr.as_ref(), Expr::Column(column) if column.name == alias.name)
+                );
+                if !kept {
+                    verify(expr)?;
+                }
+            }
+            projection.input.as_ref()
+        }
+        (_, input) => input,
+    };
+    input
+        .apply_with_subqueries(|plan| plan.apply_expressions(verify))
+        .map(|_| ())
+}
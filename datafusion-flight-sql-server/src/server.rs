@@ -0,0 +1,346 @@
+use std::{
+    future::Future,
+    io,
+    pin::{pin, Pin},
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use arrow_flight::flight_service_server::FlightServiceServer;
+use futures::{
+    channel::oneshot,
+    future::{self, Either, Shared},
+    FutureExt, TryStreamExt,
+};
+use log::{info, warn};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::TcpStream,
+};
+use tonic::transport::server::{Connected, Router, TcpConnectInfo, TcpIncoming};
+
+use super::service::FlightSqlService;
+
+type Result<T, E = Box<dyn std::error::Error>> = std::result::Result<T, E>;
+
+/// FlightSqlServer serves a [`FlightSqlService`] with the transport settings
+/// of the underlying tonic server, and shuts it down gracefully.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use datafusion::prelude::SessionContext;
+/// # use datafusion_flight_sql_server::{server::FlightSqlServer, service::FlightSqlService};
+/// # async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+/// let service = FlightSqlService::new(SessionContext::new().state());
+/// FlightSqlServer::new(service)
+///     .with_max_decoding_message_size(64 * 1024 * 1024)
+///     .with_http2_keepalive_interval(Duration::from_secs(30))
+///     .with_shutdown_timeout(Duration::from_secs(60))
+///     .serve_with_shutdown("0.0.0.0:50051".to_string(), async {
+///         tokio::signal::ctrl_c().await.ok();
+///     })
+///     .await
+/// # }
+/// ```
+pub struct FlightSqlServer {
+    service: FlightSqlService,
+    max_decoding_message_size: Option<usize>,
+    max_encoding_message_size: Option<usize>,
+    concurrency_limit_per_connection: Option<usize>,
+    max_concurrent_streams: Option<u32>,
+    http2_keepalive_interval: Option<Duration>,
+    http2_keepalive_timeout: Option<Duration>,
+    tcp_nodelay: bool,
+    tcp_keepalive: Option<Duration>,
+    shutdown_timeout: Option<Duration>,
+}
+
+impl FlightSqlServer {
+    pub fn new(service: FlightSqlService) -> Self {
+        Self {
+            service,
+            max_decoding_message_size: None,
+            max_encoding_message_size: None,
+            concurrency_limit_per_connection: None,
+            max_concurrent_streams: None,
+            http2_keepalive_interval: None,
+            http2_keepalive_timeout: None,
+            tcp_nodelay: true,
+            tcp_keepalive: None,
+            shutdown_timeout: None,
+        }
+    }
+
+    /// Sets the maximum size of the messages received from clients, 4MB by
+    /// default.
+    pub fn with_max_decoding_message_size(self, limit: usize) -> Self {
+        Self {
+            max_decoding_message_size: Some(limit),
+            ..self
+        }
+    }
+
+    /// Sets the maximum size of the messages sent to clients, unlimited by
+    /// default.
+    pub fn with_max_encoding_message_size(self, limit: usize) -> Self {
+        Self {
+            max_encoding_message_size: Some(limit),
+            ..self
+        }
+    }
+
+    /// Limits the number of requests processed concurrently per connection.
+    pub fn with_concurrency_limit_per_connection(self, limit: usize) -> Self {
+        Self {
+            concurrency_limit_per_connection: Some(limit),
+            ..self
+        }
+    }
+
+    /// Limits the number of concurrent HTTP/2 streams per connection.
+    pub fn with_max_concurrent_streams(self, max: u32) -> Self {
+        Self {
+            max_concurrent_streams: Some(max),
+            ..self
+        }
+    }
+
+    /// Sends HTTP/2 keepalive pings to clients at the interval.
+    pub fn with_http2_keepalive_interval(self, interval: Duration) -> Self {
+        Self {
+            http2_keepalive_interval: Some(interval),
+            ..self
+        }
+    }
+
+    /// Closes the connections whose keepalive pings are not acknowledged
+    /// within the timeout, 20 seconds by default.
+    pub fn with_http2_keepalive_timeout(self, timeout: Duration) -> Self {
+        Self {
+            http2_keepalive_timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Sets TCP_NODELAY on the accepted connections, true by default.
+    pub fn with_tcp_nodelay(self, enabled: bool) -> Self {
+        Self {
+            tcp_nodelay: enabled,
+            ..self
+        }
+    }
+
+    /// Enables TCP keepalive on the accepted connections.
+    pub fn with_tcp_keepalive(self, keepalive: Duration) -> Self {
+        Self {
+            tcp_keepalive: Some(keepalive),
+            ..self
+        }
+    }
+
+    /// Sets how long a graceful shutdown waits for the requests in flight,
+    /// such as DoGet streams. The connections still open at the deadline are
+    /// closed, which cancels their requests. By default the shutdown waits
+    /// for every request.
+    pub fn with_shutdown_timeout(self, timeout: Duration) -> Self {
+        Self {
+            shutdown_timeout: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Serves on the specified address.
+    pub async fn serve(self, addr: String) -> Result<()> {
+        let listener = tokio::net::TcpListener::bind(addr.parse::<std::net::SocketAddr>()?).await?;
+        self.serve_incoming(listener, None::<future::Pending<()>>)
+            .await
+    }
+
+    /// Serves on the listener.
+    pub async fn serve_with_listener(self, listener: std::net::TcpListener) -> Result<()> {
+        listener.set_nonblocking(true)?;
+        let listener = tokio::net::TcpListener::from_std(listener)?;
+        self.serve_incoming(listener, None::<future::Pending<()>>)
+            .await
+    }
+
+    /// Serves on the specified address until the signal completes.
+    ///
+    /// Once the signal completes, the server stops accepting connections and
+    /// requests, and returns when the requests in flight complete, or when
+    /// they are cancelled at the shutdown timeout.
+    pub async fn serve_with_shutdown(
+        self,
+        addr: String,
+        signal: impl Future<Output = ()>,
+    ) -> Result<()> {
+        let listener = tokio::net::TcpListener::bind(addr.parse::<std::net::SocketAddr>()?).await?;
+        self.serve_incoming(listener, Some(signal)).await
+    }
+
+    /// Serves on the listener until the signal completes, like
+    /// [`FlightSqlServer::serve_with_shutdown`].
+    pub async fn serve_with_listener_and_shutdown(
+        self,
+        listener: std::net::TcpListener,
+        signal: impl Future<Output = ()>,
+    ) -> Result<()> {
+        listener.set_nonblocking(true)?;
+        let listener = tokio::net::TcpListener::from_std(listener)?;
+        self.serve_incoming(listener, Some(signal)).await
+    }
+
+    /// Returns the gRPC service with the message size limits, to be served
+    /// alongside other services or wrapped in tower middleware by a tonic
+    /// server. The TLS and transport settings are those of that server.
+    ///
+    /// ```no_run
+    /// # use datafusion::prelude::SessionContext;
+    /// # use datafusion_flight_sql_server::{server::FlightSqlServer, service::FlightSqlService};
+    /// # use tonic::transport::Server;
+    /// # async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    /// let service = FlightSqlService::new(SessionContext::new().state());
+    /// let flight_sql = FlightSqlServer::new(service)
+    ///     .with_max_decoding_message_size(64 * 1024 * 1024)
+    ///     .into_service();
+    /// Server::builder()
+    ///     .add_service(flight_sql)
+    ///     // .add_service(health_service)
+    ///     .serve("0.0.0.0:50051".parse()?)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn into_service(self) -> FlightServiceServer<FlightSqlService> {
+        let mut svc = FlightServiceServer::new(self.service);
+        if let Some(limit) = self.max_decoding_message_size {
+            svc = svc.max_decoding_message_size(limit);
+        }
+        if let Some(limit) = self.max_encoding_message_size {
+            svc = svc.max_encoding_message_size(limit);
+        }
+        svc
+    }
+
+    /// Returns a tonic router serving the service with the TLS and transport
+    /// settings, to which other services can be added before serving it.
+    ///
+    /// Unlike [`FlightSqlServer::serve_with_shutdown`], the router waits for
+    /// every request in flight when shut down.
+    pub fn into_router(self) -> Result<Router, tonic::transport::Error> {
+        let mut server = self
+            .service
+            .server_builder()?
+            .max_concurrent_streams(self.max_concurrent_streams)
+            .http2_keepalive_interval(self.http2_keepalive_interval)
+            .http2_keepalive_timeout(self.http2_keepalive_timeout)
+            .tcp_nodelay(self.tcp_nodelay)
+            .tcp_keepalive(self.tcp_keepalive);
+        if let Some(limit) = self.concurrency_limit_per_connection {
+            server = server.concurrency_limit_per_connection(limit);
+        }
+        Ok(server.add_service(self.into_service()))
+    }
+
+    async fn serve_incoming(
+        self,
+        listener: tokio::net::TcpListener,
+        signal: Option<impl Future<Output = ()>>,
+    ) -> Result<()> {
+        info!("Listening on {}", listener.local_addr()?);
+        let incoming = TcpIncoming::from(listener)
+            .with_nodelay(Some(self.tcp_nodelay))
+            .with_keepalive(self.tcp_keepalive);
+        let shutdown_timeout = self.shutdown_timeout;
+        let router = self.into_router()?;
+
+        let Some(signal) = signal else {
+            return Ok(router.serve_with_incoming(incoming).await?);
+        };
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        let signal = signal.map(|()| {
+            info!("Shutting down");
+            let _ = shutdown_tx.send(());
+        });
+        let (deadline_tx, deadline_rx) = oneshot::channel();
+        let deadline_rx = deadline_rx.shared();
+        let incoming = incoming.map_ok(move |stream| ShutdownConnection {
+            inner: stream,
+            deadline: deadline_rx.clone(),
+        });
+        let mut serve = pin!(router.serve_with_incoming_shutdown(incoming, signal));
+        let deadline = async move {
+            match (shutdown_rx.await, shutdown_timeout) {
+                (Ok(()), Some(timeout)) => tokio::time::sleep(timeout).await,
+                _ => future::pending().await,
+            }
+        };
+        match future::select(serve.as_mut(), pin!(deadline)).await {
+            Either::Left((result, _)) => Ok(result?),
+            Either::Right(((), _)) => {
+                warn!("Shutdown timeout reached, closing the remaining connections");
+                let _ = deadline_tx.send(());
+                Ok(serve.await?)
+            }
+        }
+    }
+}
+
+/// A connection closed when the shutdown timeout is reached, which drops
+/// the requests still in flight on it, such as DoGet streams a client stopped
+/// reading.
+struct ShutdownConnection {
+    inner: TcpStream,
+    deadline: Shared<oneshot::Receiver<()>>,
+}
+
+impl ShutdownConnection {
+    fn poll_deadline(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.deadline.poll_unpin(cx) {
+            Poll::Ready(Ok(())) => Poll::Ready(Err(io::ErrorKind::ConnectionAborted.into())),
+            _ => Poll::Pending,
+        }
+    }
+}
+
+impl Connected for ShutdownConnection {
+    type ConnectInfo = TcpConnectInfo;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        self.inner.connect_info()
+    }
+}
+
+impl AsyncRead for ShutdownConnection {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if let Poll::Ready(result) = self.poll_deadline(cx) {
+            return Poll::Ready(result);
+        }
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for ShutdownConnection {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if let Poll::Ready(Err(e)) = self.poll_deadline(cx) {
+            return Poll::Ready(Err(e));
+        }
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
@@ -1,9 +1,15 @@
-use std::{collections::BTreeMap, pin::Pin, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use arrow_flight::{
     decode::{DecodedPayload, FlightDataDecoder},
     sql::{
         self,
+        metadata::{SqlInfoData, SqlInfoDataBuilder},
         server::{FlightSqlService as ArrowFlightSqlService, PeekableFlightDataStream},
         ActionBeginSavepointRequest, ActionBeginSavepointResult, ActionBeginTransactionRequest,
         ActionBeginTransactionResult, ActionCancelQueryRequest, ActionCancelQueryResult,
@@ -13,22 +19,22 @@ use arrow_flight::{
         CommandGetCrossReference, CommandGetDbSchemas, CommandGetExportedKeys,
         CommandGetImportedKeys, CommandGetPrimaryKeys, CommandGetSqlInfo, CommandGetTableTypes,
         CommandGetTables, CommandGetXdbcTypeInfo, CommandPreparedStatementQuery,
-        CommandPreparedStatementUpdate, CommandStatementQuery, CommandStatementSubstraitPlan,
-        CommandStatementUpdate, DoPutPreparedStatementResult, ProstMessageExt as _, SqlInfo,
+        CommandPreparedStatementUpdate, CommandStatementIngest, CommandStatementQuery,
+        CommandStatementSubstraitPlan, CommandStatementUpdate, DoPutPreparedStatementResult,
+        EndTransaction, ProstMessageExt as _, SqlInfo, TableExistsOption, TableNotExistOption,
         TicketStatementQuery,
     },
 };
 use arrow_flight::{
-    encode::FlightDataEncoderBuilder,
-    error::FlightError,
-    flight_service_server::{FlightService, FlightServiceServer},
-    Action, FlightDescriptor, FlightEndpoint, FlightInfo, HandshakeRequest, HandshakeResponse,
-    IpcMessage, SchemaAsIpc, Ticket,
+    encode::FlightDataEncoderBuilder, error::FlightError, flight_service_server::FlightService,
+    Action, ActionType, CancelFlightInfoRequest, CancelFlightInfoResult, CancelStatus,
+    FlightDescriptor, FlightEndpoint, FlightInfo, HandshakeRequest, HandshakeResponse, IpcMessage,
+    SchemaAsIpc, Ticket,
 };
 use datafusion::arrow::{
-    array::{ArrayRef, RecordBatch, StringArray},
+    array::{ArrayRef, AsArray as _, Int32Builder, RecordBatch, StringArray, StringBuilder},
     compute::concat_batches,
-    datatypes::{DataType, Field, SchemaBuilder, SchemaRef},
+    datatypes::{DataType, Field, SchemaBuilder, SchemaRef, UInt64Type},
     error::ArrowError,
     ipc::{
         reader::StreamReader,
@@ -36,38 +42,84 @@ use datafusion::arrow::{
     },
 };
 use datafusion::{
-    common::{arrow::datatypes::Schema, ParamValues},
-    dataframe::DataFrame,
-    datasource::TableType,
+    catalog::streaming::StreamingTable,
+    common::{arrow::datatypes::Schema, plan_err, Constraint, Constraints, DFSchema, ParamValues},
+    datasource::{provider_as_source, TableType},
     error::{DataFusionError, Result as DataFusionResult},
-    execution::context::{SQLOptions, SessionContext, SessionState},
-    logical_expr::LogicalPlan,
-    physical_plan::SendableRecordBatchStream,
+    execution::{
+        context::{SQLOptions, SessionContext, SessionState},
+        TaskContext,
+    },
+    logical_expr::{
+        dml::InsertOp, CreateMemoryTable, DdlStatement, DmlStatement, EmptyRelation, LogicalPlan,
+        LogicalPlanBuilder, WriteOp,
+    },
+    physical_plan::{
+        repartition::RepartitionExec, stream::RecordBatchStreamAdapter, streaming::PartitionStream,
+        ExecutionPlan, ExecutionPlanProperties, SendableRecordBatchStream,
+    },
     scalar::ScalarValue,
+    sql::TableReference,
 };
-use datafusion_substrait::{
-    logical_plan::consumer::from_substrait_plan, serializer::deserialize_bytes,
-};
+use datafusion_substrait::serializer::deserialize_bytes;
 
 use futures::{Stream, StreamExt, TryStreamExt};
-use log::info;
+use log::{info, warn};
 use once_cell::sync::Lazy;
 use prost::bytes::Bytes;
 use prost::Message;
-use tonic::transport::Server;
-use tonic::{Request, Response, Status, Streaming};
+use tonic::transport::{Server, ServerTlsConfig};
+use tonic::{
+    metadata::{Ascii, MetadataMap, MetadataValue},
+    Request, Response, Status, Streaming,
+};
 
+use super::access::{table_accesses, AccessPolicy, TableAccess};
+use super::auth::{basic_credentials, bearer_token, Authenticator, Identity};
+use super::cache::ResultCache;
 use super::config::FlightSqlServiceConfig;
-use super::session::{SessionStateProvider, StaticSessionStateProvider};
+use super::distributed::{self, Workers};
+use super::keys::{
+    foreign_keys_batch, table_matches, ForeignKey, ForeignKeyProvider, GET_FOREIGN_KEYS_SCHEMA,
+};
+use super::query::QueryRegistry;
+use super::rewrite::PlanRewriter;
+use super::server::FlightSqlServer;
+use super::session::{
+    close_session_result, session_id, session_option_value::OptionValue,
+    set_session_options_result, CloseSessionResult, GetSessionOptionsResult, SessionOptionValue,
+    SessionStateProvider, SessionStore, SetSessionOptionsRequest, SetSessionOptionsResult,
+    StaticSessionStateProvider, SESSION_COOKIE,
+};
+use super::signing::{TicketSigner, PREPARED_STATEMENT_HANDLE, TICKET};
+use super::sql_info::{default_sql_info, read_only};
 use super::state::{CommandTicket, QueryHandle};
+use super::substrait::{from_substrait_plan, serialize_plan};
+use super::transaction::{
+    EndSavepoint, ReadOnlyTransactionManager, Savepoints, TransactionManager,
+};
+use super::xdbc_info::xdbc_type_info;
 
 type Result<T, E = Status> = std::result::Result<T, E>;
 
-/// FlightSqlService is a basic stateless FlightSqlService implementation.
+/// FlightSqlService is a basic FlightSqlService implementation, stateless
+/// unless sessions are enabled with [`FlightSqlService::with_sessions`].
 pub struct FlightSqlService {
     provider: Box<dyn SessionStateProvider>,
     sql_options: Option<SQLOptions>,
     config: FlightSqlServiceConfig,
+    foreign_keys: Option<Box<dyn ForeignKeyProvider>>,
+    transactions: Arc<dyn TransactionManager>,
+    savepoints: Savepoints,
+    queries: Arc<QueryRegistry>,
+    sessions: Option<SessionStore>,
+    authenticator: Option<Box<dyn Authenticator>>,
+    access_policy: Option<Arc<dyn AccessPolicy>>,
+    plan_rewriter: Option<Arc<dyn PlanRewriter>>,
+    tls: Option<ServerTlsConfig>,
+    workers: Option<Arc<Workers>>,
+    result_cache: Option<Arc<ResultCache>>,
+    ticket_signer: Option<Arc<TicketSigner>>,
 }
 
 impl FlightSqlService {
@@ -82,6 +134,18 @@ impl FlightSqlService {
             provider,
             sql_options: None,
             config: FlightSqlServiceConfig::default(),
+            foreign_keys: None,
+            transactions: Arc::new(ReadOnlyTransactionManager::new()),
+            savepoints: Savepoints::default(),
+            queries: Arc::new(QueryRegistry::default()),
+            sessions: None,
+            authenticator: None,
+            access_policy: None,
+            plan_rewriter: None,
+            tls: None,
+            workers: None,
+            result_cache: None,
+            ticket_signer: None,
         }
     }
 
@@ -100,57 +164,432 @@ impl FlightSqlService {
         }
     }
 
+    /// Sets the ForeignKeyProvider used to answer GetExportedKeys,
+    /// GetImportedKeys and GetCrossReference.
+    /// When None these commands return no keys.
+    pub fn with_foreign_key_provider(self, foreign_keys: Box<dyn ForeignKeyProvider>) -> Self {
+        Self {
+            foreign_keys: Some(foreign_keys),
+            ..self
+        }
+    }
+
+    /// Replaces the TransactionManager used to begin and end transactions.
+    /// By default transactions are read-only, see [`ReadOnlyTransactionManager`].
+    pub fn with_transaction_manager(self, transactions: Arc<dyn TransactionManager>) -> Self {
+        Self {
+            transactions,
+            ..self
+        }
+    }
+
+    /// Enables server-side sessions, identified by the session cookie set by
+    /// the SetSessionOptions and GetSessionOptions actions.
+    /// Sessions idle for longer than `idle_timeout` expire, and no more than
+    /// `max_sessions` sessions can be open at once.
+    /// When disabled every request is executed with a new SessionState.
+    pub fn with_sessions(self, idle_timeout: Duration, max_sessions: usize) -> Self {
+        Self {
+            sessions: Some(SessionStore::new(idle_timeout, max_sessions)),
+            ..self
+        }
+    }
+
+    /// Requires clients to authenticate with the Authenticator, by sending a
+    /// bearer token with every request. Handshake issues bearer tokens to
+    /// clients sending Basic credentials.
+    /// The [`Identity`] of the client is inserted in the request extensions.
+    /// When None requests are not authenticated and Handshake is not supported.
+    pub fn with_authenticator(self, authenticator: Box<dyn Authenticator>) -> Self {
+        Self {
+            authenticator: Some(authenticator),
+            ..self
+        }
+    }
+
+    /// Sets the AccessPolicy authorizing the tables accessed by every plan,
+    /// and the catalogs, schemas and tables listed by the metadata commands.
+    /// When None every table is accessible.
+    pub fn with_access_policy(self, access_policy: Arc<dyn AccessPolicy>) -> Self {
+        Self {
+            access_policy: Some(access_policy),
+            ..self
+        }
+    }
+
+    /// Sets the PlanRewriter rewriting every plan per identity, such as to
+    /// filter rows or mask columns, before its schema is returned or it is
+    /// executed.
+    pub fn with_plan_rewriter(self, plan_rewriter: Arc<dyn PlanRewriter>) -> Self {
+        Self {
+            plan_rewriter: Some(plan_rewriter),
+            ..self
+        }
+    }
+
+    /// Serves over TLS with the server identity of the config. Clients are
+    /// authenticated with their certificates when the config has a client CA
+    /// root, and [`SessionStateProvider`]s get those certificates through
+    /// [`Request::peer_certs`].
+    ///
+    /// ```no_run
+    /// # use tonic::transport::{Certificate, Identity, ServerTlsConfig};
+    /// # fn config() -> std::io::Result<ServerTlsConfig> {
+    /// let config = ServerTlsConfig::new()
+    ///     .identity(Identity::from_pem(
+    ///         std::fs::read("server.pem")?,
+    ///         std::fs::read("server.key")?,
+    ///     ))
+    ///     .client_ca_root(Certificate::from_pem(std::fs::read("ca.pem")?));
+    /// # Ok(config)
+    /// # }
+    /// ```
+    pub fn with_tls(self, tls: ServerTlsConfig) -> Self {
+        Self {
+            tls: Some(tls),
+            ..self
+        }
+    }
+
+    /// Runs the service as a coordinator, whose query endpoints are executed
+    /// by the [`Workers`], one endpoint per output partition of the physical
+    /// plan. Queries within a transaction and DML statements are still
+    /// executed by the coordinator, as are all queries while no worker is
+    /// registered. See [`Workers`] for how queries are split and cancelled.
+    pub fn with_workers(self, workers: Arc<Workers>) -> Self {
+        Self {
+            workers: Some(workers),
+            ..self
+        }
+    }
+
+    /// Serves the results of the queries fetched before from the
+    /// ResultCache. Queries within a transaction are never cached, and the
+    /// cache is invalidated by DML and DDL statements and by commits.
+    pub fn with_result_cache(self, result_cache: Arc<ResultCache>) -> Self {
+        Self {
+            result_cache: Some(result_cache),
+            ..self
+        }
+    }
+
+    /// Signs the query tickets and prepared statement handles with the
+    /// TicketSigner, and rejects those failing verification with
+    /// `Unauthenticated`.
+    /// When None tickets and handles are plain protobuf messages.
+    pub fn with_ticket_signer(self, ticket_signer: Arc<TicketSigner>) -> Self {
+        Self {
+            ticket_signer: Some(ticket_signer),
+            ..self
+        }
+    }
+
     // Federate substrait plans instead of SQL
     // pub fn with_substrait() -> Self {
     // TODO: Substrait federation
     // }
 
-    // Serves straightforward on the specified address.
+    // Serves straightforward on the specified address. Use FlightSqlServer to
+    // tune the transport or to shut down gracefully.
     pub async fn serve(self, addr: String) -> Result<(), Box<dyn std::error::Error>> {
-        let addr = addr.parse()?;
-        info!("Listening on {addr:?}");
-
-        let svc = FlightServiceServer::new(self);
-
-        Ok(Server::builder().add_service(svc).serve(addr).await?)
+        FlightSqlServer::new(self).serve(addr).await
     }
 
     pub async fn serve_with_listener(
         self,
         listener: std::net::TcpListener,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        info!("Listening on {}", listener.local_addr()?);
-
-        let svc = FlightServiceServer::new(self);
-        let listener = tokio::net::TcpListener::from_std(listener)?;
+        FlightSqlServer::new(self)
+            .serve_with_listener(listener)
+            .await
+    }
 
-        Ok(Server::builder()
-            .add_service(svc)
-            .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener))
-            .await?)
+    /// Returns a tonic server builder configured with the TLS config, if any.
+    pub(crate) fn server_builder(&self) -> Result<Server, tonic::transport::Error> {
+        match &self.tls {
+            Some(tls) => Server::builder().tls_config(tls.clone()),
+            None => Ok(Server::builder()),
+        }
     }
 
     async fn new_context<T>(
         &self,
         request: Request<T>,
     ) -> Result<(Request<T>, FlightSqlSessionContext)> {
-        let (metadata, extensions, msg) = request.into_parts();
+        let (metadata, mut extensions, msg) = request.into_parts();
+        if let Some(authenticator) = &self.authenticator {
+            let identity = authenticator
+                .authenticate_token(bearer_token(&metadata)?)
+                .await?;
+            extensions.insert(identity);
+        }
         let inspect_request = Request::from_parts(metadata, extensions, ());
 
-        let state = self.provider.new_context(&inspect_request).await?;
-        let ctx = SessionContext::new_with_state(state);
+        let session_id = session_id(inspect_request.metadata()).filter(|_| self.sessions.is_some());
+        let ctx = match (&self.sessions, &session_id) {
+            (Some(sessions), Some(session_id)) => {
+                sessions.get(session_id, inspect_request.extensions().get::<Identity>())?
+            }
+            _ => {
+                let state = self.provider.new_context(&inspect_request).await?;
+                SessionContext::new_with_state(state)
+            }
+        };
+        let cache_scope = self
+            .result_cache
+            .as_ref()
+            .and_then(|_| self.provider.cache_scope(&inspect_request));
 
         let (metadata, extensions, _) = inspect_request.into_parts();
+        let identity = extensions.get::<Identity>().cloned();
         Ok((
             Request::from_parts(metadata, extensions, msg),
             FlightSqlSessionContext {
                 inner: ctx,
                 sql_options: self.sql_options,
+                transactions: self.transactions.clone(),
+                transaction_id: None,
+                identity,
+                access_policy: self.access_policy.clone(),
+                plan_rewriter: self.plan_rewriter.clone(),
+                result_cache: self.result_cache.clone(),
+                session_id,
+                cache_scope,
+                ticket_signer: self.ticket_signer.clone(),
             },
         ))
     }
+
+    /// Creates a session executing the statements with the context of the
+    /// request, unless the request already has one, and returns the cookie
+    /// identifying the new session.
+    fn open_session(
+        &self,
+        metadata: &MetadataMap,
+        identity: Option<&Identity>,
+        ctx: &FlightSqlSessionContext,
+    ) -> Result<Option<MetadataValue<Ascii>>> {
+        let sessions = self
+            .sessions
+            .as_ref()
+            .ok_or_else(|| Status::unimplemented("Sessions are not enabled"))?;
+        if session_id(metadata).is_some() {
+            return Ok(None);
+        }
+        let session_id = sessions.create(ctx.inner.clone(), identity.cloned())?;
+        let cookie = format!("{SESSION_COOKIE}={session_id}")
+            .parse()
+            .map_err(|_| Status::internal("Invalid session cookie"))?;
+        Ok(Some(cookie))
+    }
+
+    /// Builds the SqlInfo data for the session, applying the overrides
+    /// registered in the [`FlightSqlServiceConfig`].
+    fn sql_info(&self, ctx: &FlightSqlSessionContext) -> Result<SqlInfoData, FlightError> {
+        let mut builder = default_sql_info(&ctx.inner.state());
+        let read_only = read_only(&self.sql_options.unwrap_or_default());
+        builder.append(SqlInfo::FlightSqlServerReadOnly, read_only);
+        self.transactions.sql_info(&mut builder);
+        for sql_info in &self.config.sql_info {
+            sql_info(&mut builder);
+        }
+        builder.build()
+    }
+
+    /// Returns the endpoints of a query, whose tickets the client passes back
+    /// to DoGet: a single endpoint executing the whole query or, with
+    /// [`FlightSqlServiceConfig::partitioned_endpoints`] or workers, one
+    /// endpoint per output partition of its physical plan. Each endpoint is
+    /// a query of its own, cancelled along with the others by
+    /// CancelFlightInfo.
+    ///
+    /// With [`FlightSqlServiceConfig::plan_in_ticket`] or workers, the
+    /// tickets carry the plan and the schema of the FlightInfo, which
+    /// requires a TicketSigner.
+    async fn query_endpoints(
+        &self,
+        ctx: &FlightSqlSessionContext,
+        plan: &LogicalPlan,
+        schema: &Schema,
+        command: sql::Command,
+    ) -> Result<Vec<FlightEndpoint>> {
+        let is_query = is_query(plan);
+        // Workers know nothing of the transactions of the coordinator
+        let mut workers = self.workers.as_ref().filter(|workers| {
+            is_query && ctx.transaction_id.is_none() && !workers.locations().is_empty()
+        });
+
+        // DoGet and the workers execute the plan as verified and rewritten here
+        let mut ticket = CommandTicket::new(command);
+        if is_query && (self.config.plan_in_ticket || workers.is_some()) {
+            if self.ticket_signer.is_none() {
+                return Err(Status::failed_precondition(
+                    "Tickets carrying a plan must be signed, configure a TicketSigner",
+                ));
+            }
+            match serialize_plan(&ctx.inner.state(), plan) {
+                Ok(plan) => {
+                    let schema = encode_schema(schema).map_err(arrow_error_to_status)?;
+                    ticket = ticket.with_plan(plan, schema);
+                }
+                // Plans Substrait cannot represent are planned again by the
+                // DoGet of this service
+                Err(err) => {
+                    warn!("Executing the query locally, its plan cannot be serialized: {err}");
+                    workers = None;
+                }
+            }
+        }
+
+        let partition_count = if self.config.partitioned_endpoints || workers.is_some() {
+            ctx.partition_count(plan)
+                .await
+                .map_err(df_error_to_status)?
+        } else {
+            None
+        };
+        let tickets = match partition_count {
+            Some(partition_count) => (0..partition_count as u32)
+                .map(|partition| {
+                    ticket
+                        .clone()
+                        .with_partition(partition, partition_count as u32)
+                })
+                .collect(),
+            None => vec![ticket],
+        };
+
+        tickets
+            .into_iter()
+            .map(|ticket| {
+                let location = workers.and_then(|workers| workers.next());
+                // Queries executed by a worker are tracked, and cancelled, by
+                // the worker
+                let query_id = match location {
+                    Some(_) => QueryRegistry::new_query_id(),
+                    None => self.queries.register(),
+                };
+                let ticket =
+                    self.encode_ticket(ticket.with_query_id(query_id), ctx.identity.as_ref())?;
+                let endpoint = FlightEndpoint::new().with_ticket(Ticket { ticket });
+                match location {
+                    Some(uri) => Ok(endpoint.with_location(uri)),
+                    None => Ok(endpoint),
+                }
+            })
+            .collect()
+    }
+
+    /// Encodes the ticket of the client, signed by the TicketSigner, if any.
+    fn encode_ticket(&self, ticket: CommandTicket, identity: Option<&Identity>) -> Result<Bytes> {
+        let ticket = ticket.try_encode().map_err(flight_error_to_status)?;
+        match &self.ticket_signer {
+            Some(ticket_signer) => ticket_signer.sign(TICKET, identity, ticket),
+            None => Ok(ticket),
+        }
+    }
+
+    /// Decodes a ticket of the client, verified by the TicketSigner, if any.
+    fn decode_ticket(&self, ticket: Bytes, identity: Option<&Identity>) -> Result<CommandTicket> {
+        let ticket = match &self.ticket_signer {
+            Some(ticket_signer) => ticket_signer.verify(TICKET, identity, ticket)?,
+            None => ticket,
+        };
+        CommandTicket::try_decode(ticket).map_err(flight_error_to_status)
+    }
+
+    /// Cancels the queries of the endpoints of the FlightInfo of the client,
+    /// including those executed by the workers.
+    async fn cancel_flight_info(
+        &self,
+        info: &FlightInfo,
+        identity: Option<&Identity>,
+        metadata: &MetadataMap,
+    ) -> CancelStatus {
+        let mut status = CancelStatus::NotCancellable;
+        let mut locations = BTreeSet::new();
+        for endpoint in &info.endpoint {
+            let Some(ticket) = &endpoint.ticket else {
+                continue;
+            };
+            let Ok(CommandTicket {
+                query_id: Some(query_id),
+                ..
+            }) = self.decode_ticket(ticket.ticket.clone(), identity)
+            else {
+                continue;
+            };
+            if self.queries.cancel(&query_id) == CancelStatus::Cancelled {
+                status = CancelStatus::Cancelled;
+            }
+            locations.extend(endpoint.location.iter().map(|location| &location.uri));
+        }
+
+        // Only the registered workers are sent the FlightInfo, whose
+        // locations are not signed
+        let workers = self
+            .workers
+            .as_ref()
+            .map(|workers| workers.locations())
+            .unwrap_or_default();
+        let results = futures::future::join_all(
+            locations
+                .into_iter()
+                .filter(|uri| workers.contains(uri))
+                .map(|uri| async move {
+                    let result = distributed::cancel_flight_info(uri, info, metadata).await;
+                    (uri, result)
+                }),
+        )
+        .await;
+        for (uri, result) in results {
+            match result {
+                Ok(CancelStatus::Cancelled) => status = CancelStatus::Cancelled,
+                Ok(CancelStatus::Cancelling) if status != CancelStatus::Cancelled => {
+                    status = CancelStatus::Cancelling
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Failed to cancel the FlightInfo on worker {uri}: {e}"),
+            }
+        }
+        status
+    }
+
+    /// Returns the foreign keys of the session with fully qualified table
+    /// references.
+    async fn foreign_keys(&self, ctx: &FlightSqlSessionContext) -> Result<Vec<ForeignKey>> {
+        let Some(provider) = &self.foreign_keys else {
+            return Ok(vec![]);
+        };
+        let state = ctx.inner.state();
+        let keys = provider.foreign_keys(&state).await?;
+        let options = state.config().options();
+        Ok(keys
+            .iter()
+            .map(|key| {
+                key.resolve(
+                    &options.catalog.default_catalog,
+                    &options.catalog.default_schema,
+                )
+            })
+            .filter(|key| {
+                ctx.allow_table(&key.pk_table, TableAccess::Read)
+                    && ctx.allow_table(&key.fk_table, TableAccess::Read)
+            })
+            .collect())
+    }
 }
 
+/// The action type of CancelFlightInfo, which FlightSqlService handles as a
+/// custom action
+const CANCEL_FLIGHT_INFO: &str = "CancelFlightInfo";
+
+/// The action types of the session options, which FlightSqlService handles
+/// as custom actions
+const SET_SESSION_OPTIONS: &str = "SetSessionOptions";
+const GET_SESSION_OPTIONS: &str = "GetSessionOptions";
+const CLOSE_SESSION: &str = "CloseSession";
+
 /// The schema for GetTableTypes
 static GET_TABLE_TYPES_SCHEMA: Lazy<SchemaRef> = Lazy::new(|| {
     //TODO: Move this into arrow-flight itself, similar to the builder pattern for CommandGetCatalogs and CommandGetDbSchemas
@@ -161,34 +600,350 @@ static GET_TABLE_TYPES_SCHEMA: Lazy<SchemaRef> = Lazy::new(|| {
     )]))
 });
 
+/// The schema for GetPrimaryKeys
+static GET_PRIMARY_KEYS_SCHEMA: Lazy<SchemaRef> = Lazy::new(|| {
+    Arc::new(Schema::new(vec![
+        Field::new("catalog_name", DataType::Utf8, true),
+        Field::new("db_schema_name", DataType::Utf8, true),
+        Field::new("table_name", DataType::Utf8, false),
+        Field::new("column_name", DataType::Utf8, false),
+        Field::new("key_name", DataType::Utf8, true),
+        Field::new("key_sequence", DataType::Int32, false),
+    ]))
+});
+
 struct FlightSqlSessionContext {
     inner: SessionContext,
     sql_options: Option<SQLOptions>,
+    transactions: Arc<dyn TransactionManager>,
+    transaction_id: Option<Bytes>,
+    /// The authenticated client, if any
+    identity: Option<Identity>,
+    access_policy: Option<Arc<dyn AccessPolicy>>,
+    plan_rewriter: Option<Arc<dyn PlanRewriter>>,
+    /// Invalidated by the DML and DDL statements
+    result_cache: Option<Arc<ResultCache>>,
+    /// The server-side session of the request, if any
+    session_id: Option<String>,
+    /// Keeps the cached results of the request apart, see
+    /// [`SessionStateProvider::cache_scope`]
+    cache_scope: Option<String>,
+    ticket_signer: Option<Arc<TicketSigner>>,
 }
 
 impl FlightSqlSessionContext {
+    /// Executes the following statements within the transaction, if any.
+    async fn bind_transaction(&mut self, transaction_id: Option<Bytes>) -> Result<()> {
+        let Some(transaction_id) = transaction_id else {
+            return Ok(());
+        };
+        let state = self
+            .transactions
+            .transaction_state(&transaction_id, self.inner.state(), self.identity.as_ref())
+            .await?;
+        self.inner = SessionContext::new_with_state(state);
+        self.transaction_id = Some(transaction_id);
+        Ok(())
+    }
+
     async fn sql_to_logical_plan(&self, sql: &str) -> DataFusionResult<LogicalPlan> {
         let plan = self.inner.state().create_logical_plan(sql).await?;
-        let verifier = self.sql_options.unwrap_or_default();
-        verifier.verify_plan(&plan)?;
+        self.verify_plan(&plan)?;
+        self.rewrite_plan(plan)
+    }
+
+    /// Takes a substrait plan serialized as [Bytes] and deserializes this to
+    /// a Datafusion [LogicalPlan]
+    async fn substrait_to_logical_plan(&self, substrait: &Bytes) -> DataFusionResult<LogicalPlan> {
+        let substrait_plan = deserialize_bytes(substrait.to_vec()).await?;
+        let plan = from_substrait_plan(&self.inner.state(), &substrait_plan).await?;
+        self.verify_plan(&plan)?;
+        self.rewrite_plan(plan)
+    }
+
+    /// Encodes the prepared statement handle, signed by the TicketSigner, if
+    /// any.
+    fn encode_handle(&self, handle: QueryHandle) -> Result<Bytes> {
+        match &self.ticket_signer {
+            Some(ticket_signer) => ticket_signer.sign(
+                PREPARED_STATEMENT_HANDLE,
+                self.identity.as_ref(),
+                handle.encode(),
+            ),
+            None => Ok(handle.encode()),
+        }
+    }
+
+    /// Decodes a prepared statement handle, verified by the TicketSigner, if
+    /// any.
+    fn decode_handle(&self, handle: Bytes) -> Result<QueryHandle> {
+        let handle = match &self.ticket_signer {
+            Some(ticket_signer) => {
+                ticket_signer.verify(PREPARED_STATEMENT_HANDLE, self.identity.as_ref(), handle)?
+            }
+            None => handle,
+        };
+        Ok(QueryHandle::try_decode(handle)?)
+    }
+
+    /// Plans the SQL query or the Substrait plan of a prepared statement.
+    async fn handle_to_logical_plan(&self, handle: &QueryHandle) -> Result<LogicalPlan> {
+        match handle.substrait_plan() {
+            Some(substrait_plan) => self.substrait_to_logical_plan(substrait_plan).await,
+            None => self.sql_to_logical_plan(handle.query()).await,
+        }
+        .map_err(df_error_to_status)
+    }
+
+    /// Plans a prepared statement and binds its parameters, if any.
+    async fn bound_handle_to_logical_plan(&self, handle: &QueryHandle) -> Result<LogicalPlan> {
+        let plan = self.handle_to_logical_plan(handle).await?;
+        match decode_param_values(handle.parameters()).map_err(arrow_error_to_status)? {
+            Some(param_values) => plan
+                .with_param_values(param_values)
+                .map_err(df_error_to_status),
+            None => Ok(plan),
+        }
+    }
+
+    /// Executes the following statements within the transaction of the
+    /// query of a ticket, if any.
+    async fn bind_command_transaction(&mut self, command: &sql::Command) -> Result<()> {
+        let transaction_id = match command {
+            sql::Command::CommandStatementQuery(query) => query.transaction_id.clone(),
+            sql::Command::CommandStatementSubstraitPlan(query) => query.transaction_id.clone(),
+            sql::Command::CommandPreparedStatementQuery(query) => self
+                .decode_handle(query.prepared_statement_handle.clone())?
+                .transaction_id()
+                .cloned(),
+            _ => None,
+        };
+        self.bind_transaction(transaction_id).await
+    }
+
+    /// Plans the query of a ticket.
+    async fn command_to_logical_plan(&mut self, command: sql::Command) -> Result<LogicalPlan> {
+        self.bind_command_transaction(&command).await?;
+        let plan = match command {
+            sql::Command::CommandStatementQuery(CommandStatementQuery { query, .. }) => self
+                .sql_to_logical_plan(&query)
+                .await
+                .map_err(df_error_to_status)?,
+            sql::Command::CommandPreparedStatementQuery(CommandPreparedStatementQuery {
+                prepared_statement_handle,
+            }) => {
+                let handle = self.decode_handle(prepared_statement_handle)?;
+
+                self.bound_handle_to_logical_plan(&handle).await?
+            }
+            sql::Command::CommandStatementSubstraitPlan(CommandStatementSubstraitPlan {
+                plan,
+                ..
+            }) => {
+                let substrait_bytes = &plan
+                    .ok_or(Status::invalid_argument(
+                        "Expected substrait plan, found None",
+                    ))?
+                    .plan;
+
+                self.substrait_to_logical_plan(substrait_bytes)
+                    .await
+                    .map_err(df_error_to_status)?
+            }
+            command => {
+                return Err(Status::internal(format!(
+                    "statement handle not found: {command:?}"
+                )));
+            }
+        };
+        Ok(plan)
+    }
+
+    /// Deserializes a plan resolved by GetFlightInfo. The plan is verified
+    /// again, but not rewritten since it already was, and its schema must
+    /// still match the IPC encoded schema advertised by the FlightInfo.
+    async fn resolved_to_logical_plan(
+        &self,
+        plan: &Bytes,
+        schema: &Bytes,
+        with_metadata: bool,
+    ) -> Result<LogicalPlan> {
+        let substrait_plan = deserialize_bytes(plan.to_vec())
+            .await
+            .map_err(df_error_to_status)?;
+        let plan = from_substrait_plan(&self.inner.state(), &substrait_plan)
+            .await
+            .map_err(df_error_to_status)?;
+        self.verify_plan(&plan).map_err(df_error_to_status)?;
+
+        let expected =
+            Schema::try_from(IpcMessage(schema.clone())).map_err(arrow_error_to_status)?;
+        let actual = get_schema_for_plan(&plan, with_metadata);
+        if !same_fields(&expected, &actual) {
+            return Err(Status::failed_precondition(format!(
+                "The schema of the query changed since it was planned, expected {expected}, got {actual}"
+            )));
+        }
         Ok(plan)
     }
 
-    async fn execute_sql(&self, sql: &str) -> DataFusionResult<SendableRecordBatchStream> {
-        let plan = self.sql_to_logical_plan(sql).await?;
-        self.execute_logical_plan(plan).await
+    /// Verifies the plan against the configured [`SQLOptions`], the
+    /// transaction it is executed within and the [`AccessPolicy`].
+    ///
+    /// Every plan is verified before being executed, whether it was planned
+    /// from SQL, from Substrait or built by the service itself, so that the
+    /// restrictions hold regardless of how a statement is sent.
+    fn verify_plan(&self, plan: &LogicalPlan) -> DataFusionResult<()> {
+        let verifier = self.sql_options.unwrap_or_default();
+        verifier.verify_plan(plan)?;
+        if let Some(transaction_id) = &self.transaction_id {
+            self.transactions.verify_plan(transaction_id, plan)?;
+        }
+        if self.access_policy.is_some() {
+            for (table, access) in table_accesses(plan)? {
+                if !self.allow_table(&table, access) {
+                    return Err(DataFusionError::External(Box::new(
+                        Status::permission_denied(format!("{access:?} access to {table} denied")),
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Rewrites the verified plan with the [`PlanRewriter`], if any.
+    fn rewrite_plan(&self, plan: LogicalPlan) -> DataFusionResult<LogicalPlan> {
+        match &self.plan_rewriter {
+            Some(plan_rewriter) => {
+                plan_rewriter.rewrite_plan(self.identity.as_ref(), &self.inner.state(), plan)
+            }
+            None => Ok(plan),
+        }
+    }
+
+    /// Returns true if the AccessPolicy allows the access to the table,
+    /// which is resolved against the default catalog and schema.
+    fn allow_table(&self, table: &TableReference, access: TableAccess) -> bool {
+        let Some(access_policy) = &self.access_policy else {
+            return true;
+        };
+        let state = self.inner.state();
+        let options = &state.config_options().catalog;
+        let table = table
+            .clone()
+            .resolve(&options.default_catalog, &options.default_schema);
+        let table = TableReference::full(table.catalog, table.schema, table.table);
+        access_policy.allow_table(self.identity.as_ref(), &table, access)
+    }
+
+    /// Returns true if the AccessPolicy lets the client see the catalog.
+    fn allow_catalog(&self, catalog: &str) -> bool {
+        self.access_policy.as_ref().is_none_or(|access_policy| {
+            access_policy.allow_catalog(self.identity.as_ref(), catalog)
+        })
+    }
+
+    /// Returns true if the AccessPolicy lets the client see the schema.
+    fn allow_schema(&self, catalog: &str, schema: &str) -> bool {
+        self.allow_catalog(catalog)
+            && self.access_policy.as_ref().is_none_or(|access_policy| {
+                access_policy.allow_schema(self.identity.as_ref(), catalog, schema)
+            })
     }
 
     async fn execute_logical_plan(
         &self,
         plan: LogicalPlan,
     ) -> DataFusionResult<SendableRecordBatchStream> {
+        if matches!(plan, LogicalPlan::Dml(_) | LogicalPlan::Ddl(_)) {
+            self.invalidate_results();
+        }
         self.inner
             .execute_logical_plan(plan)
             .await?
             .execute_stream()
             .await
     }
+
+    /// Invalidates the results cached, unless the statements are executed
+    /// within a transaction, whose changes are not visible until committed.
+    fn invalidate_results(&self) {
+        if let (Some(result_cache), None) = (&self.result_cache, &self.transaction_id) {
+            result_cache.invalidate();
+        }
+    }
+
+    /// Returns the number of output partitions of the physical plan of the
+    /// query when they are computed independently of each other, or None
+    /// when every partition would compute the same input again, and for the
+    /// DDL and statements the session executes itself, which have no
+    /// physical plan.
+    async fn partition_count(&self, plan: &LogicalPlan) -> DataFusionResult<Option<usize>> {
+        if matches!(plan, LogicalPlan::Ddl(_) | LogicalPlan::Statement(_)) {
+            return Ok(None);
+        }
+        let physical_plan = self.inner.state().create_physical_plan(plan).await?;
+        Ok(independent_partitions(physical_plan.as_ref())
+            .then(|| physical_plan.output_partitioning().partition_count()))
+    }
+
+    /// Executes a single output partition of the physical plan of the query,
+    /// which must still have the partition count it was planned with, if
+    /// any, so that the partitions of a query cover every row exactly once.
+    async fn execute_partition(
+        &self,
+        plan: LogicalPlan,
+        partition: u32,
+        expected_partition_count: Option<u32>,
+    ) -> DataFusionResult<SendableRecordBatchStream> {
+        let state = self.inner.state();
+        let physical_plan = state.create_physical_plan(&plan).await?;
+        let partition_count = physical_plan.output_partitioning().partition_count();
+        if let Some(expected) = expected_partition_count {
+            if expected as usize != partition_count {
+                return Err(DataFusionError::External(Box::new(
+                    Status::failed_precondition(format!(
+                        "The query was planned with {expected} partitions, got {partition_count}"
+                    )),
+                )));
+            }
+        }
+        let partition = partition as usize;
+        if partition >= partition_count {
+            return plan_err!(
+                "Partition {partition} does not exist, the query has {partition_count} partitions"
+            );
+        }
+        physical_plan.execute(partition, state.task_ctx())
+    }
+
+    /// Executes a DML or DDL plan and returns the number of affected rows.
+    /// Plans other than DML, such as DDL or SET statements, affect no rows.
+    async fn execute_update(&self, plan: LogicalPlan) -> DataFusionResult<i64> {
+        let is_dml = matches!(plan, LogicalPlan::Dml(_));
+        let batches: Vec<RecordBatch> =
+            self.execute_logical_plan(plan).await?.try_collect().await?;
+        if !is_dml {
+            return Ok(0);
+        }
+        // Results cached while the rows were being written are stale
+        self.invalidate_results();
+
+        let mut count = 0;
+        for batch in batches {
+            let Some(column) = batch.column_by_name("count") else {
+                continue;
+            };
+            let column = column.as_primitive_opt::<UInt64Type>().ok_or_else(|| {
+                DataFusionError::Internal(format!(
+                    "Expected a UInt64 count column, got {}",
+                    column.data_type()
+                ))
+            })?;
+            count += column.iter().flatten().sum::<u64>();
+        }
+        Ok(count as i64)
+    }
 }
 
 #[tonic::async_trait]
@@ -197,13 +952,37 @@ impl ArrowFlightSqlService for FlightSqlService {
 
     async fn do_handshake(
         &self,
-        _request: Request<Streaming<HandshakeRequest>>,
+        request: Request<Streaming<HandshakeRequest>>,
     ) -> Result<Response<Pin<Box<dyn Stream<Item = Result<HandshakeResponse>> + Send>>>> {
         info!("do_handshake");
         // Favor middleware over handshake
         // https://github.com/apache/arrow/issues/23836
         // https://github.com/apache/arrow/issues/25848
-        Err(Status::unimplemented("handshake is not supported"))
+        let Some(authenticator) = &self.authenticator else {
+            return Err(Status::unimplemented("handshake is not supported"));
+        };
+
+        // Basic credentials are exchanged for a bearer token, which is
+        // returned both in the authorization header and as the payload
+        let (username, password) = basic_credentials(request.metadata())?;
+        let identity = authenticator
+            .authenticate_basic(&username, &password)
+            .await?;
+        let token = authenticator.issue_token(&identity).await?;
+        let authorization = format!("Bearer {token}")
+            .parse()
+            .map_err(|_| Status::internal("Invalid bearer token"))?;
+
+        let result = HandshakeResponse {
+            protocol_version: 0,
+            payload: token.into(),
+        };
+        let output = futures::stream::once(async { Ok(result) });
+        let mut response = Response::new(Box::pin(output) as _);
+        response
+            .metadata_mut()
+            .insert("authorization", authorization);
+        Ok(response)
     }
 
     async fn do_get_fallback(
@@ -211,102 +990,91 @@ impl ArrowFlightSqlService for FlightSqlService {
         request: Request<Ticket>,
         _message: Any,
     ) -> Result<Response<<Self as FlightService>::DoGetStream>> {
-        let (request, ctx) = self.new_context(request).await?;
-
-        let ticket = CommandTicket::try_decode(request.into_inner().ticket)
-            .map_err(flight_error_to_status)?;
-
-        match ticket.command {
-            sql::Command::CommandStatementQuery(CommandStatementQuery { query, .. }) => {
-                // print!("Query: {query}\n");
-
-                let stream = ctx.execute_sql(&query).await.map_err(df_error_to_status)?;
-                let arrow_schema = stream.schema();
-                let arrow_stream = stream.map(|i| {
-                    let batch = i.map_err(|e| FlightError::ExternalError(e.into()))?;
-                    Ok(batch)
-                });
-
-                let flight_data_stream = FlightDataEncoderBuilder::new()
-                    .with_schema(arrow_schema)
-                    .build(arrow_stream)
-                    .map_err(flight_error_to_status)
-                    .boxed();
-
-                Ok(Response::new(flight_data_stream))
-            }
-            sql::Command::CommandPreparedStatementQuery(CommandPreparedStatementQuery {
-                prepared_statement_handle,
-            }) => {
-                let handle = QueryHandle::try_decode(prepared_statement_handle)?;
-
-                let mut plan = ctx
-                    .sql_to_logical_plan(handle.query())
-                    .await
-                    .map_err(df_error_to_status)?;
-
-                if let Some(param_values) =
-                    decode_param_values(handle.parameters()).map_err(arrow_error_to_status)?
-                {
-                    plan = plan
-                        .with_param_values(param_values)
-                        .map_err(df_error_to_status)?;
+        let (request, mut ctx) = self.new_context(request).await?;
+
+        let ticket = self.decode_ticket(request.into_inner().ticket, ctx.identity.as_ref())?;
+
+        let plan = match &ticket.plan {
+            // Plans resolved by GetFlightInfo are executed without planning
+            // the command again, nor rewriting the plan, and are thus only
+            // trusted when signed by the services of the deployment
+            Some(plan) => {
+                let accepts_plans = self.config.plan_in_ticket || self.workers.is_some();
+                if !accepts_plans || self.ticket_signer.is_none() {
+                    return Err(Status::unauthenticated(
+                        "Tickets carrying a plan are not accepted",
+                    ));
                 }
-
-                let stream = ctx
-                    .execute_logical_plan(plan)
-                    .await
-                    .map_err(df_error_to_status)?;
-                let arrow_schema = stream.schema();
-                let arrow_stream = stream.map(|i| {
-                    let batch = i.map_err(|e| FlightError::ExternalError(e.into()))?;
-                    Ok(batch)
-                });
-
-                let flight_data_stream = FlightDataEncoderBuilder::new()
-                    .with_schema(arrow_schema)
-                    .build(arrow_stream)
-                    .map_err(flight_error_to_status)
-                    .boxed();
-
-                Ok(Response::new(flight_data_stream))
+                let schema = ticket.schema.as_ref().ok_or_else(|| {
+                    Status::invalid_argument("Tickets carrying a plan must carry its schema")
+                })?;
+                ctx.bind_command_transaction(&ticket.command).await?;
+                ctx.resolved_to_logical_plan(plan, schema, self.config.schema_with_metadata)
+                    .await?
             }
-            sql::Command::CommandStatementSubstraitPlan(CommandStatementSubstraitPlan {
-                plan,
-                ..
-            }) => {
-                let substrait_bytes = &plan
-                    .ok_or(Status::invalid_argument(
-                        "Expected substrait plan, found None",
-                    ))?
-                    .plan;
-
-                let plan = parse_substrait_bytes(&ctx, substrait_bytes).await?;
-
-                let state = ctx.inner.state();
-                let df = DataFrame::new(state, plan);
-
-                let stream = df.execute_stream().await.map_err(df_error_to_status)?;
-                let arrow_schema = stream.schema();
-                let arrow_stream = stream.map(|i| {
-                    let batch = i.map_err(|e| FlightError::ExternalError(e.into()))?;
-                    Ok(batch)
-                });
-
-                let flight_data_stream = FlightDataEncoderBuilder::new()
-                    .with_schema(arrow_schema)
-                    .build(arrow_stream)
-                    .map_err(flight_error_to_status)
-                    .boxed();
+            None => ctx.command_to_logical_plan(ticket.command).await?,
+        };
 
-                Ok(Response::new(flight_data_stream))
+        // Queries outside of transactions are served from the result cache,
+        // unless Substrait cannot represent their plan
+        let cached_query = self
+            .result_cache
+            .as_ref()
+            .filter(|_| ctx.transaction_id.is_none() && is_query(&plan))
+            .and_then(|result_cache| {
+                let key = result_cache
+                    .key(
+                        &ctx.inner.state(),
+                        &plan,
+                        ticket.partition,
+                        ctx.identity.as_ref(),
+                        ctx.session_id.as_deref(),
+                        ctx.cache_scope.as_deref(),
+                    )
+                    .ok()?;
+                Some((result_cache, key))
+            });
+        let cached = cached_query
+            .as_ref()
+            .and_then(|(result_cache, key)| result_cache.get(key));
+
+        let stream = match cached {
+            Some(stream) => stream,
+            None => {
+                // Partitioned tickets execute a single partition of the query
+                let stream = match ticket.partition {
+                    Some(partition) => {
+                        ctx.execute_partition(plan, partition, ticket.partition_count)
+                            .await
+                    }
+                    None => ctx.execute_logical_plan(plan).await,
+                }
+                .map_err(df_error_to_status)?;
+                match cached_query {
+                    Some((result_cache, key)) => result_cache.record(key, stream),
+                    None => stream,
+                }
             }
-            _ => {
-                return Err(Status::internal(format!(
-                    "statement handle not found: {:?}",
-                    ticket.command
-                )));
+        };
+        let arrow_schema = stream.schema();
+        let arrow_stream = stream.map(|i| {
+            let batch = i.map_err(|e| FlightError::ExternalError(e.into()))?;
+            Ok(batch)
+        });
+
+        let flight_data_stream = FlightDataEncoderBuilder::new()
+            .with_schema(arrow_schema)
+            .build(arrow_stream)
+            .map_err(flight_error_to_status)
+            .boxed();
+
+        // Queries planned by this service can be cancelled while they are fetched
+        match ticket.query_id {
+            Some(query_id) => {
+                let stream = self.queries.execute(query_id, flight_data_stream)?;
+                Ok(Response::new(stream))
             }
+            None => Ok(Response::new(flight_data_stream)),
         }
     }
 
@@ -315,7 +1083,8 @@ impl ArrowFlightSqlService for FlightSqlService {
         query: CommandStatementQuery,
         request: Request<FlightDescriptor>,
     ) -> Result<Response<FlightInfo>> {
-        let (request, ctx) = self.new_context(request).await?;
+        let (request, mut ctx) = self.new_context(request).await?;
+        ctx.bind_transaction(query.transaction_id.clone()).await?;
 
         let sql = &query.query;
         info!("get_flight_info_statement with query={sql}");
@@ -329,15 +1098,18 @@ impl ArrowFlightSqlService for FlightSqlService {
 
         let dataset_schema = get_schema_for_plan(&plan, self.config.schema_with_metadata);
 
-        // Form the response ticket (that the client will pass back to DoGet)
-        let ticket = CommandTicket::new(sql::Command::CommandStatementQuery(query))
-            .try_encode()
-            .map_err(flight_error_to_status)?;
+        let endpoints = self
+            .query_endpoints(
+                &ctx,
+                &plan,
+                &dataset_schema,
+                sql::Command::CommandStatementQuery(query),
+            )
+            .await?;
 
-        let endpoint = FlightEndpoint::new().with_ticket(Ticket { ticket });
-
-        let flight_info = FlightInfo::new()
-            .with_endpoint(endpoint)
+        let flight_info = endpoints
+            .into_iter()
+            .fold(FlightInfo::new(), FlightInfo::with_endpoint)
             // return descriptor we were passed
             .with_descriptor(flight_descriptor)
             .try_with_schema(dataset_schema.as_ref())
@@ -352,7 +1124,8 @@ impl ArrowFlightSqlService for FlightSqlService {
         request: Request<FlightDescriptor>,
     ) -> Result<Response<FlightInfo>> {
         info!("get_flight_info_substrait_plan");
-        let (request, ctx) = self.new_context(request).await?;
+        let (request, mut ctx) = self.new_context(request).await?;
+        ctx.bind_transaction(query.transaction_id.clone()).await?;
 
         let substrait_bytes = &query
             .plan
@@ -362,21 +1135,27 @@ impl ArrowFlightSqlService for FlightSqlService {
             ))?
             .plan;
 
-        let plan = parse_substrait_bytes(&ctx, substrait_bytes).await?;
+        let plan = ctx
+            .substrait_to_logical_plan(substrait_bytes)
+            .await
+            .map_err(df_error_to_status)?;
 
         let flight_descriptor = request.into_inner();
 
         let dataset_schema = get_schema_for_plan(&plan, self.config.schema_with_metadata);
 
-        // Form the response ticket (that the client will pass back to DoGet)
-        let ticket = CommandTicket::new(sql::Command::CommandStatementSubstraitPlan(query))
-            .try_encode()
-            .map_err(flight_error_to_status)?;
+        let endpoints = self
+            .query_endpoints(
+                &ctx,
+                &plan,
+                &dataset_schema,
+                sql::Command::CommandStatementSubstraitPlan(query),
+            )
+            .await?;
 
-        let endpoint = FlightEndpoint::new().with_ticket(Ticket { ticket });
-
-        let flight_info = FlightInfo::new()
-            .with_endpoint(endpoint)
+        let flight_info = endpoints
+            .into_iter()
+            .fold(FlightInfo::new(), FlightInfo::with_endpoint)
             // return descriptor we were passed
             .with_descriptor(flight_descriptor)
             .try_with_schema(dataset_schema.as_ref())
@@ -390,32 +1169,32 @@ impl ArrowFlightSqlService for FlightSqlService {
         cmd: CommandPreparedStatementQuery,
         request: Request<FlightDescriptor>,
     ) -> Result<Response<FlightInfo>> {
-        let (request, ctx) = self.new_context(request).await?;
+        let (request, mut ctx) = self.new_context(request).await?;
 
-        let handle = QueryHandle::try_decode(cmd.prepared_statement_handle.clone())
-            .map_err(|e| Status::internal(format!("Error decoding handle: {e}")))?;
+        let handle = ctx.decode_handle(cmd.prepared_statement_handle.clone())?;
+        ctx.bind_transaction(handle.transaction_id().cloned())
+            .await?;
 
         info!("get_flight_info_prepared_statement with handle={handle}");
 
         let flight_descriptor = request.into_inner();
 
-        let sql = handle.query();
-        let plan = ctx
-            .sql_to_logical_plan(sql)
-            .await
-            .map_err(df_error_to_status)?;
+        let plan = ctx.bound_handle_to_logical_plan(&handle).await?;
 
         let dataset_schema = get_schema_for_plan(&plan, self.config.schema_with_metadata);
 
-        // Form the response ticket (that the client will pass back to DoGet)
-        let ticket = CommandTicket::new(sql::Command::CommandPreparedStatementQuery(cmd))
-            .try_encode()
-            .map_err(flight_error_to_status)?;
+        let endpoints = self
+            .query_endpoints(
+                &ctx,
+                &plan,
+                &dataset_schema,
+                sql::Command::CommandPreparedStatementQuery(cmd),
+            )
+            .await?;
 
-        let endpoint = FlightEndpoint::new().with_ticket(Ticket { ticket });
-
-        let flight_info = FlightInfo::new()
-            .with_endpoint(endpoint)
+        let flight_info = endpoints
+            .into_iter()
+            .fold(FlightInfo::new(), FlightInfo::with_endpoint)
             // return descriptor we were passed
             .with_descriptor(flight_descriptor)
             .try_with_schema(dataset_schema.as_ref())
@@ -517,78 +1296,141 @@ impl ArrowFlightSqlService for FlightSqlService {
 
     async fn get_flight_info_sql_info(
         &self,
-        _query: CommandGetSqlInfo,
+        query: CommandGetSqlInfo,
         request: Request<FlightDescriptor>,
     ) -> Result<Response<FlightInfo>> {
         info!("get_flight_info_sql_info");
-        let (_, _) = self.new_context(request).await?;
+        let (request, _ctx) = self.new_context(request).await?;
 
-        Err(Status::unimplemented("Implement CommandGetSqlInfo"))
+        let flight_descriptor = request.into_inner();
+        let ticket = Ticket {
+            ticket: query.as_any().encode_to_vec().into(),
+        };
+        let endpoint = FlightEndpoint::new().with_ticket(ticket);
+
+        let flight_info = FlightInfo::new()
+            .try_with_schema(SqlInfoDataBuilder::schema())
+            .map_err(arrow_error_to_status)?
+            .with_endpoint(endpoint)
+            .with_descriptor(flight_descriptor);
+
+        Ok(Response::new(flight_info))
     }
 
     async fn get_flight_info_primary_keys(
         &self,
-        _query: CommandGetPrimaryKeys,
+        query: CommandGetPrimaryKeys,
         request: Request<FlightDescriptor>,
     ) -> Result<Response<FlightInfo>> {
         info!("get_flight_info_primary_keys");
-        let (_, _) = self.new_context(request).await?;
+        let (request, _ctx) = self.new_context(request).await?;
 
-        Err(Status::unimplemented(
-            "Implement get_flight_info_primary_keys",
-        ))
+        let flight_descriptor = request.into_inner();
+        let ticket = Ticket {
+            ticket: query.as_any().encode_to_vec().into(),
+        };
+        let endpoint = FlightEndpoint::new().with_ticket(ticket);
+
+        let flight_info = FlightInfo::new()
+            .try_with_schema(&GET_PRIMARY_KEYS_SCHEMA)
+            .map_err(arrow_error_to_status)?
+            .with_endpoint(endpoint)
+            .with_descriptor(flight_descriptor);
+
+        Ok(Response::new(flight_info))
     }
 
     async fn get_flight_info_exported_keys(
         &self,
-        _query: CommandGetExportedKeys,
+        query: CommandGetExportedKeys,
         request: Request<FlightDescriptor>,
     ) -> Result<Response<FlightInfo>> {
         info!("get_flight_info_exported_keys");
-        let (_, _) = self.new_context(request).await?;
+        let (request, _ctx) = self.new_context(request).await?;
 
-        Err(Status::unimplemented(
-            "Implement get_flight_info_exported_keys",
-        ))
+        let flight_descriptor = request.into_inner();
+        let ticket = Ticket {
+            ticket: query.as_any().encode_to_vec().into(),
+        };
+        let endpoint = FlightEndpoint::new().with_ticket(ticket);
+
+        let flight_info = FlightInfo::new()
+            .try_with_schema(&GET_FOREIGN_KEYS_SCHEMA)
+            .map_err(arrow_error_to_status)?
+            .with_endpoint(endpoint)
+            .with_descriptor(flight_descriptor);
+
+        Ok(Response::new(flight_info))
     }
 
     async fn get_flight_info_imported_keys(
         &self,
-        _query: CommandGetImportedKeys,
+        query: CommandGetImportedKeys,
         request: Request<FlightDescriptor>,
     ) -> Result<Response<FlightInfo>> {
         info!("get_flight_info_imported_keys");
-        let (_, _) = self.new_context(request).await?;
+        let (request, _ctx) = self.new_context(request).await?;
 
-        Err(Status::unimplemented(
-            "Implement get_flight_info_imported_keys",
-        ))
+        let flight_descriptor = request.into_inner();
+        let ticket = Ticket {
+            ticket: query.as_any().encode_to_vec().into(),
+        };
+        let endpoint = FlightEndpoint::new().with_ticket(ticket);
+
+        let flight_info = FlightInfo::new()
+            .try_with_schema(&GET_FOREIGN_KEYS_SCHEMA)
+            .map_err(arrow_error_to_status)?
+            .with_endpoint(endpoint)
+            .with_descriptor(flight_descriptor);
+
+        Ok(Response::new(flight_info))
     }
 
     async fn get_flight_info_cross_reference(
         &self,
-        _query: CommandGetCrossReference,
+        query: CommandGetCrossReference,
         request: Request<FlightDescriptor>,
     ) -> Result<Response<FlightInfo>> {
         info!("get_flight_info_cross_reference");
-        let (_, _) = self.new_context(request).await?;
+        let (request, _ctx) = self.new_context(request).await?;
+
+        let flight_descriptor = request.into_inner();
+        let ticket = Ticket {
+            ticket: query.as_any().encode_to_vec().into(),
+        };
+        let endpoint = FlightEndpoint::new().with_ticket(ticket);
+
+        let flight_info = FlightInfo::new()
+            .try_with_schema(&GET_FOREIGN_KEYS_SCHEMA)
+            .map_err(arrow_error_to_status)?
+            .with_endpoint(endpoint)
+            .with_descriptor(flight_descriptor);
 
-        Err(Status::unimplemented(
-            "Implement get_flight_info_cross_reference",
-        ))
+        Ok(Response::new(flight_info))
     }
 
     async fn get_flight_info_xdbc_type_info(
         &self,
-        _query: CommandGetXdbcTypeInfo,
+        query: CommandGetXdbcTypeInfo,
         request: Request<FlightDescriptor>,
     ) -> Result<Response<FlightInfo>> {
         info!("get_flight_info_xdbc_type_info");
-        let (_, _) = self.new_context(request).await?;
+        let (request, ctx) = self.new_context(request).await?;
+        let type_info = xdbc_type_info(&ctx.inner.state()).map_err(flight_error_to_status)?;
 
-        Err(Status::unimplemented(
-            "Implement get_flight_info_xdbc_type_info",
-        ))
+        let flight_descriptor = request.into_inner();
+        let ticket = Ticket {
+            ticket: query.as_any().encode_to_vec().into(),
+        };
+        let endpoint = FlightEndpoint::new().with_ticket(ticket);
+
+        let flight_info = FlightInfo::new()
+            .try_with_schema(&query.into_builder(&type_info).schema())
+            .map_err(arrow_error_to_status)?
+            .with_endpoint(endpoint)
+            .with_descriptor(flight_descriptor);
+
+        Ok(Response::new(flight_info))
     }
 
     async fn do_get_statement(
@@ -624,7 +1466,9 @@ impl ArrowFlightSqlService for FlightSqlService {
 
         let mut builder = query.into_builder();
         for catalog_name in &catalog_names {
-            builder.append(catalog_name);
+            if ctx.allow_catalog(catalog_name) {
+                builder.append(catalog_name);
+            }
         }
         let schema = builder.schema();
         let batch = builder.build();
@@ -648,7 +1492,9 @@ impl ArrowFlightSqlService for FlightSqlService {
         if let Some(catalog_name) = &catalog_name {
             if let Some(catalog) = ctx.inner.catalog(catalog_name) {
                 for schema_name in &catalog.schema_names() {
-                    builder.append(catalog_name, schema_name);
+                    if ctx.allow_schema(catalog_name, schema_name) {
+                        builder.append(catalog_name, schema_name);
+                    }
                 }
             }
         };
@@ -675,8 +1521,19 @@ impl ArrowFlightSqlService for FlightSqlService {
         if let Some(catalog_name) = &catalog_name {
             if let Some(catalog) = ctx.inner.catalog(catalog_name) {
                 for schema_name in &catalog.schema_names() {
+                    if !ctx.allow_schema(catalog_name, schema_name) {
+                        continue;
+                    }
                     if let Some(schema) = catalog.schema(schema_name) {
                         for table_name in &schema.table_names() {
+                            let table_ref = TableReference::full(
+                                catalog_name.as_str(),
+                                schema_name.as_str(),
+                                table_name.as_str(),
+                            );
+                            if !ctx.allow_table(&table_ref, TableAccess::Read) {
+                                continue;
+                            }
                             if let Some(table) =
                                 schema.table(table_name).await.map_err(df_error_to_status)?
                             {
@@ -732,79 +1589,229 @@ impl ArrowFlightSqlService for FlightSqlService {
 
     async fn do_get_sql_info(
         &self,
-        _query: CommandGetSqlInfo,
+        query: CommandGetSqlInfo,
         request: Request<Ticket>,
     ) -> Result<Response<<Self as FlightService>::DoGetStream>> {
         info!("do_get_sql_info");
-        let (_, _) = self.new_context(request).await?;
+        let (_request, ctx) = self.new_context(request).await?;
+        let sql_info = self.sql_info(&ctx).map_err(flight_error_to_status)?;
 
-        Err(Status::unimplemented("Implement do_get_sql_info"))
+        let builder = query.into_builder(&sql_info);
+        let schema = builder.schema();
+        let batch = builder.build();
+        let stream = FlightDataEncoderBuilder::new()
+            .with_schema(schema)
+            .build(futures::stream::once(async { batch }))
+            .map_err(Status::from);
+        Ok(Response::new(Box::pin(stream)))
     }
 
     async fn do_get_primary_keys(
         &self,
-        _query: CommandGetPrimaryKeys,
+        query: CommandGetPrimaryKeys,
         request: Request<Ticket>,
     ) -> Result<Response<<Self as FlightService>::DoGetStream>> {
         info!("do_get_primary_keys");
-        let (_, _) = self.new_context(request).await?;
+        let (_request, ctx) = self.new_context(request).await?;
+
+        let catalog_names = match &query.catalog {
+            Some(catalog_name) => vec![catalog_name.clone()],
+            None => ctx.inner.catalog_names(),
+        };
+
+        let mut catalog_name_builder = StringBuilder::new();
+        let mut db_schema_name_builder = StringBuilder::new();
+        let mut table_name_builder = StringBuilder::new();
+        let mut column_name_builder = StringBuilder::new();
+        let mut key_name_builder = StringBuilder::new();
+        let mut key_sequence_builder = Int32Builder::new();
+
+        // Catalogs and schemas are visited in sorted order, so the rows are
+        // ordered by catalog_name, db_schema_name, table_name and key_sequence.
+        for catalog_name in catalog_names.iter().collect::<BTreeSet<_>>() {
+            let Some(catalog) = ctx.inner.catalog(catalog_name) else {
+                continue;
+            };
+            let schema_names = match &query.db_schema {
+                Some(schema_name) => vec![schema_name.clone()],
+                None => catalog.schema_names(),
+            };
+            for schema_name in schema_names.iter().collect::<BTreeSet<_>>() {
+                let table_ref = TableReference::full(
+                    catalog_name.as_str(),
+                    schema_name.as_str(),
+                    query.table.as_str(),
+                );
+                if !ctx.allow_table(&table_ref, TableAccess::Read) {
+                    continue;
+                }
+                let Some(schema) = catalog.schema(schema_name) else {
+                    continue;
+                };
+                let Some(table) = schema
+                    .table(&query.table)
+                    .await
+                    .map_err(df_error_to_status)?
+                else {
+                    continue;
+                };
+                let table_schema = table.schema();
+                for constraint in table.constraints().into_iter().flat_map(|c| c.iter()) {
+                    let Constraint::PrimaryKey(indices) = constraint else {
+                        continue;
+                    };
+                    for (sequence, index) in indices.iter().enumerate() {
+                        catalog_name_builder.append_value(catalog_name);
+                        db_schema_name_builder.append_value(schema_name);
+                        table_name_builder.append_value(&query.table);
+                        column_name_builder.append_value(table_schema.field(*index).name());
+                        key_name_builder.append_null();
+                        key_sequence_builder.append_value(sequence as i32 + 1);
+                    }
+                }
+            }
+        }
+
+        let batch = RecordBatch::try_new(
+            GET_PRIMARY_KEYS_SCHEMA.clone(),
+            vec![
+                Arc::new(catalog_name_builder.finish()),
+                Arc::new(db_schema_name_builder.finish()),
+                Arc::new(table_name_builder.finish()),
+                Arc::new(column_name_builder.finish()),
+                Arc::new(key_name_builder.finish()),
+                Arc::new(key_sequence_builder.finish()),
+            ],
+        )
+        .map_err(arrow_error_to_status)?;
 
-        Err(Status::unimplemented("Implement do_get_primary_keys"))
+        let stream = FlightDataEncoderBuilder::new()
+            .with_schema(GET_PRIMARY_KEYS_SCHEMA.clone())
+            .build(futures::stream::once(async { Ok(batch) }))
+            .map_err(Status::from);
+        Ok(Response::new(Box::pin(stream)))
     }
 
     async fn do_get_exported_keys(
         &self,
-        _query: CommandGetExportedKeys,
+        query: CommandGetExportedKeys,
         request: Request<Ticket>,
     ) -> Result<Response<<Self as FlightService>::DoGetStream>> {
         info!("do_get_exported_keys");
-        let (_, _) = self.new_context(request).await?;
+        let (_request, ctx) = self.new_context(request).await?;
+
+        let mut keys: Vec<_> = self
+            .foreign_keys(&ctx)
+            .await?
+            .into_iter()
+            .filter(|key| {
+                table_matches(
+                    &key.pk_table,
+                    query.catalog.as_deref(),
+                    query.db_schema.as_deref(),
+                    &query.table,
+                )
+            })
+            .collect();
+        keys.sort_by_key(|key| (key.fk_table.clone(), key.fk_key_name.clone()));
 
-        Err(Status::unimplemented("Implement do_get_exported_keys"))
+        foreign_keys_response(&keys)
     }
 
     async fn do_get_imported_keys(
         &self,
-        _query: CommandGetImportedKeys,
+        query: CommandGetImportedKeys,
         request: Request<Ticket>,
     ) -> Result<Response<<Self as FlightService>::DoGetStream>> {
         info!("do_get_imported_keys");
-        let (_, _) = self.new_context(request).await?;
+        let (_request, ctx) = self.new_context(request).await?;
+
+        let mut keys: Vec<_> = self
+            .foreign_keys(&ctx)
+            .await?
+            .into_iter()
+            .filter(|key| {
+                table_matches(
+                    &key.fk_table,
+                    query.catalog.as_deref(),
+                    query.db_schema.as_deref(),
+                    &query.table,
+                )
+            })
+            .collect();
+        keys.sort_by_key(|key| (key.pk_table.clone(), key.pk_key_name.clone()));
 
-        Err(Status::unimplemented("Implement do_get_imported_keys"))
+        foreign_keys_response(&keys)
     }
 
     async fn do_get_cross_reference(
         &self,
-        _query: CommandGetCrossReference,
+        query: CommandGetCrossReference,
         request: Request<Ticket>,
     ) -> Result<Response<<Self as FlightService>::DoGetStream>> {
         info!("do_get_cross_reference");
-        let (_, _) = self.new_context(request).await?;
+        let (_request, ctx) = self.new_context(request).await?;
 
-        Err(Status::unimplemented("Implement do_get_cross_reference"))
+        let mut keys: Vec<_> = self
+            .foreign_keys(&ctx)
+            .await?
+            .into_iter()
+            .filter(|key| {
+                table_matches(
+                    &key.pk_table,
+                    query.pk_catalog.as_deref(),
+                    query.pk_db_schema.as_deref(),
+                    &query.pk_table,
+                ) && table_matches(
+                    &key.fk_table,
+                    query.fk_catalog.as_deref(),
+                    query.fk_db_schema.as_deref(),
+                    &query.fk_table,
+                )
+            })
+            .collect();
+        keys.sort_by_key(|key| (key.fk_table.clone(), key.fk_key_name.clone()));
+
+        foreign_keys_response(&keys)
     }
 
     async fn do_get_xdbc_type_info(
         &self,
-        _query: CommandGetXdbcTypeInfo,
+        query: CommandGetXdbcTypeInfo,
         request: Request<Ticket>,
     ) -> Result<Response<<Self as FlightService>::DoGetStream>> {
         info!("do_get_xdbc_type_info");
-        let (_, _) = self.new_context(request).await?;
+        let (_request, ctx) = self.new_context(request).await?;
+        let type_info = xdbc_type_info(&ctx.inner.state()).map_err(flight_error_to_status)?;
 
-        Err(Status::unimplemented("Implement do_get_xdbc_type_info"))
+        // The builder handles applying the data_type filter.
+        let builder = query.into_builder(&type_info);
+        let schema = builder.schema();
+        let batch = builder.build();
+        let stream = FlightDataEncoderBuilder::new()
+            .with_schema(schema)
+            .build(futures::stream::once(async { batch }))
+            .map_err(Status::from);
+        Ok(Response::new(Box::pin(stream)))
     }
 
     async fn do_put_statement_update(
         &self,
-        _ticket: CommandStatementUpdate,
+        ticket: CommandStatementUpdate,
         request: Request<PeekableFlightDataStream>,
     ) -> Result<i64, Status> {
         info!("do_put_statement_update");
-        let (_, _) = self.new_context(request).await?;
+        let (_, mut ctx) = self.new_context(request).await?;
+        ctx.bind_transaction(ticket.transaction_id).await?;
+
+        let sql = &ticket.query;
+        info!("do_put_statement_update query={sql:?}");
+        let plan = ctx
+            .sql_to_logical_plan(sql)
+            .await
+            .map_err(df_error_to_status)?;
 
-        Err(Status::unimplemented("Implement do_put_statement_update"))
+        ctx.execute_update(plan).await.map_err(df_error_to_status)
     }
 
     async fn do_put_prepared_statement_query(
@@ -813,9 +1820,9 @@ impl ArrowFlightSqlService for FlightSqlService {
         request: Request<PeekableFlightDataStream>,
     ) -> Result<DoPutPreparedStatementResult, Status> {
         info!("do_put_prepared_statement_query");
-        let (request, _) = self.new_context(request).await?;
+        let (request, ctx) = self.new_context(request).await?;
 
-        let mut handle = QueryHandle::try_decode(query.prepared_statement_handle)?;
+        let mut handle = ctx.decode_handle(query.prepared_statement_handle)?;
 
         info!(
             "do_action_create_prepared_statement query={:?}",
@@ -855,7 +1862,7 @@ impl ArrowFlightSqlService for FlightSqlService {
         handle.set_parameters(Some(parameters.into()));
 
         let res = DoPutPreparedStatementResult {
-            prepared_statement_handle: Some(Bytes::from(handle)),
+            prepared_statement_handle: Some(ctx.encode_handle(handle)?),
         };
 
         Ok(res)
@@ -863,28 +1870,210 @@ impl ArrowFlightSqlService for FlightSqlService {
 
     async fn do_put_prepared_statement_update(
         &self,
-        _handle: CommandPreparedStatementUpdate,
+        query: CommandPreparedStatementUpdate,
         request: Request<PeekableFlightDataStream>,
     ) -> Result<i64, Status> {
         info!("do_put_prepared_statement_update");
-        let (_, _) = self.new_context(request).await?;
+        let (request, mut ctx) = self.new_context(request).await?;
+
+        let handle = ctx.decode_handle(query.prepared_statement_handle)?;
+        ctx.bind_transaction(handle.transaction_id().cloned())
+            .await?;
+
+        info!("do_put_prepared_statement_update with handle={handle}");
+        let plan = ctx.handle_to_logical_plan(&handle).await?;
+
+        // Parameters are either sent along with the update, one row per
+        // execution, or were bound beforehand by do_put_prepared_statement_query.
+        // In the latter case the stream only carries the descriptor, without
+        // any IPC message to decode.
+        let flight_data = request
+            .into_inner()
+            .try_filter(|data| futures::future::ready(!data.data_header.is_empty()));
+        let mut decoder = FlightDataDecoder::new(flight_data.map_err(status_to_flight_error));
+        let mut param_rows = decode_param_rows(&mut decoder).await?;
+        if param_rows.is_empty() {
+            param_rows
+                .extend(decode_param_values(handle.parameters()).map_err(arrow_error_to_status)?);
+        }
+
+        // statements like "CREATE TABLE.." or "SET datafusion.nnn.." have no
+        // parameters and are executed once
+        if param_rows.is_empty() {
+            return ctx.execute_update(plan).await.map_err(df_error_to_status);
+        }
+
+        let mut count = 0;
+        for param_values in param_rows {
+            let plan = plan
+                .clone()
+                .with_param_values(param_values)
+                .map_err(df_error_to_status)?;
+            count += ctx.execute_update(plan).await.map_err(df_error_to_status)?;
+        }
+        Ok(count)
+    }
+
+    async fn do_put_statement_ingest(
+        &self,
+        ticket: CommandStatementIngest,
+        request: Request<PeekableFlightDataStream>,
+    ) -> Result<i64, Status> {
+        info!("do_put_statement_ingest");
+        let (request, mut ctx) = self.new_context(request).await?;
+        ctx.bind_transaction(ticket.transaction_id).await?;
+
+        // Tables are created as MemTables, which DataFusion cannot make
+        // temporary
+        if ticket.temporary {
+            return Err(Status::unimplemented(
+                "Ingesting into temporary tables is not supported",
+            ));
+        }
+
+        let table_ref = match (ticket.catalog, ticket.schema) {
+            (Some(catalog), Some(schema)) => TableReference::full(catalog, schema, ticket.table),
+            (None, Some(schema)) => TableReference::partial(schema, ticket.table),
+            // A catalog without a schema names the default schema of that
+            // catalog
+            (Some(catalog), None) => {
+                let state = ctx.inner.state();
+                let schema = state.config_options().catalog.default_schema.clone();
+                TableReference::full(catalog, schema, ticket.table)
+            }
+            (None, None) => TableReference::bare(ticket.table),
+        };
+        info!("do_put_statement_ingest table={table_ref}");
+
+        let mut decoder =
+            FlightDataDecoder::new(request.into_inner().map_err(status_to_flight_error));
+        let schema = decode_schema(&mut decoder).await?;
+
+        // Missing tables are created as MemTables, while replaced tables keep
+        // their TableProvider: its rows are deleted before the insert
+        let options = ticket.table_definition_options.unwrap_or_default();
+        let exists = ctx
+            .inner
+            .table_exist(table_ref.clone())
+            .map_err(df_error_to_status)?;
+        let replace = match (exists, options.if_exists(), options.if_not_exist()) {
+            (true, TableExistsOption::Append, _) => false,
+            (true, TableExistsOption::Replace, _) => true,
+            (true, _, _) => {
+                return Err(Status::already_exists(format!(
+                    "Table {table_ref} already exists"
+                )))
+            }
+            (false, _, TableNotExistOption::Create) => false,
+            (false, _, _) => {
+                return Err(Status::not_found(format!(
+                    "Table {table_ref} does not exist"
+                )))
+            }
+        };
+        let empty = LogicalPlan::EmptyRelation(EmptyRelation {
+            produce_one_row: false,
+            schema: Arc::new(
+                DFSchema::try_from(schema.as_ref().clone()).map_err(df_error_to_status)?,
+            ),
+        });
+        if !exists {
+            let plan = LogicalPlan::Ddl(DdlStatement::CreateMemoryTable(CreateMemoryTable {
+                name: table_ref.clone(),
+                constraints: Constraints::default(),
+                input: Arc::new(empty.clone()),
+                if_not_exists: false,
+                or_replace: false,
+                column_defaults: vec![],
+                temporary: false,
+            }));
+            ctx.verify_plan(&plan).map_err(df_error_to_status)?;
+            ctx.execute_update(plan).await.map_err(df_error_to_status)?;
+        }
 
-        // statements like "CREATE TABLE.." or "SET datafusion.nnn.." call this function
-        // and we are required to return some row count here
-        Ok(-1)
+        // The record batches are streamed into the table through its
+        // TableProvider::insert_into
+        let batches = decoder
+            .try_filter_map(|msg| async move {
+                match msg.payload {
+                    DecodedPayload::RecordBatch(batch) => Ok(Some(batch)),
+                    _ => Ok(None),
+                }
+            })
+            .map_err(|e| DataFusionError::External(e.into()));
+        let source = StreamingTable::try_new(
+            schema.clone(),
+            vec![Arc::new(IngestPartition::new(Box::pin(
+                RecordBatchStreamAdapter::new(schema, batches),
+            )))],
+        )
+        .map_err(df_error_to_status)?;
+        let input = LogicalPlanBuilder::scan("ingest", provider_as_source(Arc::new(source)), None)
+            .and_then(|builder| builder.build())
+            .map_err(df_error_to_status)?;
+        let target = ctx
+            .inner
+            .table_provider(table_ref.clone())
+            .await
+            .map_err(df_error_to_status)?;
+        let target = provider_as_source(target);
+        // The insert is verified from an empty relation, as the scan of the
+        // ingested batches is not an access to a table of the catalog
+        let verified = LogicalPlanBuilder::insert_into(
+            empty,
+            table_ref.clone(),
+            target.clone(),
+            InsertOp::Append,
+        )
+        .and_then(|builder| builder.build())
+        .map_err(df_error_to_status)?;
+        ctx.verify_plan(&verified).map_err(df_error_to_status)?;
+
+        if replace {
+            let delete = LogicalPlanBuilder::scan(table_ref.clone(), target.clone(), None)
+                .and_then(|builder| builder.build())
+                .map_err(df_error_to_status)?;
+            let delete = LogicalPlan::Dml(DmlStatement::new(
+                table_ref.clone(),
+                target.clone(),
+                WriteOp::Delete,
+                Arc::new(delete),
+            ));
+            ctx.verify_plan(&delete).map_err(df_error_to_status)?;
+            ctx.execute_update(delete)
+                .await
+                .map_err(df_error_to_status)?;
+        }
+
+        let plan = LogicalPlanBuilder::insert_into(input, table_ref, target, InsertOp::Append)
+            .and_then(|builder| builder.build())
+            .map_err(df_error_to_status)?;
+        ctx.execute_update(plan).await.map_err(df_error_to_status)
     }
 
     async fn do_put_substrait_plan(
         &self,
-        _query: CommandStatementSubstraitPlan,
+        query: CommandStatementSubstraitPlan,
         request: Request<PeekableFlightDataStream>,
     ) -> Result<i64, Status> {
-        info!("do_put_prepared_statement_update");
-        let (_, _) = self.new_context(request).await?;
+        info!("do_put_substrait_plan");
+        let (_, mut ctx) = self.new_context(request).await?;
+        ctx.bind_transaction(query.transaction_id).await?;
 
-        Err(Status::unimplemented(
-            "Implement do_put_prepared_statement_update",
-        ))
+        let substrait_bytes = &query
+            .plan
+            .as_ref()
+            .ok_or(Status::invalid_argument(
+                "Expected substrait plan, found None",
+            ))?
+            .plan;
+
+        let plan = ctx
+            .substrait_to_logical_plan(substrait_bytes)
+            .await
+            .map_err(df_error_to_status)?;
+
+        ctx.execute_update(plan).await.map_err(df_error_to_status)
     }
 
     async fn do_action_create_prepared_statement(
@@ -892,7 +2081,8 @@ impl ArrowFlightSqlService for FlightSqlService {
         query: ActionCreatePreparedStatementRequest,
         request: Request<Action>,
     ) -> Result<ActionCreatePreparedStatementResult, Status> {
-        let (_, ctx) = self.new_context(request).await?;
+        let (_, mut ctx) = self.new_context(request).await?;
+        ctx.bind_transaction(query.transaction_id.clone()).await?;
 
         let sql = query.query.clone();
         info!(
@@ -913,10 +2103,10 @@ impl ArrowFlightSqlService for FlightSqlService {
         let parameter_schema =
             encode_schema(parameter_schema.as_ref()).map_err(arrow_error_to_status)?;
 
-        let handle = QueryHandle::new(sql, None);
+        let handle = QueryHandle::new(sql, None).with_transaction_id(query.transaction_id);
 
         let res = ActionCreatePreparedStatementResult {
-            prepared_statement_handle: Bytes::from(handle),
+            prepared_statement_handle: ctx.encode_handle(handle)?,
             dataset_schema,
             parameter_schema,
         };
@@ -942,15 +2132,43 @@ impl ArrowFlightSqlService for FlightSqlService {
 
     async fn do_action_create_prepared_substrait_plan(
         &self,
-        _query: ActionCreatePreparedSubstraitPlanRequest,
+        query: ActionCreatePreparedSubstraitPlanRequest,
         request: Request<Action>,
     ) -> Result<ActionCreatePreparedStatementResult, Status> {
         info!("do_action_create_prepared_substrait_plan");
-        let (_, _) = self.new_context(request).await?;
+        let (_, mut ctx) = self.new_context(request).await?;
+        ctx.bind_transaction(query.transaction_id.clone()).await?;
 
-        Err(Status::unimplemented(
-            "Implement do_action_create_prepared_substrait_plan",
-        ))
+        let substrait_bytes = query
+            .plan
+            .ok_or(Status::invalid_argument(
+                "Expected substrait plan, found None",
+            ))?
+            .plan;
+
+        let plan = ctx
+            .substrait_to_logical_plan(&substrait_bytes)
+            .await
+            .map_err(df_error_to_status)?;
+
+        let dataset_schema = get_schema_for_plan(&plan, self.config.schema_with_metadata);
+        let parameter_schema = parameter_schema_for_plan(&plan).map_err(|e| e.as_ref().clone())?;
+
+        let dataset_schema =
+            encode_schema(dataset_schema.as_ref()).map_err(arrow_error_to_status)?;
+        let parameter_schema =
+            encode_schema(parameter_schema.as_ref()).map_err(arrow_error_to_status)?;
+
+        let handle = QueryHandle::new_substrait(substrait_bytes, None)
+            .with_transaction_id(query.transaction_id);
+
+        let res = ActionCreatePreparedStatementResult {
+            prepared_statement_handle: ctx.encode_handle(handle)?,
+            dataset_schema,
+            parameter_schema,
+        };
+
+        Ok(res)
     }
 
     async fn do_action_begin_transaction(
@@ -958,74 +2176,345 @@ impl ArrowFlightSqlService for FlightSqlService {
         _query: ActionBeginTransactionRequest,
         request: Request<Action>,
     ) -> Result<ActionBeginTransactionResult, Status> {
-        let (_, _) = self.new_context(request).await?;
+        let (_, ctx) = self.new_context(request).await?;
 
         info!("do_action_begin_transaction");
-        Err(Status::unimplemented(
-            "Implement do_action_begin_transaction",
-        ))
+        let transaction_id = self
+            .transactions
+            .begin_transaction(&ctx.inner.state(), ctx.identity.as_ref())
+            .await?;
+
+        Ok(ActionBeginTransactionResult { transaction_id })
     }
 
     async fn do_action_end_transaction(
         &self,
-        _query: ActionEndTransactionRequest,
+        query: ActionEndTransactionRequest,
         request: Request<Action>,
     ) -> Result<(), Status> {
         info!("do_action_end_transaction");
-        let (_, _) = self.new_context(request).await?;
+        let (_, ctx) = self.new_context(request).await?;
 
-        Err(Status::unimplemented("Implement do_action_end_transaction"))
+        let action = match query.action() {
+            EndTransaction::Unspecified => {
+                return Err(Status::invalid_argument(
+                    "end transaction action must be commit or rollback",
+                ))
+            }
+            action => action,
+        };
+        self.transactions
+            .end_transaction(&query.transaction_id, action, ctx.identity.as_ref())
+            .await?;
+        self.savepoints.end_transaction(&query.transaction_id);
+        if let (Some(result_cache), EndTransaction::Commit) = (&self.result_cache, action) {
+            result_cache.invalidate();
+        }
+        Ok(())
     }
 
     async fn do_action_begin_savepoint(
         &self,
-        _query: ActionBeginSavepointRequest,
+        query: ActionBeginSavepointRequest,
         request: Request<Action>,
     ) -> Result<ActionBeginSavepointResult, Status> {
         info!("do_action_begin_savepoint");
-        let (_, _) = self.new_context(request).await?;
+        let (_, ctx) = self.new_context(request).await?;
+
+        self.transactions
+            .begin_savepoint(&query.transaction_id, &query.name, ctx.identity.as_ref())
+            .await?;
+        let savepoint_id = self.savepoints.begin(query.transaction_id, query.name);
 
-        Err(Status::unimplemented("Implement do_action_begin_savepoint"))
+        Ok(ActionBeginSavepointResult { savepoint_id })
     }
 
     async fn do_action_end_savepoint(
         &self,
-        _query: ActionEndSavepointRequest,
+        query: ActionEndSavepointRequest,
         request: Request<Action>,
     ) -> Result<(), Status> {
         info!("do_action_end_savepoint");
-        let (_, _) = self.new_context(request).await?;
+        let (_, ctx) = self.new_context(request).await?;
 
-        Err(Status::unimplemented("Implement do_action_end_savepoint"))
+        // arrow-flight does not export the EndSavepoint enum of the protocol
+        let action = match query.action {
+            1 => EndSavepoint::Release,
+            2 => EndSavepoint::Rollback,
+            _ => {
+                return Err(Status::invalid_argument(
+                    "end savepoint action must be release or rollback",
+                ))
+            }
+        };
+        let savepoint = self.savepoints.get(&query.savepoint_id)?;
+        self.transactions
+            .end_savepoint(
+                &savepoint.transaction_id,
+                &savepoint.name,
+                action,
+                ctx.identity.as_ref(),
+            )
+            .await?;
+        self.savepoints.end(&savepoint, action);
+        Ok(())
     }
 
     async fn do_action_cancel_query(
         &self,
-        _query: ActionCancelQueryRequest,
+        query: ActionCancelQueryRequest,
         request: Request<Action>,
     ) -> Result<ActionCancelQueryResult, Status> {
         info!("do_action_cancel_query");
-        let (_, _) = self.new_context(request).await?;
+        let (request, ctx) = self.new_context(request).await?;
 
-        Err(Status::unimplemented("Implement do_action_cancel_query"))
+        let info = FlightInfo::decode(query.info)
+            .map_err(|e| Status::invalid_argument(format!("Invalid FlightInfo: {e}")))?;
+        // arrow-flight does not export the CancelResult enum of the protocol,
+        // which has the same values as CancelStatus
+        let result = self
+            .cancel_flight_info(&info, ctx.identity.as_ref(), request.metadata())
+            .await;
+
+        Ok(ActionCancelQueryResult {
+            result: result as i32,
+        })
+    }
+
+    async fn do_action_fallback(
+        &self,
+        request: Request<Action>,
+    ) -> Result<Response<<Self as FlightService>::DoActionStream>> {
+        let (request, ctx) = self.new_context(request).await?;
+        let (metadata, extensions, action) = request.into_parts();
+        let identity = extensions.get::<Identity>();
+
+        match action.r#type.as_str() {
+            CANCEL_FLIGHT_INFO => {
+                info!("do_action_fallback {CANCEL_FLIGHT_INFO}");
+                let request = CancelFlightInfoRequest::decode(action.body).map_err(|e| {
+                    Status::invalid_argument(format!("Invalid CancelFlightInfoRequest: {e}"))
+                })?;
+                let info = request
+                    .info
+                    .ok_or_else(|| Status::invalid_argument("Expected FlightInfo, found None"))?;
+                let result = self.cancel_flight_info(&info, identity, &metadata).await;
+                let result = CancelFlightInfoResult::new(result);
+
+                let output = futures::stream::once(async move {
+                    Ok(arrow_flight::Result {
+                        body: result.encode_to_vec().into(),
+                    })
+                });
+                Ok(Response::new(Box::pin(output)))
+            }
+            SET_SESSION_OPTIONS => {
+                info!("do_action_fallback {SET_SESSION_OPTIONS}");
+                let request = SetSessionOptionsRequest::decode(action.body).map_err(|e| {
+                    Status::invalid_argument(format!("Invalid SetSessionOptionsRequest: {e}"))
+                })?;
+                let cookie = self.open_session(&metadata, identity, &ctx)?;
+                let result = set_session_options(&ctx, request);
+                Ok(action_response(result.encode_to_vec(), cookie))
+            }
+            GET_SESSION_OPTIONS => {
+                info!("do_action_fallback {GET_SESSION_OPTIONS}");
+                let cookie = self.open_session(&metadata, identity, &ctx)?;
+                let result = get_session_options(&ctx);
+                Ok(action_response(result.encode_to_vec(), cookie))
+            }
+            CLOSE_SESSION => {
+                info!("do_action_fallback {CLOSE_SESSION}");
+                let sessions = self
+                    .sessions
+                    .as_ref()
+                    .ok_or_else(|| Status::unimplemented("Sessions are not enabled"))?;
+                let session_id = session_id(&metadata)
+                    .ok_or_else(|| Status::not_found("The request has no session"))?;
+                sessions.close(&session_id, identity)?;
+                let result = CloseSessionResult {
+                    status: close_session_result::Status::Closed as i32,
+                };
+                Ok(action_response(result.encode_to_vec(), None))
+            }
+            action_type => Err(Status::invalid_argument(format!(
+                "do_action: The defined request is invalid: {action_type:?}"
+            ))),
+        }
+    }
+
+    async fn list_custom_actions(&self) -> Option<Vec<Result<ActionType, Status>>> {
+        let mut actions = vec![Ok(ActionType {
+            r#type: CANCEL_FLIGHT_INFO.to_string(),
+            description: "Cancel the execution of a FlightInfo\n
+                Request Message: CancelFlightInfoRequest\n
+                Response Message: CancelFlightInfoResult"
+                .into(),
+        })];
+        if self.sessions.is_some() {
+            actions.extend([
+                Ok(ActionType {
+                    r#type: SET_SESSION_OPTIONS.to_string(),
+                    description: "Set options of the session\n
+                        Request Message: SetSessionOptionsRequest\n
+                        Response Message: SetSessionOptionsResult"
+                        .into(),
+                }),
+                Ok(ActionType {
+                    r#type: GET_SESSION_OPTIONS.to_string(),
+                    description: "Get the options of the session\n
+                        Request Message: GetSessionOptionsRequest\n
+                        Response Message: GetSessionOptionsResult"
+                        .into(),
+                }),
+                Ok(ActionType {
+                    r#type: CLOSE_SESSION.to_string(),
+                    description: "Close the session\n
+                        Request Message: CloseSessionRequest\n
+                        Response Message: CloseSessionResult"
+                        .into(),
+                }),
+            ]);
+        }
+        Some(actions)
     }
 
     async fn register_sql_info(&self, _id: i32, _result: &SqlInfo) {}
 }
 
-/// Takes a substrait plan serialized as [Bytes] and deserializes this to
-/// a Datafusion [LogicalPlan]
-async fn parse_substrait_bytes(
+/// Builds the response of an action returning a single result, setting the
+/// session cookie if any.
+fn action_response(
+    body: Vec<u8>,
+    cookie: Option<MetadataValue<Ascii>>,
+) -> Response<<FlightSqlService as FlightService>::DoActionStream> {
+    let output =
+        futures::stream::once(async move { Ok(arrow_flight::Result { body: body.into() }) });
+    let mut response = Response::new(Box::pin(output) as _);
+    if let Some(cookie) = cookie {
+        response.metadata_mut().insert("set-cookie", cookie);
+    }
+    response
+}
+
+/// Sets the DataFusion configuration options of the session.
+fn set_session_options(
     ctx: &FlightSqlSessionContext,
-    substrait: &Bytes,
-) -> Result<LogicalPlan> {
-    let substrait_plan = deserialize_bytes(substrait.to_vec())
-        .await
-        .map_err(df_error_to_status)?;
+    request: SetSessionOptionsRequest,
+) -> SetSessionOptionsResult {
+    use set_session_options_result::{Error, ErrorValue};
+
+    let state = ctx.inner.state_ref();
+    let mut state = state.write();
+    let options = state.config_mut().options_mut();
+    let names: BTreeSet<String> = options.entries().into_iter().map(|e| e.key).collect();
+
+    let mut errors = HashMap::new();
+    for (name, value) in request.session_options {
+        let value = match value.option_value {
+            Some(OptionValue::StringValue(value)) => Some(value),
+            Some(OptionValue::BoolValue(value)) => Some(value.to_string()),
+            Some(OptionValue::Int64Value(value)) => Some(value.to_string()),
+            Some(OptionValue::DoubleValue(value)) => Some(value.to_string()),
+            Some(OptionValue::StringListValue(_)) | None => None,
+        };
+        let error = if !names.contains(&name) {
+            Some(ErrorValue::InvalidName)
+        } else {
+            match value.map(|value| options.set(&name, &value)) {
+                Some(Ok(())) => None,
+                Some(Err(_)) | None => Some(ErrorValue::InvalidValue),
+            }
+        };
+        if let Some(error) = error {
+            errors.insert(
+                name,
+                Error {
+                    value: error as i32,
+                },
+            );
+        }
+    }
+    SetSessionOptionsResult { errors }
+}
 
-    from_substrait_plan(&ctx.inner.state(), &substrait_plan)
-        .await
-        .map_err(df_error_to_status)
+/// Returns the DataFusion configuration options of the session that have a
+/// value.
+fn get_session_options(ctx: &FlightSqlSessionContext) -> GetSessionOptionsResult {
+    let session_options = ctx
+        .inner
+        .copied_config()
+        .options()
+        .entries()
+        .into_iter()
+        .filter_map(|entry| {
+            let value = entry.value?;
+            Some((
+                entry.key,
+                SessionOptionValue {
+                    option_value: Some(OptionValue::StringValue(value)),
+                },
+            ))
+        })
+        .collect();
+    GetSessionOptionsResult { session_options }
+}
+
+/// A [`PartitionStream`] handing out the record batches of a bulk ingestion,
+/// which can only be executed once.
+struct IngestPartition {
+    schema: SchemaRef,
+    stream: Mutex<Option<SendableRecordBatchStream>>,
+}
+
+impl IngestPartition {
+    fn new(stream: SendableRecordBatchStream) -> Self {
+        Self {
+            schema: stream.schema(),
+            stream: Mutex::new(Some(stream)),
+        }
+    }
+}
+
+impl std::fmt::Debug for IngestPartition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IngestPartition")
+            .field("schema", &self.schema)
+            .finish()
+    }
+}
+
+impl PartitionStream for IngestPartition {
+    fn schema(&self) -> &SchemaRef {
+        &self.schema
+    }
+
+    fn execute(&self, _ctx: Arc<TaskContext>) -> SendableRecordBatchStream {
+        self.stream.lock().unwrap().take().unwrap_or_else(|| {
+            Box::pin(RecordBatchStreamAdapter::new(
+                self.schema.clone(),
+                futures::stream::once(async {
+                    Err(DataFusionError::Execution(
+                        "Ingested record batches can only be read once".to_string(),
+                    ))
+                }),
+            ))
+        })
+    }
+}
+
+/// Streams the rows of GetExportedKeys, GetImportedKeys or GetCrossReference
+/// for the already filtered and ordered `keys`.
+fn foreign_keys_response(
+    keys: &[ForeignKey],
+) -> Result<Response<<FlightSqlService as FlightService>::DoGetStream>> {
+    let batch = foreign_keys_batch(keys).map_err(arrow_error_to_status)?;
+
+    let stream = FlightDataEncoderBuilder::new()
+        .with_schema(GET_FOREIGN_KEYS_SCHEMA.clone())
+        .build(futures::stream::once(async { Ok(batch) }))
+        .map_err(Status::from);
+    Ok(Response::new(Box::pin(stream)))
 }
 
 /// Encodes the schema IPC encoded (schema_bytes)
@@ -1040,6 +2529,47 @@ fn encode_schema(schema: &Schema) -> std::result::Result<Bytes, ArrowError> {
     Ok(schema)
 }
 
+/// Returns true if the plan is a query, rather than a DML, DDL or other
+/// statement.
+fn is_query(plan: &LogicalPlan) -> bool {
+    !matches!(
+        plan,
+        LogicalPlan::Dml(_) | LogicalPlan::Ddl(_) | LogicalPlan::Statement(_)
+    )
+}
+
+/// Returns true if each output partition of the physical plan only reads
+/// the same partition of the inputs of the plan, so that executing the
+/// partitions separately costs no more than executing the whole plan.
+///
+/// Repartitions, such as those of aggregates and partitioned joins, read
+/// every partition of their input, as do the operators collecting their
+/// input into fewer partitions, such as the build side of a join.
+fn independent_partitions(plan: &dyn ExecutionPlan) -> bool {
+    if plan.as_any().is::<RepartitionExec>() {
+        return false;
+    }
+    let partition_count = plan.output_partitioning().partition_count();
+    plan.children().into_iter().all(|child| {
+        child.output_partitioning().partition_count() == partition_count
+            && independent_partitions(child.as_ref())
+    })
+}
+
+/// Returns true if the schemas have the same field names and types. The
+/// metadata and nullability of the fields may differ once a plan went
+/// through Substrait.
+fn same_fields(expected: &Schema, actual: &Schema) -> bool {
+    expected.fields().len() == actual.fields().len()
+        && expected
+            .fields()
+            .iter()
+            .zip(actual.fields())
+            .all(|(expected, actual)| {
+                expected.name() == actual.name() && expected.data_type() == actual.data_type()
+            })
+}
+
 /// Return the schema for the specified logical plan
 fn get_schema_for_plan(logical_plan: &LogicalPlan, with_metadata: bool) -> SchemaRef {
     let schema: SchemaRef = if with_metadata {
@@ -1113,7 +2643,11 @@ fn flight_error_to_status(err: FlightError) -> Status {
 }
 
 fn df_error_to_status(err: DataFusionError) -> Status {
-    Status::internal(format!("{err:?}"))
+    match err {
+        // A Status raised while planning, such as a denied access
+        DataFusionError::External(err) if err.is::<Status>() => *err.downcast::<Status>().unwrap(),
+        err => Status::internal(format!("{err:?}")),
+    }
 }
 
 fn status_to_flight_error(status: Status) -> FlightError {
@@ -1140,6 +2674,21 @@ async fn decode_schema(decoder: &mut FlightDataDecoder) -> Result<SchemaRef, Sta
     ))
 }
 
+// Decode each row of the parameter flight data as ParamValues
+async fn decode_param_rows(decoder: &mut FlightDataDecoder) -> Result<Vec<ParamValues>, Status> {
+    let mut param_rows = Vec::new();
+    while let Some(msg) = decoder.try_next().await? {
+        if let DecodedPayload::RecordBatch(batch) = msg.payload {
+            for row in 0..batch.num_rows() {
+                let param_values =
+                    record_to_param_values(&batch.slice(row, 1)).map_err(df_error_to_status)?;
+                param_rows.push(param_values);
+            }
+        }
+    }
+    Ok(param_rows)
+}
+
 // Decode parameter ipc stream as ParamValues
 fn decode_param_values(parameters: Option<&[u8]>) -> Result<Option<ParamValues>, ArrowError> {
     parameters
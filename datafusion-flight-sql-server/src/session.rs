@@ -1,14 +1,33 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
 use async_trait::async_trait;
-use datafusion::execution::context::SessionState;
-use tonic::{Request, Status};
+use datafusion::execution::context::{SessionContext, SessionState};
+
+use super::auth::Identity;
+use tonic::{metadata::MetadataMap, Request, Status};
 
 type Result<T, E = Status> = std::result::Result<T, E>;
 
 // SessionStateProvider is a trait used to provide a SessionState for a given
 // request.
+//
+// When the service is served over mutual TLS, the certificates of the client
+// are available through Request::peer_certs.
 #[async_trait]
 pub trait SessionStateProvider: Sync + Send {
     async fn new_context(&self, request: &Request<()>) -> Result<SessionState>;
+
+    /// Returns the scope the cached results of the request are shared
+    /// within, which must differ between requests the provider answers with
+    /// different data, such as per tenant. Results are already kept apart
+    /// per identity and per session.
+    fn cache_scope(&self, _request: &Request<()>) -> Option<String> {
+        None
+    }
 }
 
 // StaticSessionStateProvider is a simple implementation of SessionStateProvider that
@@ -29,3 +48,206 @@ impl SessionStateProvider for StaticSessionStateProvider {
         Ok(self.state.clone())
     }
 }
+
+/// The cookie identifying the server-side session of a client, as defined by
+/// the Flight session options.
+pub const SESSION_COOKIE: &str = "arrow_flight_session_id";
+
+/// Returns the session id sent in the cookies of the request, if any.
+pub(crate) fn session_id(metadata: &MetadataMap) -> Option<String> {
+    metadata
+        .get_all("cookie")
+        .iter()
+        .filter_map(|cookies| cookies.to_str().ok())
+        .flat_map(|cookies| cookies.split(';'))
+        .find_map(|cookie| {
+            let (name, value) = cookie.trim().split_once('=')?;
+            (name == SESSION_COOKIE).then(|| value.to_string())
+        })
+}
+
+struct Session {
+    ctx: SessionContext,
+    /// The client that created the session, when authenticated
+    identity: Option<Identity>,
+    last_used: Instant,
+}
+
+/// The server-side sessions, by session id.
+///
+/// A session keeps the SessionContext its statements are executed with, so
+/// that settings, tables and views created by a request are visible to the
+/// next requests of the session. Sessions idle for longer than
+/// `idle_timeout` are expired.
+///
+/// A session can only be used by the client that created it.
+pub(crate) struct SessionStore {
+    sessions: Mutex<HashMap<String, Session>>,
+    idle_timeout: Duration,
+    max_sessions: usize,
+}
+
+impl SessionStore {
+    pub fn new(idle_timeout: Duration, max_sessions: usize) -> Self {
+        Self {
+            sessions: Mutex::default(),
+            idle_timeout,
+            max_sessions,
+        }
+    }
+
+    /// Creates a session of the client executing statements with the
+    /// SessionContext and returns its id.
+    pub fn create(&self, ctx: SessionContext, identity: Option<Identity>) -> Result<String> {
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, session| session.last_used.elapsed() < self.idle_timeout);
+        if sessions.len() >= self.max_sessions {
+            return Err(Status::resource_exhausted(format!(
+                "Too many open sessions, the limit is {}",
+                self.max_sessions
+            )));
+        }
+
+        let session_id = uuid::Uuid::new_v4().to_string();
+        sessions.insert(
+            session_id.clone(),
+            Session {
+                ctx,
+                identity,
+                last_used: Instant::now(),
+            },
+        );
+        Ok(session_id)
+    }
+
+    /// Returns the SessionContext of the session, sharing its state.
+    pub fn get(&self, session_id: &str, identity: Option<&Identity>) -> Result<SessionContext> {
+        let mut sessions = self.sessions.lock().unwrap();
+        match sessions.get_mut(session_id) {
+            Some(session) if session.identity.as_ref() != identity => {
+                Err(session_not_found(session_id))
+            }
+            Some(session) if session.last_used.elapsed() < self.idle_timeout => {
+                session.last_used = Instant::now();
+                Ok(session.ctx.clone())
+            }
+            Some(_) => {
+                sessions.remove(session_id);
+                Err(session_not_found(session_id))
+            }
+            None => Err(session_not_found(session_id)),
+        }
+    }
+
+    /// Closes the session.
+    pub fn close(&self, session_id: &str, identity: Option<&Identity>) -> Result<()> {
+        let mut sessions = self.sessions.lock().unwrap();
+        match sessions.get(session_id) {
+            Some(session) if session.identity.as_ref() == identity => {
+                sessions.remove(session_id);
+                Ok(())
+            }
+            _ => Err(session_not_found(session_id)),
+        }
+    }
+}
+
+fn session_not_found(session_id: &str) -> Status {
+    Status::not_found(format!("Session {session_id} does not exist or expired"))
+}
+
+// The messages of the SetSessionOptions, GetSessionOptions and CloseSession
+// actions, which arrow-flight does not provide.
+
+/// The value of a session option.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SessionOptionValue {
+    #[prost(oneof = "session_option_value::OptionValue", tags = "1, 2, 3, 4, 5")]
+    pub option_value: Option<session_option_value::OptionValue>,
+}
+
+pub mod session_option_value {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct StringListValue {
+        #[prost(string, repeated, tag = "1")]
+        pub values: Vec<String>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum OptionValue {
+        #[prost(string, tag = "1")]
+        StringValue(String),
+        #[prost(bool, tag = "2")]
+        BoolValue(bool),
+        #[prost(sfixed64, tag = "3")]
+        Int64Value(i64),
+        #[prost(double, tag = "4")]
+        DoubleValue(f64),
+        #[prost(message, tag = "5")]
+        StringListValue(StringListValue),
+    }
+}
+
+/// Sets session options, creating the session if needed.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SetSessionOptionsRequest {
+    #[prost(map = "string, message", tag = "1")]
+    pub session_options: HashMap<String, SessionOptionValue>,
+}
+
+/// The options that could not be set, by name.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct SetSessionOptionsResult {
+    #[prost(map = "string, message", tag = "1")]
+    pub errors: HashMap<String, set_session_options_result::Error>,
+}
+
+pub mod set_session_options_result {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Error {
+        #[prost(enumeration = "ErrorValue", tag = "1")]
+        pub value: i32,
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+    #[repr(i32)]
+    pub enum ErrorValue {
+        Unspecified = 0,
+        /// The option name is not supported
+        InvalidName = 1,
+        /// The option value is not valid for the option
+        InvalidValue = 2,
+        Error = 3,
+    }
+}
+
+/// Gets the options of the session, creating the session if needed.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetSessionOptionsRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetSessionOptionsResult {
+    #[prost(map = "string, message", tag = "1")]
+    pub session_options: HashMap<String, SessionOptionValue>,
+}
+
+/// Closes the session.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CloseSessionRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct CloseSessionResult {
+    #[prost(enumeration = "close_session_result::Status", tag = "1")]
+    pub status: i32,
+}
+
+pub mod close_session_result {
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+    #[repr(i32)]
+    pub enum Status {
+        Unspecified = 0,
+        Closed = 1,
+        Closing = 2,
+        NotClosable = 3,
+    }
+}
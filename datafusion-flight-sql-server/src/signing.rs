@@ -0,0 +1,240 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use prost::{bytes::Bytes, Message};
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+use tonic::Status;
+
+use super::auth::Identity;
+
+type Result<T, E = Status> = std::result::Result<T, E>;
+
+/// How long signed messages are valid by default.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// What a signed message is, so that a message signed as one cannot be used
+/// as the other.
+pub(crate) const TICKET: &str = "ticket";
+pub(crate) const PREPARED_STATEMENT_HANDLE: &str = "prepared_statement_handle";
+
+/// TicketSigner signs the query tickets and the prepared statement handles
+/// the service hands out to its clients, enabled with
+/// [`FlightSqlService::with_ticket_signer`], so that clients cannot forge
+/// them to run arbitrary SQL. Tickets and handles that fail verification are
+/// rejected with `Unauthenticated`.
+///
+/// Messages are signed with HMAC-SHA256 or, with encryption, encrypted with
+/// AES-256-GCM so that clients cannot read them either. Every service of a
+/// deployment, such as a coordinator and its workers, must share the keys
+/// and whether messages are encrypted.
+///
+/// Messages are bound to the identity of the client they were issued to,
+/// when the service authenticates its clients, and expire after the TTL.
+///
+/// Keys are rotated by first adding the new key as a verification key
+/// everywhere, then signing with it while still verifying with the previous
+/// key, until the messages signed with the previous key expired.
+///
+/// [`FlightSqlService::with_ticket_signer`]: crate::service::FlightSqlService::with_ticket_signer
+pub struct TicketSigner {
+    /// The first key signs, every key verifies
+    keys: Vec<SigningKey>,
+    encrypt: bool,
+    ttl: Duration,
+    random: SystemRandom,
+}
+
+struct SigningKey {
+    id: String,
+    hmac: hmac::Key,
+    aead: LessSafeKey,
+}
+
+impl SigningKey {
+    /// Derives distinct HMAC and AES keys from the secret.
+    fn new(id: String, secret: &[u8]) -> Self {
+        let derive = |label: &[u8]| hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, secret), label);
+        let hmac = hmac::Key::new(hmac::HMAC_SHA256, derive(b"signing").as_ref());
+        let aead = UnboundKey::new(&AES_256_GCM, derive(b"encryption").as_ref())
+            .expect("HMAC-SHA256 tags are valid AES-256 keys");
+        Self {
+            id,
+            hmac,
+            aead: LessSafeKey::new(aead),
+        }
+    }
+}
+
+impl TicketSigner {
+    /// Creates a signer signing with the secret, identified by `key_id`.
+    pub fn new(key_id: impl Into<String>, secret: &[u8]) -> Self {
+        Self {
+            keys: vec![SigningKey::new(key_id.into(), secret)],
+            encrypt: false,
+            ttl: DEFAULT_TTL,
+            random: SystemRandom::new(),
+        }
+    }
+
+    /// Also verifies the messages signed with the secret, such as the
+    /// previous or the next key of a rotation.
+    pub fn with_verification_key(mut self, key_id: impl Into<String>, secret: &[u8]) -> Self {
+        self.keys.push(SigningKey::new(key_id.into(), secret));
+        self
+    }
+
+    /// Encrypts the messages instead of only signing them. Messages that
+    /// are only signed are then rejected, and the other way around.
+    pub fn with_encryption(mut self, encrypt: bool) -> Self {
+        self.encrypt = encrypt;
+        self
+    }
+
+    /// Sets how long the messages are valid after they were signed,
+    /// [`DEFAULT_TTL`] by default.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Signs, or encrypts, the message for the purpose, issued to the
+    /// client with the identity, if any.
+    pub(crate) fn sign(
+        &self,
+        purpose: &str,
+        identity: Option<&Identity>,
+        message: Bytes,
+    ) -> Result<Bytes> {
+        let key = &self.keys[0];
+        let issued_at = now();
+        let header = signed_header(purpose, issued_at, identity);
+        let signed = if self.encrypt {
+            let mut nonce = [0; NONCE_LEN];
+            self.random
+                .fill(&mut nonce)
+                .map_err(|_| Status::internal("Failed to generate a nonce"))?;
+            let mut payload = message.to_vec();
+            key.aead
+                .seal_in_place_append_tag(
+                    Nonce::assume_unique_for_key(nonce),
+                    Aad::from(&header),
+                    &mut payload,
+                )
+                .map_err(|_| Status::internal("Failed to encrypt message"))?;
+            SignedMessage {
+                payload: payload.into(),
+                nonce: Some(Bytes::copy_from_slice(&nonce)),
+                signature: Bytes::new(),
+                key_id: key.id.clone(),
+                issued_at,
+            }
+        } else {
+            let signature = hmac::sign(&key.hmac, &[header.as_slice(), &message].concat());
+            SignedMessage {
+                payload: message,
+                nonce: None,
+                signature: Bytes::copy_from_slice(signature.as_ref()),
+                key_id: key.id.clone(),
+                issued_at,
+            }
+        };
+        Ok(signed.encode_to_vec().into())
+    }
+
+    /// Verifies, or decrypts, a message signed for the purpose and issued to
+    /// the client with the identity, and returns its content.
+    pub(crate) fn verify(
+        &self,
+        purpose: &str,
+        identity: Option<&Identity>,
+        message: Bytes,
+    ) -> Result<Bytes> {
+        let invalid = || Status::unauthenticated(format!("Invalid {purpose} signature"));
+        let signed = SignedMessage::decode(message).map_err(|_| invalid())?;
+        let key = self
+            .keys
+            .iter()
+            .find(|key| key.id == signed.key_id)
+            .ok_or_else(invalid)?;
+        let header = signed_header(purpose, signed.issued_at, identity);
+
+        let content = match (signed.nonce, self.encrypt) {
+            (Some(nonce), true) => {
+                let nonce = Nonce::try_assume_unique_for_key(&nonce).map_err(|_| invalid())?;
+                let mut payload = signed.payload.to_vec();
+                let content = key
+                    .aead
+                    .open_in_place(nonce, Aad::from(&header), &mut payload)
+                    .map_err(|_| invalid())?;
+                Bytes::copy_from_slice(content)
+            }
+            (None, false) => {
+                hmac::verify(
+                    &key.hmac,
+                    &[header.as_slice(), &signed.payload].concat(),
+                    &signed.signature,
+                )
+                .map_err(|_| invalid())?;
+                signed.payload
+            }
+            // Only the configured mode is accepted, so that signed messages
+            // cannot be read by the clients of an encrypting service
+            _ => return Err(invalid()),
+        };
+
+        // Checked once authentic, so that the issue time can be trusted
+        if now().saturating_sub(signed.issued_at) > self.ttl.as_secs() {
+            return Err(Status::unauthenticated(format!("Expired {purpose}")));
+        }
+        Ok(content)
+    }
+}
+
+/// The seconds since the Unix epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// The content authenticated along with the payload: the purpose, the
+/// issue time and the identity of the client, each prefixed with its length
+/// so that no two headers are the same.
+fn signed_header(purpose: &str, issued_at: u64, identity: Option<&Identity>) -> Vec<u8> {
+    let mut header = vec![];
+    let mut append = |field: &[u8]| {
+        header.extend_from_slice(&(field.len() as u64).to_be_bytes());
+        header.extend_from_slice(field);
+    };
+    append(purpose.as_bytes());
+    append(&issued_at.to_be_bytes());
+    if let Some(identity) = identity {
+        append(identity.user.as_bytes());
+        for (key, value) in &identity.attributes {
+            append(key.as_bytes());
+            append(value.as_bytes());
+        }
+    }
+    header
+}
+
+/// A signed or encrypted message. Tag 1 is left unused so that it never
+/// decodes as a Flight SQL command, whose `Any` has its type URL there.
+#[derive(Clone, PartialEq, Message)]
+struct SignedMessage {
+    #[prost(bytes = "bytes", tag = "2")]
+    payload: Bytes,
+    /// Set when the payload is encrypted
+    #[prost(bytes = "bytes", optional, tag = "3")]
+    nonce: Option<Bytes>,
+    #[prost(bytes = "bytes", tag = "4")]
+    signature: Bytes,
+    #[prost(string, tag = "5")]
+    key_id: String,
+    /// The seconds since the Unix epoch when the message was signed
+    #[prost(uint64, tag = "6")]
+    issued_at: u64,
+}
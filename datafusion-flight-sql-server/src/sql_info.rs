@@ -0,0 +1,317 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use arrow_flight::sql::{
+    metadata::SqlInfoDataBuilder, SqlInfo, SqlNullOrdering, SqlOuterJoinsSupportLevel,
+    SqlSupportedCaseSensitivity, SqlSupportedGroupBy, SqlSupportedSubqueries,
+    SqlSupportedTransaction, SqlSupportedUnions, SqlSupportsConvert, SqlTransactionIsolationLevel,
+    SupportedSqlGrammar,
+};
+
+use datafusion::{
+    arrow::{datatypes::Schema, ARROW_VERSION},
+    common::{DFSchema, TableReference},
+    datasource::{empty::EmptyTable, provider_as_source},
+    execution::context::{SQLOptions, SessionState},
+    logical_expr::{
+        dml::InsertOp,
+        scalar_doc_sections::{
+            DOC_SECTION_BINARY_STRING, DOC_SECTION_CONDITIONAL, DOC_SECTION_DATETIME,
+            DOC_SECTION_HASHING, DOC_SECTION_MATH, DOC_SECTION_OTHER, DOC_SECTION_REGEX,
+            DOC_SECTION_STRING,
+        },
+        DdlStatement, DropView, LogicalPlan, LogicalPlanBuilder,
+    },
+    sql::sqlparser::keywords::ALL_KEYWORDS,
+    DATAFUSION_VERSION,
+};
+
+/// The name reported for [`SqlInfo::FlightSqlServerName`].
+pub const SERVER_NAME: &str = "datafusion-flight-sql-server";
+
+/// Returns true if the [`SQLOptions`] reject both DML and DDL statements,
+/// so that clients cannot change any data or table through the service.
+///
+/// SQLOptions only expose their settings through the plans they verify, so
+/// a DML and a DDL plan are verified with them.
+pub(crate) fn read_only(sql_options: &SQLOptions) -> bool {
+    let schema = Arc::new(Schema::empty());
+    let target = provider_as_source(Arc::new(EmptyTable::new(schema)));
+    let dml = LogicalPlanBuilder::empty(false)
+        .build()
+        .and_then(|input| LogicalPlanBuilder::insert_into(input, "t", target, InsertOp::Append))
+        .and_then(LogicalPlanBuilder::build);
+    let ddl = LogicalPlan::Ddl(DdlStatement::DropView(DropView {
+        name: TableReference::bare("t"),
+        if_exists: true,
+        schema: Arc::new(DFSchema::empty()),
+    }));
+    let allowed = |plan: &LogicalPlan| sql_options.verify_plan(plan).is_ok();
+    !dml.is_ok_and(|dml| allowed(&dml)) && !allowed(&ddl)
+}
+
+/// Builds the [`SqlInfo`] entries describing the capabilities of the
+/// DataFusion engine behind the given [`SessionState`].
+///
+/// Entries that depend on the session, such as identifier normalization,
+/// null ordering and the registered functions, are read from the state so
+/// that the reported metadata follows the configuration of the server.
+pub fn default_sql_info(state: &SessionState) -> SqlInfoDataBuilder {
+    let options = state.config().options();
+    let mut builder = SqlInfoDataBuilder::new();
+
+    // Server information
+    builder.append(SqlInfo::FlightSqlServerName, SERVER_NAME);
+    builder.append(SqlInfo::FlightSqlServerVersion, DATAFUSION_VERSION);
+    builder.append(SqlInfo::FlightSqlServerArrowVersion, ARROW_VERSION);
+    // Overridden by the service according to its SQLOptions
+    builder.append(SqlInfo::FlightSqlServerReadOnly, false);
+    builder.append(SqlInfo::FlightSqlServerSql, true);
+    builder.append(SqlInfo::FlightSqlServerSubstrait, true);
+    builder.append(
+        SqlInfo::FlightSqlServerTransaction,
+        SqlSupportedTransaction::None as i32,
+    );
+    builder.append(SqlInfo::FlightSqlServerCancel, true);
+    builder.append(SqlInfo::FlightSqlServerBulkIngestion, true);
+    builder.append(SqlInfo::FlightSqlServerIngestTransactionsSupported, false);
+    builder.append(SqlInfo::FlightSqlServerStatementTimeout, 0);
+    builder.append(SqlInfo::FlightSqlServerTransactionTimeout, 0);
+
+    // SQL grammar
+    builder.append(SqlInfo::SqlDdlCatalog, true);
+    builder.append(SqlInfo::SqlDdlSchema, true);
+    builder.append(SqlInfo::SqlDdlTable, true);
+    let identifier_case = if options.sql_parser.enable_ident_normalization {
+        SqlSupportedCaseSensitivity::SqlCaseSensitivityLowercase
+    } else {
+        SqlSupportedCaseSensitivity::SqlCaseSensitivityUnknown
+    };
+    builder.append(SqlInfo::SqlIdentifierCase, identifier_case as i32);
+    builder.append(SqlInfo::SqlIdentifierQuoteChar, "\"");
+    builder.append(
+        SqlInfo::SqlQuotedIdentifierCase,
+        SqlSupportedCaseSensitivity::SqlCaseSensitivityUnknown as i32,
+    );
+    builder.append(SqlInfo::SqlAllTablesAreSelectable, true);
+    let null_ordering = match options.sql_parser.default_null_ordering.as_str() {
+        "nulls_min" => SqlNullOrdering::SqlNullsSortedLow,
+        "nulls_first" => SqlNullOrdering::SqlNullsSortedAtStart,
+        "nulls_last" => SqlNullOrdering::SqlNullsSortedAtEnd,
+        _ => SqlNullOrdering::SqlNullsSortedHigh,
+    };
+    builder.append(SqlInfo::SqlNullOrdering, null_ordering as i32);
+    builder.append(SqlInfo::SqlKeywords, ALL_KEYWORDS);
+
+    let functions = FunctionNames::new(state);
+    builder.append(SqlInfo::SqlNumericFunctions, functions.numeric);
+    builder.append(SqlInfo::SqlStringFunctions, functions.string);
+    builder.append(SqlInfo::SqlSystemFunctions, functions.system);
+    builder.append(SqlInfo::SqlDatetimeFunctions, functions.datetime);
+
+    builder.append(SqlInfo::SqlSearchStringEscape, "\\");
+    builder.append(SqlInfo::SqlExtraNameCharacters, "");
+    builder.append(SqlInfo::SqlSupportsColumnAliasing, true);
+    builder.append(SqlInfo::SqlNullPlusNullIsNull, true);
+    builder.append(SqlInfo::SqlSupportsConvert, supported_conversions());
+    builder.append(SqlInfo::SqlSupportsTableCorrelationNames, true);
+    builder.append(SqlInfo::SqlSupportsDifferentTableCorrelationNames, false);
+    builder.append(SqlInfo::SqlSupportsExpressionsInOrderBy, true);
+    builder.append(SqlInfo::SqlSupportsOrderByUnrelated, true);
+    builder.append(
+        SqlInfo::SqlSupportedGroupBy,
+        bitmask([
+            SqlSupportedGroupBy::SqlGroupByUnrelated as i32,
+            SqlSupportedGroupBy::SqlGroupByBeyondSelect as i32,
+        ]),
+    );
+    builder.append(SqlInfo::SqlSupportsLikeEscapeClause, true);
+    builder.append(SqlInfo::SqlSupportsNonNullableColumns, true);
+    builder.append(
+        SqlInfo::SqlSupportedGrammar,
+        bitmask([
+            SupportedSqlGrammar::SqlMinimumGrammar as i32,
+            SupportedSqlGrammar::SqlCoreGrammar as i32,
+        ]),
+    );
+    builder.append(SqlInfo::SqlSupportsIntegrityEnhancementFacility, false);
+    builder.append(
+        SqlInfo::SqlOuterJoinsSupportLevel,
+        SqlOuterJoinsSupportLevel::SqlFullOuterJoins as i32,
+    );
+    builder.append(SqlInfo::SqlSchemaTerm, "schema");
+    builder.append(SqlInfo::SqlProcedureTerm, "procedure");
+    builder.append(SqlInfo::SqlCatalogTerm, "catalog");
+    builder.append(SqlInfo::SqlCatalogAtStart, true);
+    builder.append(SqlInfo::SqlSchemasSupportedActions, 0);
+    builder.append(SqlInfo::SqlCatalogsSupportedActions, 0);
+    builder.append(SqlInfo::SqlSupportedPositionedCommands, 0);
+    builder.append(SqlInfo::SqlSelectForUpdateSupported, false);
+    builder.append(SqlInfo::SqlStoredProceduresSupported, false);
+    builder.append(
+        SqlInfo::SqlSupportedSubqueries,
+        bitmask([
+            SqlSupportedSubqueries::SqlSubqueriesInComparisons as i32,
+            SqlSupportedSubqueries::SqlSubqueriesInExists as i32,
+            SqlSupportedSubqueries::SqlSubqueriesInIns as i32,
+        ]),
+    );
+    builder.append(SqlInfo::SqlCorrelatedSubqueriesSupported, true);
+    builder.append(
+        SqlInfo::SqlSupportedUnions,
+        bitmask([
+            SqlSupportedUnions::SqlUnion as i32,
+            SqlSupportedUnions::SqlUnionAll as i32,
+        ]),
+    );
+
+    // Limits, zero means there is no limit or it is unknown
+    for info in [
+        SqlInfo::SqlMaxBinaryLiteralLength,
+        SqlInfo::SqlMaxCharLiteralLength,
+        SqlInfo::SqlMaxColumnNameLength,
+        SqlInfo::SqlMaxColumnsInGroupBy,
+        SqlInfo::SqlMaxColumnsInIndex,
+        SqlInfo::SqlMaxColumnsInOrderBy,
+        SqlInfo::SqlMaxColumnsInSelect,
+        SqlInfo::SqlMaxColumnsInTable,
+        SqlInfo::SqlMaxConnections,
+        SqlInfo::SqlMaxCursorNameLength,
+        SqlInfo::SqlMaxIndexLength,
+        SqlInfo::SqlDbSchemaNameLength,
+        SqlInfo::SqlMaxProcedureNameLength,
+        SqlInfo::SqlMaxCatalogNameLength,
+        SqlInfo::SqlMaxRowSize,
+        SqlInfo::SqlMaxStatementLength,
+        SqlInfo::SqlMaxStatements,
+        SqlInfo::SqlMaxTableNameLength,
+        SqlInfo::SqlMaxTablesInSelect,
+        SqlInfo::SqlMaxUsernameLength,
+    ] {
+        builder.append(info, 0_i64);
+    }
+    builder.append(SqlInfo::SqlMaxRowSizeIncludesBlobs, false);
+
+    // Transactions
+    builder.append(
+        SqlInfo::SqlDefaultTransactionIsolation,
+        SqlTransactionIsolationLevel::SqlTransactionNone as i32,
+    );
+    builder.append(SqlInfo::SqlTransactionsSupported, false);
+    builder.append(
+        SqlInfo::SqlSupportedTransactionsIsolationLevels,
+        bitmask([SqlTransactionIsolationLevel::SqlTransactionNone as i32]),
+    );
+    builder.append(SqlInfo::SqlDataDefinitionCausesTransactionCommit, false);
+    builder.append(SqlInfo::SqlDataDefinitionsInTransactionsIgnored, false);
+    builder.append(SqlInfo::SqlBatchUpdatesSupported, false);
+    builder.append(SqlInfo::SqlSavepointsSupported, false);
+    builder.append(SqlInfo::SqlNamedParametersSupported, false);
+    builder.append(SqlInfo::SqlLocatorsUpdateCopy, false);
+    builder.append(SqlInfo::SqlStoredFunctionsUsingCallSyntaxSupported, false);
+
+    builder
+}
+
+/// Names of the registered scalar functions, grouped by the categories
+/// used by Flight SQL.
+struct FunctionNames {
+    numeric: Vec<String>,
+    string: Vec<String>,
+    system: Vec<String>,
+    datetime: Vec<String>,
+}
+
+impl FunctionNames {
+    fn new(state: &SessionState) -> Self {
+        let mut numeric = BTreeSet::new();
+        let mut string = BTreeSet::new();
+        let mut system = BTreeSet::new();
+        let mut datetime = BTreeSet::new();
+
+        for (name, udf) in state.scalar_functions() {
+            let Some(section) = udf.documentation().map(|doc| doc.doc_section.label) else {
+                continue;
+            };
+            let category = if section == DOC_SECTION_MATH.label {
+                &mut numeric
+            } else if section == DOC_SECTION_STRING.label
+                || section == DOC_SECTION_BINARY_STRING.label
+                || section == DOC_SECTION_REGEX.label
+            {
+                &mut string
+            } else if section == DOC_SECTION_DATETIME.label {
+                &mut datetime
+            } else if section == DOC_SECTION_CONDITIONAL.label
+                || section == DOC_SECTION_HASHING.label
+                || section == DOC_SECTION_OTHER.label
+            {
+                &mut system
+            } else {
+                continue;
+            };
+            category.insert(name.to_uppercase());
+        }
+
+        Self {
+            numeric: numeric.into_iter().collect(),
+            string: string.into_iter().collect(),
+            system: system.into_iter().collect(),
+            datetime: datetime.into_iter().collect(),
+        }
+    }
+}
+
+/// The conversions supported by `CAST`, for [`SqlInfo::SqlSupportsConvert`].
+fn supported_conversions() -> BTreeMap<i32, Vec<i32>> {
+    use SqlSupportsConvert::*;
+
+    let numeric = [
+        SqlConvertBigint,
+        SqlConvertDecimal,
+        SqlConvertFloat,
+        SqlConvertInteger,
+        SqlConvertNumeric,
+        SqlConvertReal,
+        SqlConvertSmallint,
+        SqlConvertTinyint,
+    ];
+    let strings = [SqlConvertChar, SqlConvertLongvarchar, SqlConvertVarchar];
+    let binary = [
+        SqlConvertBinary,
+        SqlConvertLongvarbinary,
+        SqlConvertVarbinary,
+    ];
+    let temporal = [SqlConvertDate, SqlConvertTime, SqlConvertTimestamp];
+
+    let mut conversions = BTreeMap::new();
+    let mut add = |from: &[SqlSupportsConvert], to: &[&[SqlSupportsConvert]]| {
+        for from in from {
+            let entry: &mut Vec<i32> = conversions.entry(*from as i32).or_default();
+            entry.extend(to.iter().flat_map(|to| to.iter().map(|to| *to as i32)));
+            entry.sort_unstable();
+            entry.dedup();
+        }
+    };
+
+    add(&numeric, &[&numeric, &strings, &[SqlConvertBit]]);
+    add(&[SqlConvertBit], &[&numeric, &strings, &[SqlConvertBit]]);
+    add(
+        &strings,
+        &[&numeric, &strings, &binary, &temporal, &[SqlConvertBit]],
+    );
+    add(&binary, &[&strings, &binary]);
+    add(&temporal, &[&strings, &temporal]);
+    add(
+        &[SqlConvertIntervalDayTime, SqlConvertIntervalYearMonth],
+        &[&strings],
+    );
+
+    conversions
+}
+
+pub(crate) fn bitmask(values: impl IntoIterator<Item = i32>) -> i32 {
+    values
+        .into_iter()
+        .fold(0, |mask, value| mask | (1 << value))
+}
@@ -11,24 +11,76 @@ pub type Result<T, E = FlightError> = std::result::Result<T, E>;
 #[derive(Debug, PartialEq, Clone)]
 pub struct CommandTicket {
     pub command: sql::Command,
+    /// Identifies the query for cancellation
+    pub query_id: Option<Bytes>,
+    /// The output partition of the physical plan to execute, or every
+    /// partition when None
+    pub partition: Option<u32>,
+    /// The number of output partitions the physical plan had when the
+    /// partitioned ticket was planned
+    pub partition_count: Option<u32>,
+    /// The plan resolved by GetFlightInfo, serialized as Substrait, which is
+    /// executed instead of planning the command again
+    pub plan: Option<Bytes>,
+    /// The IPC encoded schema advertised by the FlightInfo, which the
+    /// resolved plan must still have when executed
+    pub schema: Option<Bytes>,
 }
 
 impl CommandTicket {
     pub fn new(cmd: sql::Command) -> Self {
-        Self { command: cmd }
+        Self {
+            command: cmd,
+            query_id: None,
+            partition: None,
+            partition_count: None,
+            plan: None,
+            schema: None,
+        }
+    }
+
+    pub fn with_query_id(self, query_id: Bytes) -> Self {
+        Self {
+            query_id: Some(query_id),
+            ..self
+        }
+    }
+
+    pub fn with_partition(self, partition: u32, partition_count: u32) -> Self {
+        Self {
+            partition: Some(partition),
+            partition_count: Some(partition_count),
+            ..self
+        }
+    }
+
+    pub fn with_plan(self, plan: Bytes, schema: Bytes) -> Self {
+        Self {
+            plan: Some(plan),
+            schema: Some(schema),
+            ..self
+        }
     }
 
     pub fn try_decode(msg: Bytes) -> Result<Self> {
         let msg = CommandTicketMessage::decode(msg).map_err(decode_error_flight_error)?;
 
-        Self::try_decode_command(msg.command)
+        let ticket = Self::try_decode_command(msg.command)?;
+        Ok(Self {
+            query_id: msg.query_id,
+            partition: msg.partition,
+            partition_count: msg.partition_count,
+            plan: msg.plan,
+            schema: msg.schema,
+            ..ticket
+        })
     }
 
     pub fn try_decode_command(cmd: Bytes) -> Result<Self> {
         let content_msg = Any::decode(cmd).map_err(decode_error_flight_error)?;
         let command = Command::try_from(content_msg).map_err(FlightError::Arrow)?;
 
-        Ok(Self { command })
+        Ok(Self::new(command))
     }
 
     pub fn try_encode(self) -> Result<Bytes> {
@@ -36,6 +88,11 @@ impl CommandTicket {
 
         let msg = CommandTicketMessage {
             command: content_msg.into(),
+            query_id: self.query_id,
+            partition: self.partition,
+            partition_count: self.partition_count,
+            plan: self.plan,
+            schema: self.schema,
         };
 
         Ok(msg.encode_to_vec().into())
@@ -46,6 +103,16 @@ impl CommandTicket {
 struct CommandTicketMessage {
     #[prost(bytes = "bytes", tag = "2")]
     command: Bytes,
+    #[prost(bytes = "bytes", optional, tag = "3")]
+    query_id: Option<Bytes>,
+    #[prost(uint32, optional, tag = "4")]
+    partition: Option<u32>,
+    #[prost(bytes = "bytes", optional, tag = "5")]
+    plan: Option<Bytes>,
+    #[prost(bytes = "bytes", optional, tag = "6")]
+    schema: Option<Bytes>,
+    #[prost(uint32, optional, tag = "7")]
+    partition_count: Option<u32>,
 }
 
 fn decode_error_flight_error(err: prost::DecodeError) -> FlightError {
@@ -60,17 +127,52 @@ pub struct QueryHandle {
     /// The raw SQL query text
     query: String,
     parameters: Option<Bytes>,
+    /// The serialized Substrait plan, which replaces the SQL query when set
+    substrait_plan: Option<Bytes>,
+    /// The transaction the statement was prepared within, if any
+    transaction_id: Option<Bytes>,
 }
 
 impl QueryHandle {
     pub fn new(query: String, parameters: Option<Bytes>) -> Self {
-        Self { query, parameters }
+        Self {
+            query,
+            parameters,
+            substrait_plan: None,
+            transaction_id: None,
+        }
+    }
+
+    /// Creates a handle for a prepared Substrait plan.
+    pub fn new_substrait(substrait_plan: Bytes, parameters: Option<Bytes>) -> Self {
+        Self {
+            query: String::new(),
+            parameters,
+            substrait_plan: Some(substrait_plan),
+            transaction_id: None,
+        }
+    }
+
+    /// Executes the prepared statement within the transaction, if any.
+    pub fn with_transaction_id(self, transaction_id: Option<Bytes>) -> Self {
+        Self {
+            transaction_id,
+            ..self
+        }
     }
 
     pub fn query(&self) -> &str {
         self.query.as_ref()
     }
 
+    pub fn substrait_plan(&self) -> Option<&Bytes> {
+        self.substrait_plan.as_ref()
+    }
+
+    pub fn transaction_id(&self) -> Option<&Bytes> {
+        self.transaction_id.as_ref()
+    }
+
     pub fn parameters(&self) -> Option<&[u8]> {
         self.parameters.as_deref()
     }
@@ -85,6 +187,8 @@ impl QueryHandle {
         Ok(Self {
             query: msg.query,
             parameters: msg.parameters,
+            substrait_plan: msg.substrait_plan,
+            transaction_id: msg.transaction_id,
         })
     }
 
@@ -92,6 +196,8 @@ impl QueryHandle {
         let msg = QueryHandleMessage {
             query: self.query,
             parameters: self.parameters,
+            substrait_plan: self.substrait_plan,
+            transaction_id: self.transaction_id,
         };
 
         msg.encode_to_vec().into()
@@ -106,7 +212,10 @@ impl From<QueryHandle> for Bytes {
 
 impl Display for QueryHandle {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Query({})", self.query)
+        match &self.substrait_plan {
+            Some(plan) => write!(f, "SubstraitPlan({} bytes)", plan.len()),
+            None => write!(f, "Query({})", self.query),
+        }
     }
 }
 
@@ -117,4 +226,8 @@ pub struct QueryHandleMessage {
     query: String,
     #[prost(bytes = "bytes", optional, tag = "2")]
     parameters: Option<Bytes>,
+    #[prost(bytes = "bytes", optional, tag = "3")]
+    substrait_plan: Option<Bytes>,
+    #[prost(bytes = "bytes", optional, tag = "4")]
+    transaction_id: Option<Bytes>,
 }
@@ -0,0 +1,251 @@
+use std::sync::Arc;
+
+use datafusion::{
+    common::{not_impl_err, plan_datafusion_err, plan_err, substrait_err, Constraints, DFSchema},
+    datasource::provider_as_source,
+    error::Result,
+    execution::context::SessionState,
+    logical_expr::{
+        dml::InsertOp, CreateMemoryTable, CreateView, DdlStatement, DmlStatement, DropTable,
+        DropView, EmptyRelation, Expr, ExprSchemable, LogicalPlan, LogicalPlanBuilder, TableSource,
+        WriteOp,
+    },
+    sql::TableReference,
+};
+use datafusion_substrait::{
+    extensions::Extensions,
+    logical_plan::consumer::{
+        from_substrait_named_struct, from_substrait_plan as from_substrait_query_plan,
+        DefaultSubstraitConsumer, SubstraitConsumer,
+    },
+    logical_plan::producer::to_substrait_plan,
+    substrait::proto::{
+        ddl_rel::{self, DdlObject, DdlOp},
+        plan_rel,
+        rel::RelType,
+        write_rel::{self, CreateMode, WriteOp as SubstraitWriteOp},
+        DdlRel, Plan, WriteRel,
+    },
+};
+use prost::{bytes::Bytes, Message};
+
+/// Converts a Substrait plan to a DataFusion [`LogicalPlan`].
+///
+/// The DataFusion Substrait consumer only supports queries, so the plans
+/// whose root is a `WriteRel` or a `DdlRel` are converted here, while their
+/// inputs are still consumed by DataFusion.
+pub(crate) async fn from_substrait_plan(state: &SessionState, plan: &Plan) -> Result<LogicalPlan> {
+    let rel_type = match plan.relations.as_slice() {
+        [relation] => match &relation.rel_type {
+            Some(plan_rel::RelType::Root(root)) => {
+                root.input.as_ref().and_then(|rel| rel.rel_type.as_ref())
+            }
+            Some(plan_rel::RelType::Rel(rel)) => rel.rel_type.as_ref(),
+            None => None,
+        },
+        _ => None,
+    };
+    match rel_type {
+        Some(RelType::Write(write)) => {
+            let extensions = Extensions::try_from(&plan.extensions)?;
+            let consumer = DefaultSubstraitConsumer::new(&extensions, state);
+            from_write_rel(&consumer, state, write).await
+        }
+        Some(RelType::Ddl(ddl)) => {
+            let extensions = Extensions::try_from(&plan.extensions)?;
+            let consumer = DefaultSubstraitConsumer::new(&extensions, state);
+            from_ddl_rel(&consumer, ddl).await
+        }
+        _ => from_substrait_query_plan(state, plan).await,
+    }
+}
+
+/// Serializes a query plan resolved by the service as a Substrait plan, to
+/// be executed later from a ticket.
+pub(crate) fn serialize_plan(state: &SessionState, plan: &LogicalPlan) -> Result<Bytes> {
+    let plan = to_substrait_plan(plan, state)?;
+    Ok(plan.encode_to_vec().into())
+}
+
+/// Converts an INSERT, DELETE, UPDATE or CREATE TABLE AS `WriteRel`.
+async fn from_write_rel(
+    consumer: &DefaultSubstraitConsumer<'_>,
+    state: &SessionState,
+    write: &WriteRel,
+) -> Result<LogicalPlan> {
+    let table = match &write.write_type {
+        Some(write_rel::WriteType::NamedTable(named)) => table_reference(&named.names)?,
+        _ => return not_impl_err!("Only WriteRel on named tables is supported"),
+    };
+    let Some(input) = &write.input else {
+        return substrait_err!("WriteRel must have an input");
+    };
+    let input = consumer.consume_rel(input).await?;
+
+    match write.op() {
+        SubstraitWriteOp::Insert => insert_into(state, table, input).await,
+        SubstraitWriteOp::Delete => Ok(LogicalPlan::Dml(DmlStatement::new(
+            table.clone(),
+            table_source(state, &table).await?,
+            WriteOp::Delete,
+            Arc::new(input),
+        ))),
+        SubstraitWriteOp::Update => Ok(LogicalPlan::Dml(DmlStatement::new(
+            table.clone(),
+            table_source(state, &table).await?,
+            WriteOp::Update,
+            Arc::new(input),
+        ))),
+        SubstraitWriteOp::Ctas => {
+            let (if_not_exists, or_replace) = match write.create_mode() {
+                CreateMode::AppendIfExists => {
+                    if state
+                        .schema_for_ref(table.clone())?
+                        .table_exist(table.table())
+                    {
+                        return insert_into(state, table, input).await;
+                    }
+                    (false, false)
+                }
+                CreateMode::ReplaceIfExists => (false, true),
+                CreateMode::IgnoreIfExists => (true, false),
+                CreateMode::ErrorIfExists | CreateMode::Unspecified => (false, false),
+            };
+            Ok(LogicalPlan::Ddl(DdlStatement::CreateMemoryTable(
+                CreateMemoryTable {
+                    name: table,
+                    constraints: Constraints::default(),
+                    input: Arc::new(input),
+                    if_not_exists,
+                    or_replace,
+                    column_defaults: vec![],
+                    temporary: false,
+                },
+            )))
+        }
+        SubstraitWriteOp::Unspecified => substrait_err!("WriteRel must have an operation"),
+    }
+}
+
+/// Converts a CREATE or DROP of a table or a view.
+async fn from_ddl_rel(
+    consumer: &DefaultSubstraitConsumer<'_>,
+    ddl: &DdlRel,
+) -> Result<LogicalPlan> {
+    let name = match &ddl.write_type {
+        Some(ddl_rel::WriteType::NamedObject(named)) => table_reference(&named.names)?,
+        _ => return not_impl_err!("Only DdlRel on named objects is supported"),
+    };
+    let empty_schema = Arc::new(DFSchema::empty());
+
+    let statement = match (ddl.object(), ddl.op()) {
+        (DdlObject::Table, op @ (DdlOp::Create | DdlOp::CreateOrReplace)) => {
+            let Some(table_schema) = &ddl.table_schema else {
+                return substrait_err!("DdlRel creating a table must have a table schema");
+            };
+            let schema = from_substrait_named_struct(consumer, table_schema)?;
+            DdlStatement::CreateMemoryTable(CreateMemoryTable {
+                name,
+                constraints: Constraints::default(),
+                input: Arc::new(LogicalPlan::EmptyRelation(EmptyRelation {
+                    produce_one_row: false,
+                    schema: Arc::new(schema),
+                })),
+                if_not_exists: false,
+                or_replace: op == DdlOp::CreateOrReplace,
+                column_defaults: vec![],
+                temporary: false,
+            })
+        }
+        (DdlObject::View, op @ (DdlOp::Create | DdlOp::CreateOrReplace)) => {
+            let Some(view_definition) = &ddl.view_definition else {
+                return substrait_err!("DdlRel creating a view must have a view definition");
+            };
+            DdlStatement::CreateView(CreateView {
+                name,
+                input: Arc::new(consumer.consume_rel(view_definition).await?),
+                or_replace: op == DdlOp::CreateOrReplace,
+                definition: None,
+                temporary: false,
+            })
+        }
+        (DdlObject::Table, op @ (DdlOp::Drop | DdlOp::DropIfExist)) => {
+            DdlStatement::DropTable(DropTable {
+                name,
+                if_exists: op == DdlOp::DropIfExist,
+                schema: empty_schema,
+            })
+        }
+        (DdlObject::View, op @ (DdlOp::Drop | DdlOp::DropIfExist)) => {
+            DdlStatement::DropView(DropView {
+                name,
+                if_exists: op == DdlOp::DropIfExist,
+                schema: empty_schema,
+            })
+        }
+        (object, op) => {
+            return not_impl_err!(
+                "Unsupported DdlRel: {} of {}",
+                op.as_str_name(),
+                object.as_str_name()
+            )
+        }
+    };
+    Ok(LogicalPlan::Ddl(statement))
+}
+
+async fn insert_into(
+    state: &SessionState,
+    table: TableReference,
+    input: LogicalPlan,
+) -> Result<LogicalPlan> {
+    let target = table_source(state, &table).await?;
+    // The columns of a WriteRel are matched by position rather than by name
+    let table_schema = target.schema();
+    if table_schema.fields().len() != input.schema().fields().len() {
+        return plan_err!(
+            "Inserting {} columns into table {table} with {} columns",
+            input.schema().fields().len(),
+            table_schema.fields().len()
+        );
+    }
+    let exprs = input
+        .schema()
+        .columns()
+        .into_iter()
+        .zip(table_schema.fields())
+        .map(|(column, field)| {
+            Expr::Column(column)
+                .cast_to(field.data_type(), input.schema())
+                .map(|expr| expr.alias(field.name()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let input = LogicalPlanBuilder::from(input).project(exprs)?.build()?;
+    LogicalPlanBuilder::insert_into(input, table, target, InsertOp::Append)?.build()
+}
+
+async fn table_source(
+    state: &SessionState,
+    table: &TableReference,
+) -> Result<Arc<dyn TableSource>> {
+    let provider = state
+        .schema_for_ref(table.clone())?
+        .table(table.table())
+        .await?
+        .ok_or_else(|| plan_datafusion_err!("Table {table} does not exist"))?;
+    Ok(provider_as_source(provider))
+}
+
+/// Returns the table referenced by the names of a `NamedObjectWrite`.
+fn table_reference(names: &[String]) -> Result<TableReference> {
+    match names {
+        [table] => Ok(TableReference::bare(table.as_str())),
+        [schema, table] => Ok(TableReference::partial(schema.as_str(), table.as_str())),
+        [catalog, schema, table] => Ok(TableReference::full(
+            catalog.as_str(),
+            schema.as_str(),
+            table.as_str(),
+        )),
+        _ => substrait_err!("Invalid table name: {}", names.join(".")),
+    }
+}
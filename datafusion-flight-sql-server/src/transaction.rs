@@ -0,0 +1,320 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use arrow_flight::sql::{
+    metadata::SqlInfoDataBuilder, EndTransaction, SqlInfo, SqlSupportedTransaction,
+    SqlTransactionIsolationLevel,
+};
+use async_trait::async_trait;
+use datafusion::{
+    error::Result as DataFusionResult, execution::context::SQLOptions,
+    execution::context::SessionState, logical_expr::LogicalPlan,
+};
+use prost::bytes::Bytes;
+use tonic::Status;
+
+use super::auth::Identity;
+use super::sql_info::bitmask;
+
+type Result<T, E = Status> = std::result::Result<T, E>;
+
+/// How a savepoint is ended, as requested by an EndSavepoint action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndSavepoint {
+    /// Releases the savepoint.
+    Release,
+    /// Rolls back the changes made since the savepoint was created.
+    Rollback,
+}
+
+// TransactionManager is a trait used to begin and end the transactions
+// requested by clients, and to execute the statements referencing a
+// transaction within it. The identity passed to its methods is the
+// authenticated identity of the request, if any.
+#[async_trait]
+pub trait TransactionManager: Sync + Send {
+    /// Begins a transaction owned by the identity and returns its id.
+    async fn begin_transaction(
+        &self,
+        state: &SessionState,
+        identity: Option<&Identity>,
+    ) -> Result<Bytes>;
+
+    /// Commits or rolls back the transaction.
+    async fn end_transaction(
+        &self,
+        transaction_id: &[u8],
+        action: EndTransaction,
+        identity: Option<&Identity>,
+    ) -> Result<()>;
+
+    /// Returns the SessionState the statements of the transaction are
+    /// executed with, or an error when the transaction is not open.
+    async fn transaction_state(
+        &self,
+        transaction_id: &[u8],
+        state: SessionState,
+        identity: Option<&Identity>,
+    ) -> Result<SessionState>;
+
+    /// Creates the savepoint `name` within the transaction.
+    async fn begin_savepoint(
+        &self,
+        _transaction_id: &[u8],
+        _name: &str,
+        _identity: Option<&Identity>,
+    ) -> Result<()> {
+        Err(Status::unimplemented("savepoints are not supported"))
+    }
+
+    /// Releases the savepoint `name` of the transaction, or rolls back the
+    /// changes made since it was created.
+    async fn end_savepoint(
+        &self,
+        _transaction_id: &[u8],
+        _name: &str,
+        _action: EndSavepoint,
+        _identity: Option<&Identity>,
+    ) -> Result<()> {
+        Err(Status::unimplemented("savepoints are not supported"))
+    }
+
+    /// Verifies the plan can be executed within the transaction.
+    fn verify_plan(&self, _transaction_id: &[u8], _plan: &LogicalPlan) -> DataFusionResult<()> {
+        Ok(())
+    }
+
+    /// Advertises the supported transactions in SqlInfo.
+    fn sql_info(&self, builder: &mut SqlInfoDataBuilder) {
+        builder.append(
+            SqlInfo::FlightSqlServerTransaction,
+            SqlSupportedTransaction::Transaction as i32,
+        );
+        builder.append(SqlInfo::SqlTransactionsSupported, true);
+    }
+}
+
+/// How long a transaction of a [`ReadOnlyTransactionManager`] stays open
+/// without being used by default.
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+// ReadOnlyTransactionManager is a TransactionManager whose transactions only
+// read data, so that committing and rolling back have nothing to do.
+// Statements see the data committed when they are executed. Transactions can
+// only be used by the identity which began them, and are ended once they have
+// not been used for the idle timeout.
+#[derive(Debug)]
+pub struct ReadOnlyTransactionManager {
+    transactions: Mutex<HashMap<Bytes, Transaction>>,
+    idle_timeout: Duration,
+}
+
+#[derive(Debug)]
+struct Transaction {
+    owner: Option<Identity>,
+    last_used: Instant,
+}
+
+impl Default for ReadOnlyTransactionManager {
+    fn default() -> Self {
+        Self {
+            transactions: Mutex::default(),
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+        }
+    }
+}
+
+impl ReadOnlyTransactionManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how long transactions stay open without being used, one hour by
+    /// default. Idle transactions are ended as transactions are used.
+    pub fn with_idle_timeout(self, idle_timeout: Duration) -> Self {
+        Self {
+            idle_timeout,
+            ..self
+        }
+    }
+
+    /// Marks the transaction as used by the identity, which must own it.
+    fn use_transaction(&self, transaction_id: &[u8], identity: Option<&Identity>) -> Result<()> {
+        let mut transactions = self.transactions.lock().unwrap();
+        transactions.retain(|_, transaction| transaction.last_used.elapsed() < self.idle_timeout);
+        let Some(transaction) = transactions.get_mut(transaction_id) else {
+            return Err(Status::not_found(format!(
+                "Transaction {} is not open",
+                String::from_utf8_lossy(transaction_id)
+            )));
+        };
+        if transaction.owner.as_ref() != identity {
+            return Err(Status::permission_denied(format!(
+                "Transaction {} belongs to another user",
+                String::from_utf8_lossy(transaction_id)
+            )));
+        }
+        transaction.last_used = Instant::now();
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TransactionManager for ReadOnlyTransactionManager {
+    async fn begin_transaction(
+        &self,
+        _state: &SessionState,
+        identity: Option<&Identity>,
+    ) -> Result<Bytes> {
+        let transaction_id = Bytes::from(uuid::Uuid::new_v4().to_string());
+        let mut transactions = self.transactions.lock().unwrap();
+        transactions.retain(|_, transaction| transaction.last_used.elapsed() < self.idle_timeout);
+        transactions.insert(
+            transaction_id.clone(),
+            Transaction {
+                owner: identity.cloned(),
+                last_used: Instant::now(),
+            },
+        );
+        Ok(transaction_id)
+    }
+
+    async fn end_transaction(
+        &self,
+        transaction_id: &[u8],
+        _action: EndTransaction,
+        identity: Option<&Identity>,
+    ) -> Result<()> {
+        self.use_transaction(transaction_id, identity)?;
+        self.transactions.lock().unwrap().remove(transaction_id);
+        Ok(())
+    }
+
+    async fn transaction_state(
+        &self,
+        transaction_id: &[u8],
+        state: SessionState,
+        identity: Option<&Identity>,
+    ) -> Result<SessionState> {
+        self.use_transaction(transaction_id, identity)?;
+        Ok(state)
+    }
+
+    async fn begin_savepoint(
+        &self,
+        transaction_id: &[u8],
+        _name: &str,
+        identity: Option<&Identity>,
+    ) -> Result<()> {
+        self.use_transaction(transaction_id, identity)
+    }
+
+    async fn end_savepoint(
+        &self,
+        transaction_id: &[u8],
+        _name: &str,
+        _action: EndSavepoint,
+        identity: Option<&Identity>,
+    ) -> Result<()> {
+        self.use_transaction(transaction_id, identity)
+    }
+
+    fn verify_plan(&self, _transaction_id: &[u8], plan: &LogicalPlan) -> DataFusionResult<()> {
+        SQLOptions::new()
+            .with_allow_ddl(false)
+            .with_allow_dml(false)
+            .verify_plan(plan)
+    }
+
+    fn sql_info(&self, builder: &mut SqlInfoDataBuilder) {
+        builder.append(
+            SqlInfo::FlightSqlServerTransaction,
+            SqlSupportedTransaction::Savepoint as i32,
+        );
+        builder.append(SqlInfo::SqlTransactionsSupported, true);
+        builder.append(SqlInfo::SqlSavepointsSupported, true);
+        builder.append(
+            SqlInfo::SqlDefaultTransactionIsolation,
+            SqlTransactionIsolationLevel::SqlTransactionReadCommitted as i32,
+        );
+        builder.append(
+            SqlInfo::SqlSupportedTransactionsIsolationLevels,
+            bitmask([SqlTransactionIsolationLevel::SqlTransactionReadCommitted as i32]),
+        );
+    }
+}
+
+/// A savepoint of an open transaction.
+#[derive(Debug, Clone)]
+pub(crate) struct Savepoint {
+    pub id: Bytes,
+    pub transaction_id: Bytes,
+    pub name: String,
+}
+
+/// The savepoints of the open transactions, in the order they were created.
+///
+/// As in SQL, releasing a savepoint also releases the savepoints created
+/// after it, while rolling back to a savepoint only releases the later ones.
+#[derive(Debug, Default)]
+pub(crate) struct Savepoints {
+    transactions: Mutex<HashMap<Bytes, Vec<Savepoint>>>,
+}
+
+impl Savepoints {
+    /// Records a savepoint of the transaction and returns its id.
+    pub fn begin(&self, transaction_id: Bytes, name: String) -> Bytes {
+        let id = Bytes::from(uuid::Uuid::new_v4().to_string());
+        self.transactions
+            .lock()
+            .unwrap()
+            .entry(transaction_id.clone())
+            .or_default()
+            .push(Savepoint {
+                id: id.clone(),
+                transaction_id,
+                name,
+            });
+        id
+    }
+
+    pub fn get(&self, savepoint_id: &[u8]) -> Result<Savepoint> {
+        self.transactions
+            .lock()
+            .unwrap()
+            .values()
+            .flatten()
+            .find(|savepoint| savepoint.id == savepoint_id)
+            .cloned()
+            .ok_or_else(|| {
+                Status::not_found(format!(
+                    "Savepoint {} does not exist",
+                    String::from_utf8_lossy(savepoint_id)
+                ))
+            })
+    }
+
+    /// Forgets the savepoints created after the savepoint, as well as the
+    /// savepoint itself when it is released.
+    pub fn end(&self, savepoint: &Savepoint, action: EndSavepoint) {
+        let mut transactions = self.transactions.lock().unwrap();
+        let Some(savepoints) = transactions.get_mut(&savepoint.transaction_id) else {
+            return;
+        };
+        if let Some(position) = savepoints.iter().position(|s| s.id == savepoint.id) {
+            let keep = match action {
+                EndSavepoint::Release => position,
+                EndSavepoint::Rollback => position + 1,
+            };
+            savepoints.truncate(keep);
+        }
+    }
+
+    /// Forgets the savepoints of the transaction.
+    pub fn end_transaction(&self, transaction_id: &[u8]) {
+        self.transactions.lock().unwrap().remove(transaction_id);
+    }
+}
@@ -0,0 +1,212 @@
+use arrow_flight::{
+    error::FlightError,
+    sql::{
+        metadata::{XdbcTypeInfo, XdbcTypeInfoData, XdbcTypeInfoDataBuilder},
+        Nullable, Searchable, XdbcDataType, XdbcDatetimeSubcode,
+    },
+};
+use datafusion::{
+    arrow::datatypes::{DataType, TimeUnit},
+    common::DFSchema,
+    error::Result as DataFusionResult,
+    execution::context::SessionState,
+    logical_expr::ExprSchemable,
+};
+
+/// The SQL types DataFusion can plan, other than arrays, which XDBC has no
+/// type for.
+const SQL_TYPES: [&str; 19] = [
+    "BOOLEAN",
+    "TINYINT",
+    "SMALLINT",
+    "INT",
+    "BIGINT",
+    "TINYINT UNSIGNED",
+    "SMALLINT UNSIGNED",
+    "INT UNSIGNED",
+    "BIGINT UNSIGNED",
+    "REAL",
+    "DOUBLE",
+    "DECIMAL",
+    "VARCHAR",
+    "BYTEA",
+    "DATE",
+    "TIME",
+    "TIMESTAMP",
+    "TIMESTAMP WITH TIME ZONE",
+    "INTERVAL",
+];
+
+/// Builds the XDBC type info for the SQL types DataFusion can plan.
+///
+/// Each row is derived from the Arrow [`DataType`] the SQL planner of the
+/// session plans the SQL type as, so string types follow the
+/// `map_string_types_to_utf8view` option and timestamps with time zone
+/// follow the configured session time zone.
+pub fn xdbc_type_info(state: &SessionState) -> Result<XdbcTypeInfoData, FlightError> {
+    let mut builder = XdbcTypeInfoDataBuilder::new();
+    for type_name in SQL_TYPES {
+        let data_type = planned_type(state, type_name)
+            .map_err(|err| FlightError::ExternalError(Box::new(err)))?;
+        builder.append(type_info(type_name, &data_type));
+    }
+    builder.build()
+}
+
+/// Returns the Arrow type the SQL type is planned as.
+fn planned_type(state: &SessionState, type_name: &str) -> DataFusionResult<DataType> {
+    let schema = DFSchema::empty();
+    let expr = state.create_logical_expr(&format!("CAST(NULL AS {type_name})"), &schema)?;
+    expr.get_type(&schema)
+}
+
+/// Describes the SQL type `type_name` which is planned as `data_type`.
+fn type_info(type_name: &str, data_type: &DataType) -> XdbcTypeInfo {
+    let mut info = XdbcTypeInfo {
+        type_name: type_name.to_string(),
+        nullable: Nullable::NullabilityNullable,
+        searchable: Searchable::Basic,
+        local_type_name: Some(data_type.to_string()),
+        ..Default::default()
+    };
+
+    match data_type {
+        DataType::Boolean => {
+            info.data_type = XdbcDataType::XdbcBit;
+            info.column_size = Some(1);
+        }
+        DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::UInt8
+        | DataType::UInt16
+        | DataType::UInt32
+        | DataType::UInt64 => {
+            let (xdbc_type, column_size) = match data_type {
+                DataType::Int8 => (XdbcDataType::XdbcTinyint, 3),
+                DataType::UInt8 => (XdbcDataType::XdbcTinyint, 3),
+                DataType::Int16 => (XdbcDataType::XdbcSmallint, 5),
+                DataType::UInt16 => (XdbcDataType::XdbcSmallint, 5),
+                DataType::Int32 => (XdbcDataType::XdbcInteger, 10),
+                DataType::UInt32 => (XdbcDataType::XdbcInteger, 10),
+                DataType::Int64 => (XdbcDataType::XdbcBigint, 19),
+                _ => (XdbcDataType::XdbcBigint, 20),
+            };
+            info.data_type = xdbc_type;
+            info.column_size = Some(column_size);
+            info.unsigned_attribute = Some(data_type.is_unsigned_integer());
+            info.auto_increment = Some(false);
+            info.minimum_scale = Some(0);
+            info.maximum_scale = Some(0);
+            info.num_prec_radix = Some(10);
+        }
+        DataType::Float32 | DataType::Float64 => {
+            let (xdbc_type, column_size) = match data_type {
+                DataType::Float32 => (XdbcDataType::XdbcReal, 24),
+                _ => (XdbcDataType::XdbcDouble, 53),
+            };
+            info.data_type = xdbc_type;
+            info.column_size = Some(column_size);
+            info.unsigned_attribute = Some(false);
+            info.auto_increment = Some(false);
+            info.num_prec_radix = Some(2);
+        }
+        DataType::Decimal128(precision, _) | DataType::Decimal256(precision, _) => {
+            info.data_type = XdbcDataType::XdbcDecimal;
+            info.column_size = Some(*precision as i32);
+            info.create_params = Some(vec!["precision".to_string(), "scale".to_string()]);
+            info.unsigned_attribute = Some(false);
+            info.fixed_prec_scale = true;
+            info.auto_increment = Some(false);
+            info.minimum_scale = Some(0);
+            info.maximum_scale = Some(*precision as i32);
+            info.num_prec_radix = Some(10);
+        }
+        DataType::Utf8 | DataType::LargeUtf8 | DataType::Utf8View => {
+            info.data_type = XdbcDataType::XdbcVarchar;
+            info.literal_prefix = Some("'".to_string());
+            info.literal_suffix = Some("'".to_string());
+            info.case_sensitive = true;
+            info.searchable = Searchable::Full;
+        }
+        DataType::Binary | DataType::LargeBinary | DataType::BinaryView => {
+            info.data_type = XdbcDataType::XdbcVarbinary;
+            info.literal_prefix = Some("X'".to_string());
+            info.literal_suffix = Some("'".to_string());
+        }
+        DataType::Date32 | DataType::Date64 => {
+            info.data_type = XdbcDataType::XdbcDate;
+            info.column_size = Some(10);
+            info.literal_prefix = Some("DATE '".to_string());
+            info.literal_suffix = Some("'".to_string());
+            info.datetime_subcode = Some(XdbcDatetimeSubcode::XdbcSubcodeYear);
+        }
+        DataType::Time32(unit) | DataType::Time64(unit) => {
+            let scale = time_unit_scale(unit);
+            info.data_type = XdbcDataType::XdbcTime;
+            // HH:MM:SS followed by the fractional seconds
+            info.column_size = Some(8 + fraction_size(scale));
+            info.literal_prefix = Some("TIME '".to_string());
+            info.literal_suffix = Some("'".to_string());
+            info.minimum_scale = Some(0);
+            info.maximum_scale = Some(scale);
+            info.datetime_subcode = Some(XdbcDatetimeSubcode::XdbcSubcodeTime);
+        }
+        DataType::Timestamp(unit, tz) => {
+            let scale = time_unit_scale(unit);
+            info.data_type = XdbcDataType::XdbcTimestamp;
+            // YYYY-MM-DD HH:MM:SS followed by the fractional seconds and the offset
+            let offset_size = if tz.is_some() { 6 } else { 0 };
+            info.column_size = Some(19 + fraction_size(scale) + offset_size);
+            info.literal_prefix = Some("TIMESTAMP '".to_string());
+            info.literal_suffix = Some("'".to_string());
+            info.minimum_scale = Some(0);
+            info.maximum_scale = Some(scale);
+            info.datetime_subcode = Some(if tz.is_some() {
+                XdbcDatetimeSubcode::XdbcSubcodeTimestampWithTimezone
+            } else {
+                XdbcDatetimeSubcode::XdbcSubcodeTimestamp
+            });
+        }
+        DataType::Interval(_) | DataType::Duration(_) => {
+            info.data_type = XdbcDataType::XdbcInterval;
+            info.literal_prefix = Some("INTERVAL '".to_string());
+            info.literal_suffix = Some("'".to_string());
+            info.searchable = Searchable::None;
+        }
+        _ => {
+            info.data_type = XdbcDataType::XdbcUnknownType;
+            info.searchable = Searchable::None;
+        }
+    }
+
+    // Datetime and interval types report their concise type as the verbose
+    // SQL_DATETIME / SQL_INTERVAL types together with a subcode
+    info.sql_data_type = match info.data_type {
+        XdbcDataType::XdbcDate | XdbcDataType::XdbcTime | XdbcDataType::XdbcTimestamp => {
+            XdbcDataType::XdbcDatetime
+        }
+        other => other,
+    };
+
+    info
+}
+
+fn time_unit_scale(unit: &TimeUnit) -> i32 {
+    match unit {
+        TimeUnit::Second => 0,
+        TimeUnit::Millisecond => 3,
+        TimeUnit::Microsecond => 6,
+        TimeUnit::Nanosecond => 9,
+    }
+}
+
+/// The number of characters of the fractional seconds, including the dot.
+fn fraction_size(scale: i32) -> i32 {
+    if scale == 0 {
+        0
+    } else {
+        scale + 1
+    }
+}
@@ -0,0 +1,283 @@
+use std::sync::Arc;
+
+use arrow_flight::{
+    sql::{client::FlightSqlServiceClient, CommandGetDbSchemas, CommandGetTables},
+    FlightInfo,
+};
+use datafusion::arrow::{
+    array::{Int32Array, RecordBatch, StringArray},
+    datatypes::{DataType, Field, Schema},
+    util::pretty::pretty_format_batches,
+};
+use datafusion::{
+    catalog::MemorySchemaProvider,
+    datasource::MemTable,
+    execution::context::{SessionContext, SessionState},
+};
+use datafusion_flight_sql_server::{
+    access::{MemoryAccessPolicy, TableAccess},
+    auth::{Identity, MemoryAuthenticator},
+    service::FlightSqlService,
+};
+use futures::TryStreamExt;
+use tokio::time::{sleep, Duration};
+use tonic::{
+    transport::{Channel, Endpoint},
+    Code,
+};
+
+fn create_test_session() -> SessionState {
+    let ctx = SessionContext::new();
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2, 3])),
+            Arc::new(StringArray::from(vec!["Alice", "Bob", "Charlie"])),
+        ],
+    )
+    .unwrap();
+
+    for name in ["users", "orders"] {
+        let table = MemTable::try_new(schema.clone(), vec![vec![batch.clone()]]).unwrap();
+        ctx.register_table(name, Arc::new(table)).unwrap();
+    }
+    ctx.catalog("datafusion")
+        .unwrap()
+        .register_schema("sales", Arc::new(MemorySchemaProvider::new()))
+        .unwrap();
+
+    ctx.state()
+}
+
+fn create_test_service() -> FlightSqlService {
+    let authenticator = MemoryAuthenticator::new()
+        .with_token("alice-token", Identity::new("alice"))
+        .with_token("bob-token", Identity::new("bob"))
+        .with_token("carol-token", Identity::new("carol"));
+    let policy = MemoryAccessPolicy::new()
+        .with_grant("alice", "users", [TableAccess::Read])
+        .with_grant(
+            "bob",
+            "datafusion.public.orders",
+            [TableAccess::Read, TableAccess::Insert],
+        );
+    FlightSqlService::new(create_test_session())
+        .with_authenticator(Box::new(authenticator))
+        .with_access_policy(Arc::new(policy))
+}
+
+async fn start_test_server(addr: String, service: FlightSqlService) {
+    tokio::spawn(async move {
+        service
+            .serve(addr)
+            .await
+            .expect("Server should start successfully");
+    });
+
+    sleep(Duration::from_millis(500)).await;
+}
+
+async fn create_test_client(addr: &str, token: &str) -> FlightSqlServiceClient<Channel> {
+    let endpoint = Endpoint::new(addr.to_string()).expect("Valid endpoint");
+    let channel = endpoint.connect().await.expect("Connection successful");
+    let mut client = FlightSqlServiceClient::new(channel);
+    client.set_token(token.to_string());
+    client
+}
+
+async fn fetch_batches(
+    client: &mut FlightSqlServiceClient<Channel>,
+    flight_info: FlightInfo,
+) -> Vec<RecordBatch> {
+    let ticket = flight_info
+        .endpoint
+        .first()
+        .expect("Should have endpoint")
+        .ticket
+        .clone()
+        .expect("Should have ticket");
+
+    client
+        .do_get(ticket)
+        .await
+        .expect("do_get should succeed")
+        .try_collect()
+        .await
+        .expect("Stream should work")
+}
+
+async fn get_tables(client: &mut FlightSqlServiceClient<Channel>) -> String {
+    let flight_info = client
+        .get_tables(CommandGetTables {
+            catalog: Some("datafusion".to_string()),
+            db_schema_filter_pattern: None,
+            table_name_filter_pattern: None,
+            table_types: vec![],
+            include_schema: false,
+        })
+        .await
+        .expect("GetTables should succeed");
+    let batches = fetch_batches(client, flight_info).await;
+    pretty_format_batches(&batches).unwrap().to_string()
+}
+
+async fn get_db_schemas(client: &mut FlightSqlServiceClient<Channel>) -> String {
+    let flight_info = client
+        .get_db_schemas(CommandGetDbSchemas {
+            catalog: Some("datafusion".to_string()),
+            db_schema_filter_pattern: None,
+        })
+        .await
+        .expect("GetDbSchemas should succeed");
+    let batches = fetch_batches(client, flight_info).await;
+    pretty_format_batches(&batches).unwrap().to_string()
+}
+
+async fn get_catalogs(client: &mut FlightSqlServiceClient<Channel>) -> String {
+    let flight_info = client
+        .get_catalogs()
+        .await
+        .expect("GetCatalogs should succeed");
+    let batches = fetch_batches(client, flight_info).await;
+    pretty_format_batches(&batches).unwrap().to_string()
+}
+
+async fn query(
+    client: &mut FlightSqlServiceClient<Channel>,
+    sql: &str,
+) -> Result<String, arrow_flight::error::FlightError> {
+    let flight_info = client.execute(sql.to_string(), None).await?;
+    let batches = fetch_batches(client, flight_info).await;
+    Ok(pretty_format_batches(&batches).unwrap().to_string())
+}
+
+fn assert_permission_denied<T: std::fmt::Debug>(
+    result: Result<T, arrow_flight::error::FlightError>,
+) {
+    match result {
+        Err(arrow_flight::error::FlightError::Tonic(status)) => {
+            assert_eq!(status.code(), Code::PermissionDenied, "{status}")
+        }
+        other => panic!("Expected PermissionDenied, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_table_access() {
+    let addr = "0.0.0.0:50151";
+    start_test_server(addr.to_string(), create_test_service()).await;
+
+    let mut alice = create_test_client(&format!("http://{}", addr), "alice-token").await;
+    let mut bob = create_test_client(&format!("http://{}", addr), "bob-token").await;
+
+    let formatted = query(&mut alice, "SELECT count(*) AS n FROM users")
+        .await
+        .expect("alice should read users");
+    assert!(formatted.contains("| 3 |"), "{formatted}");
+
+    assert_permission_denied(query(&mut alice, "SELECT count(*) AS n FROM orders").await);
+    assert_permission_denied(
+        query(
+            &mut alice,
+            "SELECT * FROM users WHERE id IN (SELECT id FROM orders)",
+        )
+        .await,
+    );
+    assert_permission_denied(
+        alice
+            .execute_update("INSERT INTO users VALUES (4, 'Dave')".to_string(), None)
+            .await,
+    );
+    assert_permission_denied(
+        alice
+            .prepare("SELECT * FROM orders".to_string(), None)
+            .await,
+    );
+    assert_permission_denied(
+        alice
+            .execute_update("DROP TABLE users".to_string(), None)
+            .await,
+    );
+
+    let rows = bob
+        .execute_update("INSERT INTO orders VALUES (4, 'Dave')".to_string(), None)
+        .await
+        .expect("bob should insert into orders");
+    assert_eq!(rows, 1);
+    assert_permission_denied(
+        bob.execute_update("DELETE FROM orders WHERE id = 4".to_string(), None)
+            .await,
+    );
+    // Inserting rows read from a table requires reading that table as well
+    assert_permission_denied(
+        bob.execute_update("INSERT INTO orders SELECT * FROM users".to_string(), None)
+            .await,
+    );
+}
+
+#[tokio::test]
+async fn test_schema_access() {
+    let addr = "0.0.0.0:50153";
+    start_test_server(addr.to_string(), create_test_service()).await;
+
+    let mut bob = create_test_client(&format!("http://{}", addr), "bob-token").await;
+
+    // Dropping a schema drops its tables, which are not checked one by one
+    assert_permission_denied(
+        bob.execute_update("DROP SCHEMA datafusion.public CASCADE".to_string(), None)
+            .await,
+    );
+    assert_permission_denied(
+        bob.execute_update("CREATE SCHEMA datafusion.staging".to_string(), None)
+            .await,
+    );
+    assert_permission_denied(
+        bob.execute_update("CREATE DATABASE staging".to_string(), None)
+            .await,
+    );
+    // DESCRIBE does not name the table in its plan
+    assert_permission_denied(query(&mut bob, "DESCRIBE users").await);
+
+    let formatted = query(&mut bob, "SELECT count(*) AS n FROM orders")
+        .await
+        .expect("The schema should not be dropped");
+    assert!(formatted.contains("| 3 |"), "{formatted}");
+}
+
+#[tokio::test]
+async fn test_metadata_access() {
+    let addr = "0.0.0.0:50152";
+    start_test_server(addr.to_string(), create_test_service()).await;
+
+    let mut alice = create_test_client(&format!("http://{}", addr), "alice-token").await;
+    let mut bob = create_test_client(&format!("http://{}", addr), "bob-token").await;
+    let mut carol = create_test_client(&format!("http://{}", addr), "carol-token").await;
+
+    let tables = get_tables(&mut alice).await;
+    assert!(tables.contains("users"), "{tables}");
+    assert!(!tables.contains("orders"), "{tables}");
+
+    let tables = get_tables(&mut bob).await;
+    assert!(tables.contains("orders"), "{tables}");
+    assert!(!tables.contains("users"), "{tables}");
+
+    // The grant of bob is qualified with the schema, unlike the one of alice
+    let schemas = get_db_schemas(&mut alice).await;
+    assert!(schemas.contains("sales"), "{schemas}");
+    let schemas = get_db_schemas(&mut bob).await;
+    assert!(schemas.contains("public"), "{schemas}");
+    assert!(!schemas.contains("sales"), "{schemas}");
+
+    let catalogs = get_catalogs(&mut bob).await;
+    assert!(catalogs.contains("datafusion"), "{catalogs}");
+    let catalogs = get_catalogs(&mut carol).await;
+    assert!(!catalogs.contains("datafusion"), "{catalogs}");
+    let schemas = get_db_schemas(&mut carol).await;
+    assert!(!schemas.contains("public"), "{schemas}");
+}
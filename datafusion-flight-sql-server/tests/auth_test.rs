@@ -0,0 +1,231 @@
+use std::sync::Arc;
+
+use arrow_flight::sql::client::FlightSqlServiceClient;
+use async_trait::async_trait;
+use datafusion::arrow::{
+    array::{Int32Array, RecordBatch, StringArray},
+    datatypes::{DataType, Field, Schema},
+    util::pretty::pretty_format_batches,
+};
+use datafusion::{
+    datasource::MemTable,
+    execution::context::{SessionContext, SessionState},
+};
+use datafusion_flight_sql_server::{
+    auth::{Identity, MemoryAuthenticator},
+    service::FlightSqlService,
+    session::SessionStateProvider,
+};
+use futures::TryStreamExt;
+use tokio::time::{sleep, Duration};
+use tonic::{
+    transport::{Channel, Endpoint},
+    Request, Status,
+};
+
+fn create_test_session() -> SessionState {
+    let ctx = SessionContext::new();
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2, 3])),
+            Arc::new(StringArray::from(vec!["Alice", "Bob", "Charlie"])),
+        ],
+    )
+    .unwrap();
+
+    let table = MemTable::try_new(schema, vec![vec![batch]]).unwrap();
+    ctx.register_table("users", Arc::new(table)).unwrap();
+
+    ctx.state()
+}
+
+async fn start_test_server(addr: String, service: FlightSqlService) {
+    tokio::spawn(async move {
+        service
+            .serve(addr)
+            .await
+            .expect("Server should start successfully");
+    });
+
+    sleep(Duration::from_millis(500)).await;
+}
+
+async fn create_test_client(addr: &str) -> FlightSqlServiceClient<Channel> {
+    let endpoint = Endpoint::new(addr.to_string()).expect("Valid endpoint");
+    let channel = endpoint.connect().await.expect("Connection successful");
+    FlightSqlServiceClient::new(channel)
+}
+
+async fn query(
+    client: &mut FlightSqlServiceClient<Channel>,
+    sql: &str,
+) -> Result<String, arrow_flight::error::FlightError> {
+    let flight_info = client.execute(sql.to_string(), None).await?;
+    let ticket = flight_info
+        .endpoint
+        .first()
+        .expect("Should have endpoint")
+        .ticket
+        .clone()
+        .expect("Should have ticket");
+    let batches: Vec<RecordBatch> = client.do_get(ticket).await?.try_collect().await?;
+    Ok(pretty_format_batches(&batches).unwrap().to_string())
+}
+
+fn authenticator() -> MemoryAuthenticator {
+    MemoryAuthenticator::new()
+        .with_user("admin", "secret")
+        .with_token(
+            "token1",
+            Identity::new("alice").with_attribute("tenant", "a"),
+        )
+}
+
+/// Answers with the name of the authenticated user.
+struct IdentitySessionStateProvider;
+
+#[async_trait]
+impl SessionStateProvider for IdentitySessionStateProvider {
+    async fn new_context(&self, request: &Request<()>) -> Result<SessionState, Status> {
+        let identity = request
+            .extensions()
+            .get::<Identity>()
+            .ok_or_else(|| Status::internal("Expected an identity"))?;
+        let ctx = SessionContext::new_with_state(create_test_session());
+        ctx.sql(&format!(
+            "CREATE VIEW whoami AS SELECT '{}' AS user, '{}' AS tenant",
+            identity.user,
+            identity.attribute("tenant").unwrap_or_default()
+        ))
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(ctx.state())
+    }
+}
+
+#[tokio::test]
+async fn test_handshake() {
+    let addr = "0.0.0.0:50141";
+    start_test_server(
+        addr.to_string(),
+        FlightSqlService::new(create_test_session()).with_authenticator(Box::new(authenticator())),
+    )
+    .await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let result = query(&mut client, "SELECT count(*) AS n FROM users").await;
+    assert!(result.is_err(), "Requests without a token should fail");
+
+    let result = client.handshake("admin", "wrong").await;
+    assert!(
+        result.is_err(),
+        "Handshake with a wrong password should fail"
+    );
+
+    let token = client
+        .handshake("admin", "secret")
+        .await
+        .expect("Handshake should succeed");
+    assert!(!token.is_empty());
+
+    let formatted = query(&mut client, "SELECT count(*) AS n FROM users")
+        .await
+        .expect("Authenticated requests should succeed");
+    assert!(formatted.contains("| 3 |"), "{formatted}");
+
+    client.set_token("invalid".to_string());
+    let result = query(&mut client, "SELECT count(*) AS n FROM users").await;
+    assert!(
+        result.is_err(),
+        "Requests with an invalid token should fail"
+    );
+}
+
+#[tokio::test]
+async fn test_identity_in_extensions() {
+    let addr = "0.0.0.0:50142";
+    start_test_server(
+        addr.to_string(),
+        FlightSqlService::new_with_provider(Box::new(IdentitySessionStateProvider))
+            .with_authenticator(Box::new(authenticator())),
+    )
+    .await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+    client.set_token("token1".to_string());
+
+    let formatted = query(&mut client, "SELECT * FROM whoami")
+        .await
+        .expect("Authenticated requests should succeed");
+    let expected = [
+        "+-------+--------+",
+        "| user  | tenant |",
+        "+-------+--------+",
+        "| alice | a      |",
+        "+-------+--------+",
+    ];
+    assert_eq!(formatted, expected.join("\n"));
+}
+
+#[tokio::test]
+async fn test_handshake_without_authenticator() {
+    let addr = "0.0.0.0:50143";
+    start_test_server(
+        addr.to_string(),
+        FlightSqlService::new(create_test_session()),
+    )
+    .await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let result = client.handshake("admin", "secret").await;
+    assert!(result.is_err(), "Handshake should not be supported");
+
+    let formatted = query(&mut client, "SELECT count(*) AS n FROM users")
+        .await
+        .expect("Requests should not be authenticated");
+    assert!(formatted.contains("| 3 |"), "{formatted}");
+}
+
+#[tokio::test]
+async fn test_token_ttl() {
+    let addr = "0.0.0.0:50144";
+    start_test_server(
+        addr.to_string(),
+        FlightSqlService::new(create_test_session()).with_authenticator(Box::new(
+            authenticator().with_token_ttl(Duration::from_millis(500)),
+        )),
+    )
+    .await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    client
+        .handshake("admin", "secret")
+        .await
+        .expect("Handshake should succeed");
+    query(&mut client, "SELECT count(*) AS n FROM users")
+        .await
+        .expect("Authenticated requests should succeed");
+
+    sleep(Duration::from_millis(600)).await;
+    let result = query(&mut client, "SELECT count(*) AS n FROM users").await;
+    assert!(
+        result.is_err(),
+        "Requests with an expired token should fail"
+    );
+
+    // Tokens added to the authenticator do not expire
+    client.set_token("token1".to_string());
+    query(&mut client, "SELECT count(*) AS n FROM users")
+        .await
+        .expect("Authenticated requests should succeed");
+}
@@ -0,0 +1,288 @@
+use std::sync::Arc;
+
+use arrow_flight::{
+    flight_service_client::FlightServiceClient, sql::client::FlightSqlServiceClient, Action,
+};
+use async_trait::async_trait;
+use datafusion::arrow::array::{ArrayRef, Int32Array, RecordBatch, StringArray};
+use datafusion::{
+    datasource::MemTable,
+    execution::context::{SessionContext, SessionState},
+};
+use datafusion_flight_sql_server::{
+    cache::ResultCache,
+    service::FlightSqlService,
+    session::{GetSessionOptionsRequest, SessionStateProvider},
+};
+use futures::TryStreamExt;
+use prost::Message;
+use tokio::time::{sleep, Duration};
+use tonic::{
+    transport::{Channel, Endpoint},
+    Request, Status,
+};
+
+/// Registers the users table with the names, replacing the previous one
+/// without the service knowing.
+fn register_users(ctx: &SessionContext, names: Vec<&str>) {
+    let ids = (1..=names.len() as i32).collect::<Vec<_>>();
+    let batch = RecordBatch::try_from_iter(vec![
+        ("id", Arc::new(Int32Array::from(ids)) as ArrayRef),
+        ("name", Arc::new(StringArray::from(names)) as ArrayRef),
+    ])
+    .unwrap();
+    let table = MemTable::try_new(batch.schema(), vec![vec![batch]]).unwrap();
+    ctx.deregister_table("users").unwrap();
+    ctx.register_table("users", Arc::new(table)).unwrap();
+}
+
+async fn start_test_server(addr: String, service: FlightSqlService) {
+    tokio::spawn(async move {
+        service
+            .serve(addr)
+            .await
+            .expect("Server should start successfully");
+    });
+
+    sleep(Duration::from_millis(500)).await;
+}
+
+async fn create_test_client(addr: &str) -> FlightSqlServiceClient<Channel> {
+    let endpoint = Endpoint::new(addr.to_string()).expect("Valid endpoint");
+    let channel = endpoint.connect().await.expect("Connection successful");
+    FlightSqlServiceClient::new(channel)
+}
+
+async fn query_names(client: &mut FlightSqlServiceClient<Channel>) -> String {
+    query(client, "SELECT name FROM users ORDER BY id").await
+}
+
+/// Returns the names of the first column of the results of the query.
+async fn query(client: &mut FlightSqlServiceClient<Channel>, sql: &str) -> String {
+    let flight_info = client
+        .execute(sql.to_string(), None)
+        .await
+        .expect("Query should succeed");
+    let ticket = flight_info
+        .endpoint
+        .first()
+        .expect("Should have endpoint")
+        .ticket
+        .clone()
+        .expect("Should have ticket");
+    let batches: Vec<RecordBatch> = client
+        .do_get(ticket)
+        .await
+        .expect("DoGet should succeed")
+        .try_collect()
+        .await
+        .expect("Stream should succeed");
+    let names = batches
+        .iter()
+        .flat_map(|batch| {
+            let column = batch.column(0).as_any().downcast_ref::<StringArray>();
+            column.expect("Should be a Utf8 column").iter().flatten()
+        })
+        .collect::<Vec<_>>();
+    names.join(",")
+}
+
+#[tokio::test]
+async fn test_result_cache() {
+    let addr = "0.0.0.0:50221";
+    let ctx = SessionContext::new();
+    register_users(&ctx, vec!["Alice", "Bob"]);
+    let result_cache = Arc::new(ResultCache::new(1024 * 1024, Duration::from_secs(60)));
+    let service = FlightSqlService::new(ctx.state()).with_result_cache(result_cache.clone());
+    start_test_server(addr.to_string(), service).await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    assert_eq!(query_names(&mut client).await, "Alice,Bob");
+
+    // The results of the query are served from the cache
+    register_users(&ctx, vec!["Charlie"]);
+    assert_eq!(query_names(&mut client).await, "Alice,Bob");
+
+    // Statements executed by the service invalidate the cache
+    client
+        .execute_update("INSERT INTO users VALUES (2, 'Dave')".to_string(), None)
+        .await
+        .expect("Insert should succeed");
+    assert_eq!(query_names(&mut client).await, "Charlie,Dave");
+
+    register_users(&ctx, vec!["Eve"]);
+    assert_eq!(query_names(&mut client).await, "Charlie,Dave");
+    let version = result_cache.version();
+    result_cache.invalidate();
+    assert_eq!(result_cache.version(), version + 1);
+    assert_eq!(query_names(&mut client).await, "Eve");
+}
+
+#[tokio::test]
+async fn test_result_cache_ttl() {
+    let addr = "0.0.0.0:50222";
+    let ctx = SessionContext::new();
+    register_users(&ctx, vec!["Alice", "Bob"]);
+    let result_cache = Arc::new(ResultCache::new(1024 * 1024, Duration::from_millis(500)));
+    let service = FlightSqlService::new(ctx.state()).with_result_cache(result_cache);
+    start_test_server(addr.to_string(), service).await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    assert_eq!(query_names(&mut client).await, "Alice,Bob");
+    register_users(&ctx, vec!["Charlie"]);
+    assert_eq!(query_names(&mut client).await, "Alice,Bob");
+
+    sleep(Duration::from_millis(600)).await;
+    assert_eq!(query_names(&mut client).await, "Charlie");
+}
+
+#[tokio::test]
+async fn test_result_cache_spill() {
+    let addr = "0.0.0.0:50223";
+    let spill_dir = std::env::temp_dir().join(format!("result-cache-{}", std::process::id()));
+    let ctx = SessionContext::new();
+    register_users(&ctx, vec!["Alice", "Bob"]);
+    // No result fits in memory, so that every result is spilled
+    let result_cache =
+        Arc::new(ResultCache::new(0, Duration::from_secs(60)).with_spill(&spill_dir, 1024 * 1024));
+    let service = FlightSqlService::new(ctx.state()).with_result_cache(result_cache.clone());
+    start_test_server(addr.to_string(), service).await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    assert_eq!(query_names(&mut client).await, "Alice,Bob");
+    assert_eq!(std::fs::read_dir(&spill_dir).unwrap().count(), 1);
+
+    register_users(&ctx, vec!["Charlie"]);
+    assert_eq!(query_names(&mut client).await, "Alice,Bob");
+
+    // Invalidated results are removed from the disk
+    result_cache.invalidate();
+    assert_eq!(std::fs::read_dir(&spill_dir).unwrap().count(), 0);
+    assert_eq!(query_names(&mut client).await, "Charlie");
+
+    std::fs::remove_dir_all(&spill_dir).ok();
+}
+
+/// Answers with the users of the tenant of the request.
+struct TenantSessionStateProvider;
+
+fn tenant(request: &Request<()>) -> Option<String> {
+    let tenant = request.metadata().get("x-tenant")?;
+    Some(tenant.to_str().ok()?.to_string())
+}
+
+#[async_trait]
+impl SessionStateProvider for TenantSessionStateProvider {
+    async fn new_context(&self, request: &Request<()>) -> Result<SessionState, Status> {
+        let ctx = SessionContext::new();
+        match tenant(request).as_deref() {
+            Some("a") => register_users(&ctx, vec!["Alice"]),
+            _ => register_users(&ctx, vec!["Bob"]),
+        }
+        Ok(ctx.state())
+    }
+
+    fn cache_scope(&self, request: &Request<()>) -> Option<String> {
+        tenant(request)
+    }
+}
+
+#[tokio::test]
+async fn test_result_cache_scope() {
+    let addr = "0.0.0.0:50224";
+    let result_cache = Arc::new(ResultCache::new(1024 * 1024, Duration::from_secs(60)));
+    let service = FlightSqlService::new_with_provider(Box::new(TenantSessionStateProvider))
+        .with_result_cache(result_cache);
+    start_test_server(addr.to_string(), service).await;
+
+    let mut a = create_test_client(&format!("http://{}", addr)).await;
+    a.set_header("x-tenant", "a");
+    let mut b = create_test_client(&format!("http://{}", addr)).await;
+    b.set_header("x-tenant", "b");
+
+    // The same query of another tenant is not served the cached results
+    assert_eq!(query_names(&mut a).await, "Alice");
+    assert_eq!(query_names(&mut b).await, "Bob");
+    assert_eq!(query_names(&mut a).await, "Alice");
+}
+
+/// Opens a server-side session and returns its cookie.
+async fn open_session(addr: &str) -> String {
+    let endpoint = Endpoint::new(addr.to_string()).expect("Valid endpoint");
+    let channel = endpoint.connect().await.expect("Connection successful");
+    let action = Action {
+        r#type: "GetSessionOptions".to_string(),
+        body: GetSessionOptionsRequest {}.encode_to_vec().into(),
+    };
+    let response = FlightServiceClient::new(channel)
+        .do_action(action)
+        .await
+        .expect("GetSessionOptions should succeed");
+    let cookie = response.metadata().get("set-cookie");
+    cookie
+        .expect("A session should be created")
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+#[tokio::test]
+async fn test_result_cache_sessions() {
+    let addr = "0.0.0.0:50225";
+    let ctx = SessionContext::new();
+    register_users(&ctx, vec!["Alice", "Bob"]);
+    let result_cache = Arc::new(ResultCache::new(1024 * 1024, Duration::from_secs(60)));
+    let service = FlightSqlService::new(ctx.state())
+        .with_sessions(Duration::from_secs(60), 10)
+        .with_result_cache(result_cache);
+    start_test_server(addr.to_string(), service).await;
+
+    let addr = format!("http://{}", addr);
+    let mut a = create_test_client(&addr).await;
+    a.set_header("cookie", open_session(&addr).await);
+    let mut b = create_test_client(&addr).await;
+    b.set_header("cookie", open_session(&addr).await);
+
+    // Each session has a time zone of its own
+    for (client, time_zone) in [(&mut a, "+01:00"), (&mut b, "+02:00")] {
+        client
+            .execute_update(
+                format!("SET datafusion.execution.time_zone = '{time_zone}'"),
+                None,
+            )
+            .await
+            .expect("Set should succeed");
+    }
+    let sql = "SELECT arrow_typeof(now()) AS name";
+    assert_eq!(query(&mut a, sql).await, "Timestamp(ns, \"+01:00\")");
+    assert_eq!(query(&mut b, sql).await, "Timestamp(ns, \"+02:00\")");
+}
+
+#[tokio::test]
+async fn test_result_cache_spill_file_removed() {
+    let addr = "0.0.0.0:50226";
+    let spill_dir =
+        std::env::temp_dir().join(format!("result-cache-removed-{}", std::process::id()));
+    let ctx = SessionContext::new();
+    register_users(&ctx, vec!["Alice", "Bob"]);
+    let result_cache =
+        Arc::new(ResultCache::new(0, Duration::from_secs(60)).with_spill(&spill_dir, 1024 * 1024));
+    let service = FlightSqlService::new(ctx.state()).with_result_cache(result_cache);
+    start_test_server(addr.to_string(), service).await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    assert_eq!(query_names(&mut client).await, "Alice,Bob");
+
+    // Results whose file is gone are executed again
+    for file in std::fs::read_dir(&spill_dir).unwrap() {
+        std::fs::remove_file(file.unwrap().path()).unwrap();
+    }
+    register_users(&ctx, vec!["Charlie"]);
+    assert_eq!(query_names(&mut client).await, "Charlie");
+
+    std::fs::remove_dir_all(&spill_dir).ok();
+}
@@ -0,0 +1,201 @@
+use arrow_flight::{
+    sql::{
+        client::FlightSqlServiceClient, ActionCancelQueryRequest, ActionCancelQueryResult, Any,
+        ProstMessageExt,
+    },
+    Action, CancelFlightInfoRequest, CancelStatus, FlightClient, FlightInfo,
+};
+use datafusion::execution::context::SessionContext;
+use datafusion_flight_sql_server::service::FlightSqlService;
+use futures::{StreamExt, TryStreamExt};
+use prost::Message;
+use tokio::time::{sleep, Duration};
+use tonic::{
+    transport::{Channel, Endpoint},
+    Code,
+};
+
+async fn start_test_server(addr: String) {
+    tokio::spawn(async move {
+        FlightSqlService::new(SessionContext::new().state())
+            .serve(addr)
+            .await
+            .expect("Server should start successfully");
+    });
+
+    sleep(Duration::from_millis(500)).await;
+}
+
+async fn create_test_channel(addr: &str) -> Channel {
+    let endpoint = Endpoint::new(addr.to_string()).expect("Valid endpoint");
+    endpoint.connect().await.expect("Connection successful")
+}
+
+async fn cancel_query(client: &mut FlightSqlServiceClient<Channel>, info: &FlightInfo) -> i32 {
+    let request = ActionCancelQueryRequest {
+        info: info.encode_to_vec().into(),
+    };
+    let action = Action {
+        r#type: "CancelQuery".to_string(),
+        body: request.as_any().encode_to_vec().into(),
+    };
+    let result = client
+        .do_action(action)
+        .await
+        .expect("CancelQuery should succeed")
+        .message()
+        .await
+        .expect("Stream should work")
+        .expect("Should have a result");
+    let result: ActionCancelQueryResult = Any::decode(result.body)
+        .unwrap()
+        .unpack()
+        .unwrap()
+        .expect("Should be a CancelQuery result");
+    result.result
+}
+
+#[tokio::test]
+async fn test_cancel_query() {
+    let addr = "0.0.0.0:50111";
+    start_test_server(addr.to_string()).await;
+
+    let channel = create_test_channel(&format!("http://{}", addr)).await;
+    let mut client = FlightSqlServiceClient::new(channel);
+
+    // Cancelled before being fetched
+    let flight_info = client
+        .execute("SELECT 1".to_string(), None)
+        .await
+        .expect("Query should succeed");
+    assert_eq!(
+        cancel_query(&mut client, &flight_info).await,
+        CancelStatus::Cancelled as i32
+    );
+    let ticket = flight_info.endpoint[0].ticket.clone().unwrap();
+    let status = client
+        .do_get(ticket)
+        .await
+        .expect_err("A cancelled query should not be fetched");
+    assert!(status.to_string().contains("cancelled"), "{status}");
+
+    // Completed before being cancelled
+    let flight_info = client
+        .execute("SELECT 1".to_string(), None)
+        .await
+        .expect("Query should succeed");
+    let ticket = flight_info.endpoint[0].ticket.clone().unwrap();
+    let _batches: Vec<_> = client
+        .do_get(ticket)
+        .await
+        .expect("do_get should succeed")
+        .try_collect()
+        .await
+        .expect("Stream should work");
+    assert_eq!(
+        cancel_query(&mut client, &flight_info).await,
+        CancelStatus::NotCancellable as i32
+    );
+}
+
+#[tokio::test]
+async fn test_cancel_flight_info() {
+    let addr = "0.0.0.0:50112";
+    start_test_server(addr.to_string()).await;
+
+    let channel = create_test_channel(&format!("http://{}", addr)).await;
+    let mut sql_client = FlightSqlServiceClient::new(channel.clone());
+    let mut flight_client = FlightClient::new(channel);
+
+    let flight_info = sql_client
+        .execute(
+            "SELECT * FROM generate_series(1, 10000000000)".to_string(),
+            None,
+        )
+        .await
+        .expect("Query should succeed");
+    let ticket = flight_info.endpoint[0].ticket.clone().unwrap();
+    let mut stream = flight_client
+        .do_get(ticket)
+        .await
+        .expect("do_get should succeed");
+    stream
+        .next()
+        .await
+        .expect("Should have a batch")
+        .expect("Stream should work");
+
+    let result = flight_client
+        .cancel_flight_info(CancelFlightInfoRequest::new(flight_info))
+        .await
+        .expect("CancelFlightInfo should succeed");
+    assert_eq!(result.status(), CancelStatus::Cancelled);
+
+    let error = loop {
+        match stream.next().await {
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => break e,
+            None => panic!("A cancelled query should end with an error"),
+        }
+    };
+    let arrow_flight::error::FlightError::Tonic(status) = error else {
+        panic!("Expected a tonic status, got {error}");
+    };
+    assert_eq!(status.code(), Code::Cancelled);
+}
+
+#[tokio::test]
+async fn test_cancel_flight_info_fetched_twice() {
+    let addr = "0.0.0.0:50113";
+    start_test_server(addr.to_string()).await;
+
+    let channel = create_test_channel(&format!("http://{}", addr)).await;
+    let mut sql_client = FlightSqlServiceClient::new(channel.clone());
+    let mut flight_client = FlightClient::new(channel);
+
+    let flight_info = sql_client
+        .execute(
+            "SELECT * FROM generate_series(1, 10000000000)".to_string(),
+            None,
+        )
+        .await
+        .expect("Query should succeed");
+    let ticket = flight_info.endpoint[0].ticket.clone().unwrap();
+    let mut first = flight_client
+        .do_get(ticket.clone())
+        .await
+        .expect("do_get should succeed");
+    let mut second = flight_client
+        .do_get(ticket)
+        .await
+        .expect("do_get should succeed");
+    for stream in [&mut first, &mut second] {
+        stream
+            .next()
+            .await
+            .expect("Should have a batch")
+            .expect("Stream should work");
+    }
+
+    // The end of one stream leaves the other one cancellable
+    drop(first);
+    sleep(Duration::from_millis(200)).await;
+
+    let result = flight_client
+        .cancel_flight_info(CancelFlightInfoRequest::new(flight_info))
+        .await
+        .expect("CancelFlightInfo should succeed");
+    assert_eq!(result.status(), CancelStatus::Cancelled);
+
+    let error = loop {
+        match second.next().await {
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => break e,
+            None => panic!("A cancelled query should end with an error"),
+        }
+    };
+    let arrow_flight::error::FlightError::Tonic(status) = error else {
+        panic!("Expected a tonic status, got {error}");
+    };
+    assert_eq!(status.code(), Code::Cancelled);
+}
@@ -0,0 +1,292 @@
+use std::{collections::BTreeSet, sync::Arc};
+
+use arrow_flight::{
+    error::FlightError, sql::client::FlightSqlServiceClient, CancelFlightInfoRequest, CancelStatus,
+    FlightClient, FlightInfo,
+};
+use datafusion::arrow::{
+    array::{Int32Array, RecordBatch},
+    datatypes::{DataType, Field, Schema},
+};
+use datafusion::{
+    datasource::MemTable,
+    execution::context::{SessionContext, SessionState},
+    prelude::SessionConfig,
+};
+use datafusion_flight_sql_server::{
+    config::FlightSqlServiceConfig, distributed::Workers, service::FlightSqlService,
+    signing::TicketSigner,
+};
+use futures::{future::try_join_all, StreamExt, TryStreamExt};
+use tokio::time::{sleep, Duration};
+use tonic::{
+    transport::{Channel, Endpoint},
+    Code,
+};
+
+const PARTITIONS: usize = 4;
+
+fn create_test_session() -> SessionState {
+    create_test_session_with_partitions(PARTITIONS)
+}
+
+fn create_test_session_with_partitions(partitions: usize) -> SessionState {
+    let ctx =
+        SessionContext::new_with_config(SessionConfig::new().with_target_partitions(partitions));
+
+    let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+
+    let partitions = (0..partitions as i32)
+        .map(|partition| {
+            let ids = (0..10).map(|i| partition * 10 + i).collect::<Vec<_>>();
+            let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(ids))])
+                .unwrap();
+            vec![batch]
+        })
+        .collect();
+
+    let table = MemTable::try_new(schema, partitions).unwrap();
+    ctx.register_table("numbers", Arc::new(table)).unwrap();
+
+    ctx.state()
+}
+
+/// A worker executing the plans in the tickets signed by its coordinator.
+fn create_worker(state: SessionState) -> FlightSqlService {
+    FlightSqlService::new(state)
+        .with_config(FlightSqlServiceConfig::new().with_plan_in_ticket(true))
+        .with_ticket_signer(Arc::new(TicketSigner::new("k1", b"secret")))
+}
+
+fn create_coordinator(workers: Arc<Workers>) -> FlightSqlService {
+    FlightSqlService::new(create_test_session())
+        .with_workers(workers)
+        .with_ticket_signer(Arc::new(TicketSigner::new("k1", b"secret")))
+}
+
+async fn start_test_server(addr: String, service: FlightSqlService) {
+    tokio::spawn(async move {
+        service
+            .serve(addr)
+            .await
+            .expect("Server should start successfully");
+    });
+
+    sleep(Duration::from_millis(500)).await;
+}
+
+async fn create_test_client(addr: &str) -> FlightSqlServiceClient<Channel> {
+    let endpoint = Endpoint::new(addr.to_string()).expect("Valid endpoint");
+    let channel = endpoint.connect().await.expect("Connection successful");
+    FlightSqlServiceClient::new(channel)
+}
+
+/// Fetches every endpoint of the FlightInfo from its location and returns
+/// the ids of every endpoint, sorted.
+async fn fetch_ids(flight_info: FlightInfo) -> Vec<i32> {
+    let fetches = flight_info.endpoint.into_iter().map(|endpoint| async move {
+        let location = endpoint.location.first().expect("Should have location");
+        let mut client = create_test_client(&location.uri).await;
+        let ticket = endpoint.ticket.expect("Should have ticket");
+        client.do_get(ticket).await?.try_collect::<Vec<_>>().await
+    });
+    let batches: Vec<RecordBatch> = try_join_all(fetches)
+        .await
+        .expect("Every endpoint should be fetched")
+        .into_iter()
+        .flatten()
+        .collect();
+
+    let mut ids = vec![];
+    for batch in batches {
+        let column = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .expect("Should be an Int32 column");
+        ids.extend(column.values().iter().copied());
+    }
+    ids.sort();
+    ids
+}
+
+fn locations(flight_info: &FlightInfo) -> BTreeSet<String> {
+    flight_info
+        .endpoint
+        .iter()
+        .flat_map(|endpoint| {
+            endpoint
+                .location
+                .iter()
+                .map(|location| location.uri.clone())
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_workers_execute_partitions() {
+    let workers = ["0.0.0.0:50201", "0.0.0.0:50202"];
+    for worker in workers {
+        start_test_server(worker.to_string(), create_worker(create_test_session())).await;
+    }
+    let worker_uris = workers.map(|worker| format!("http://{worker}"));
+
+    let addr = "0.0.0.0:50203";
+    let coordinator = create_coordinator(Arc::new(Workers::new(worker_uris.clone())));
+    start_test_server(addr.to_string(), coordinator).await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let flight_info = client
+        .execute("SELECT id FROM numbers WHERE id % 2 = 0".to_string(), None)
+        .await
+        .expect("Query should succeed");
+    assert_eq!(flight_info.endpoint.len(), PARTITIONS);
+    assert_eq!(locations(&flight_info), BTreeSet::from(worker_uris.clone()));
+    let ids = fetch_ids(flight_info).await;
+    assert_eq!(ids, (0..40).step_by(2).collect::<Vec<_>>());
+
+    // Plans merging their partitions are executed by a single worker
+    let flight_info = client
+        .execute("SELECT id FROM numbers ORDER BY id".to_string(), None)
+        .await
+        .expect("Query should succeed");
+    assert_eq!(flight_info.endpoint.len(), 1);
+    assert_eq!(locations(&flight_info).len(), 1);
+    let ids = fetch_ids(flight_info).await;
+    assert_eq!(ids, (0..40).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn test_register_workers() {
+    let worker = "0.0.0.0:50204";
+    start_test_server(worker.to_string(), create_worker(create_test_session())).await;
+    let worker_uri = format!("http://{worker}");
+
+    let addr = "0.0.0.0:50205";
+    let workers = Arc::new(Workers::default());
+    let coordinator = create_coordinator(workers.clone());
+    start_test_server(addr.to_string(), coordinator).await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    // The coordinator executes the queries while no worker is registered
+    let flight_info = client
+        .execute("SELECT id FROM numbers".to_string(), None)
+        .await
+        .expect("Query should succeed");
+    assert_eq!(flight_info.endpoint.len(), 1);
+    assert!(locations(&flight_info).is_empty());
+
+    workers.register(worker_uri.clone());
+    let flight_info = client
+        .execute("SELECT id FROM numbers".to_string(), None)
+        .await
+        .expect("Query should succeed");
+    assert_eq!(flight_info.endpoint.len(), PARTITIONS);
+    assert_eq!(
+        locations(&flight_info),
+        BTreeSet::from([worker_uri.clone()])
+    );
+    let ids = fetch_ids(flight_info).await;
+    assert_eq!(ids, (0..40).collect::<Vec<_>>());
+
+    assert!(workers.deregister(&worker_uri));
+    assert!(workers.locations().is_empty());
+}
+
+#[tokio::test]
+async fn test_cancel_on_worker() {
+    let worker = "0.0.0.0:50206";
+    start_test_server(worker.to_string(), create_worker(create_test_session())).await;
+    let worker_uri = format!("http://{worker}");
+
+    let addr = "0.0.0.0:50207";
+    let coordinator = create_coordinator(Arc::new(Workers::new([worker_uri.clone()])));
+    start_test_server(addr.to_string(), coordinator).await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+    let mut coordinator_client = FlightClient::new_from_inner(client.inner().clone());
+    let endpoint = Endpoint::new(worker_uri).expect("Valid endpoint");
+    let channel = endpoint.connect().await.expect("Connection successful");
+    let mut worker_client = FlightClient::new(channel);
+
+    // Produces far more rows than the test fetches
+    let flight_info = client
+        .execute(
+            "SELECT a.id FROM numbers a, numbers b, numbers c, numbers d, numbers e".to_string(),
+            None,
+        )
+        .await
+        .expect("Query should succeed");
+    assert_eq!(flight_info.endpoint.len(), 1);
+    let ticket = flight_info.endpoint[0].ticket.clone().unwrap();
+    let mut stream = worker_client
+        .do_get(ticket)
+        .await
+        .expect("do_get should succeed");
+    stream
+        .next()
+        .await
+        .expect("Should have a batch")
+        .expect("Stream should work");
+
+    // The coordinator forwards the cancellation to the worker
+    let result = coordinator_client
+        .cancel_flight_info(CancelFlightInfoRequest::new(flight_info.clone()))
+        .await
+        .expect("CancelFlightInfo should succeed");
+    assert_eq!(result.status(), CancelStatus::Cancelled);
+
+    let result = worker_client
+        .cancel_flight_info(CancelFlightInfoRequest::new(flight_info))
+        .await
+        .expect("CancelFlightInfo should succeed");
+    assert_eq!(result.status(), CancelStatus::NotCancellable);
+
+    let error = loop {
+        match stream.next().await {
+            Some(Ok(_)) => continue,
+            Some(Err(e)) => break e,
+            None => panic!("A cancelled query should end with an error"),
+        }
+    };
+    let FlightError::Tonic(status) = error else {
+        panic!("Expected a tonic status, got {error}");
+    };
+    assert_eq!(status.code(), Code::Cancelled);
+}
+
+#[tokio::test]
+async fn test_worker_partition_count_mismatch() {
+    let worker = "0.0.0.0:50208";
+    start_test_server(
+        worker.to_string(),
+        create_worker(create_test_session_with_partitions(PARTITIONS / 2)),
+    )
+    .await;
+    let worker_uri = format!("http://{worker}");
+
+    let addr = "0.0.0.0:50209";
+    let coordinator = create_coordinator(Arc::new(Workers::new([worker_uri.clone()])));
+    start_test_server(addr.to_string(), coordinator).await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+    let mut worker_client = create_test_client(&worker_uri).await;
+
+    // The worker would only return a part of the rows
+    let flight_info = client
+        .execute("SELECT id FROM numbers".to_string(), None)
+        .await
+        .expect("Query should succeed");
+    assert_eq!(flight_info.endpoint.len(), PARTITIONS);
+    let ticket = flight_info.endpoint[0].ticket.clone().unwrap();
+    let result = match worker_client.do_get(ticket).await {
+        Ok(stream) => stream.try_collect::<Vec<_>>().await.map(|_| ()),
+        Err(err) => Err(err),
+    };
+    let Err(FlightError::Tonic(status)) = result else {
+        panic!("Expected a tonic status, got {result:?}");
+    };
+    assert_eq!(status.code(), Code::FailedPrecondition, "{status}");
+}
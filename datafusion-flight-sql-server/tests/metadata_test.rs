@@ -0,0 +1,442 @@
+use std::{collections::HashMap, sync::Arc};
+
+use arrow_flight::{
+    sql::{
+        client::FlightSqlServiceClient, CommandGetCrossReference, CommandGetExportedKeys,
+        CommandGetImportedKeys, CommandGetPrimaryKeys, CommandGetXdbcTypeInfo, SqlInfo,
+        UpdateDeleteRules, XdbcDataType,
+    },
+    FlightInfo,
+};
+use datafusion::arrow::{
+    array::{Int32Array, RecordBatch, StringArray},
+    datatypes::{DataType, Field, Schema},
+    util::pretty::pretty_format_batches,
+};
+use datafusion::{
+    common::{Constraint, Constraints},
+    datasource::MemTable,
+    execution::context::{SQLOptions, SessionContext, SessionState},
+};
+use datafusion_flight_sql_server::{
+    config::FlightSqlServiceConfig,
+    keys::{ForeignKey, MemoryForeignKeyProvider, FOREIGN_KEY_METADATA_KEY},
+    service::FlightSqlService,
+};
+use futures::TryStreamExt;
+use tokio::time::{sleep, Duration};
+use tonic::transport::{Channel, Endpoint};
+
+fn create_test_session() -> SessionState {
+    let ctx = SessionContext::new();
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2, 3])),
+            Arc::new(StringArray::from(vec!["Alice", "Bob", "Charlie"])),
+        ],
+    )
+    .unwrap();
+
+    let table = MemTable::try_new(schema, vec![vec![batch]])
+        .unwrap()
+        .with_constraints(Constraints::new_unverified(vec![Constraint::PrimaryKey(
+            vec![0],
+        )]));
+    ctx.register_table("users", Arc::new(table)).unwrap();
+
+    let memberships_schema = Arc::new(Schema::new(vec![
+        Field::new("user_id", DataType::Int32, false),
+        Field::new("group_name", DataType::Utf8, false),
+    ]));
+    let memberships_table = MemTable::try_new(memberships_schema, vec![vec![]])
+        .unwrap()
+        .with_constraints(Constraints::new_unverified(vec![Constraint::PrimaryKey(
+            vec![1, 0],
+        )]));
+    ctx.register_table("memberships", Arc::new(memberships_table))
+        .unwrap();
+
+    let orders_schema = Arc::new(Schema::new(vec![
+        Field::new("order_id", DataType::Int32, false),
+        Field::new("user_id", DataType::Int32, false).with_metadata(HashMap::from([(
+            FOREIGN_KEY_METADATA_KEY.to_string(),
+            "users.id".to_string(),
+        )])),
+    ]));
+    let orders_table = MemTable::try_new(orders_schema, vec![vec![]]).unwrap();
+    ctx.register_table("orders", Arc::new(orders_table))
+        .unwrap();
+
+    ctx.state()
+}
+
+async fn start_test_server(addr: String, service: FlightSqlService) {
+    tokio::spawn(async move {
+        service
+            .serve(addr)
+            .await
+            .expect("Server should start successfully");
+    });
+
+    sleep(Duration::from_millis(500)).await;
+}
+
+async fn create_test_client(addr: &str) -> FlightSqlServiceClient<Channel> {
+    let endpoint = Endpoint::new(addr.to_string()).expect("Valid endpoint");
+    let channel = endpoint.connect().await.expect("Connection successful");
+    FlightSqlServiceClient::new(channel)
+}
+
+async fn fetch_batches(
+    client: &mut FlightSqlServiceClient<Channel>,
+    flight_info: FlightInfo,
+) -> Vec<RecordBatch> {
+    let ticket = flight_info
+        .endpoint
+        .first()
+        .expect("Should have endpoint")
+        .ticket
+        .clone()
+        .expect("Should have ticket");
+
+    client
+        .do_get(ticket)
+        .await
+        .expect("do_get should succeed")
+        .try_collect()
+        .await
+        .expect("Stream should work")
+}
+
+#[tokio::test]
+async fn test_get_sql_info() {
+    let addr = "0.0.0.0:50081";
+    start_test_server(
+        addr.to_string(),
+        FlightSqlService::new(create_test_session()),
+    )
+    .await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let flight_info = client
+        .get_sql_info(vec![])
+        .await
+        .expect("GetSqlInfo should succeed");
+    let batches = fetch_batches(&mut client, flight_info).await;
+    let total_rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+    assert!(total_rows > 50, "Should return the full SqlInfo table");
+
+    let flight_info = client
+        .get_sql_info(vec![
+            SqlInfo::FlightSqlServerName,
+            SqlInfo::SqlIdentifierQuoteChar,
+        ])
+        .await
+        .expect("GetSqlInfo should succeed");
+    let batches = fetch_batches(&mut client, flight_info).await;
+    let total_rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+    assert_eq!(total_rows, 2, "Should only return the requested infos");
+
+    let formatted = pretty_format_batches(&batches).unwrap().to_string();
+    assert!(formatted.contains("datafusion-flight-sql-server"));
+}
+
+/// Returns the formatted value of the SqlInfo.
+async fn sql_info_value(client: &mut FlightSqlServiceClient<Channel>, info: SqlInfo) -> String {
+    let flight_info = client
+        .get_sql_info(vec![info])
+        .await
+        .expect("GetSqlInfo should succeed");
+    let batches = fetch_batches(client, flight_info).await;
+    pretty_format_batches(&batches).unwrap().to_string()
+}
+
+#[tokio::test]
+async fn test_get_sql_info_read_only() {
+    let writable = "0.0.0.0:50086";
+    start_test_server(
+        writable.to_string(),
+        FlightSqlService::new(create_test_session()),
+    )
+    .await;
+    let read_only = "0.0.0.0:50087";
+    let sql_options = SQLOptions::new()
+        .with_allow_ddl(false)
+        .with_allow_dml(false);
+    start_test_server(
+        read_only.to_string(),
+        FlightSqlService::new(create_test_session()).with_sql_options(sql_options),
+    )
+    .await;
+
+    let mut client = create_test_client(&format!("http://{}", writable)).await;
+    let formatted = sql_info_value(&mut client, SqlInfo::FlightSqlServerReadOnly).await;
+    assert!(formatted.contains("false"), "{formatted}");
+
+    let mut client = create_test_client(&format!("http://{}", read_only)).await;
+    let formatted = sql_info_value(&mut client, SqlInfo::FlightSqlServerReadOnly).await;
+    assert!(formatted.contains("true"), "{formatted}");
+}
+
+#[tokio::test]
+async fn test_get_sql_info_with_overrides() {
+    let addr = "0.0.0.0:50082";
+    let config = FlightSqlServiceConfig::new().with_sql_info(|builder| {
+        builder.append(SqlInfo::FlightSqlServerName, "custom-server");
+        builder.append(10_000_u32, "custom value");
+    });
+    let service = FlightSqlService::new(create_test_session()).with_config(config);
+    start_test_server(addr.to_string(), service).await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let flight_info = client
+        .get_sql_info(vec![SqlInfo::FlightSqlServerName])
+        .await
+        .expect("GetSqlInfo should succeed");
+    let batches = fetch_batches(&mut client, flight_info).await;
+    let formatted = pretty_format_batches(&batches).unwrap().to_string();
+    assert!(formatted.contains("custom-server"));
+    assert!(!formatted.contains("datafusion-flight-sql-server"));
+
+    let flight_info = client
+        .get_sql_info(vec![])
+        .await
+        .expect("GetSqlInfo should succeed");
+    let batches = fetch_batches(&mut client, flight_info).await;
+    let formatted = pretty_format_batches(&batches).unwrap().to_string();
+    assert!(formatted.contains("custom value"));
+}
+
+#[tokio::test]
+async fn test_get_xdbc_type_info() {
+    let addr = "0.0.0.0:50083";
+    start_test_server(
+        addr.to_string(),
+        FlightSqlService::new(create_test_session()),
+    )
+    .await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let flight_info = client
+        .get_xdbc_type_info(CommandGetXdbcTypeInfo { data_type: None })
+        .await
+        .expect("GetXdbcTypeInfo should succeed");
+    let batches = fetch_batches(&mut client, flight_info).await;
+    let formatted = pretty_format_batches(&batches).unwrap().to_string();
+    for type_name in ["VARCHAR", "DECIMAL", "TIMESTAMP WITH TIME ZONE", "INTERVAL"] {
+        assert!(formatted.contains(type_name), "Should report {type_name}");
+    }
+    assert!(formatted.contains("Utf8View"));
+    // The types are reported as the SQL planner plans them
+    assert!(
+        formatted.contains("| DECIMAL ") && formatted.contains("Decimal128(38, 10)"),
+        "{formatted}"
+    );
+    assert!(!formatted.contains("Decimal256"), "{formatted}");
+    assert!(!formatted.contains("ARRAY"), "{formatted}");
+
+    let flight_info = client
+        .get_xdbc_type_info(CommandGetXdbcTypeInfo {
+            data_type: Some(XdbcDataType::XdbcInteger as i32),
+        })
+        .await
+        .expect("GetXdbcTypeInfo should succeed");
+    let batches = fetch_batches(&mut client, flight_info).await;
+    let formatted = pretty_format_batches(&batches).unwrap().to_string();
+    let total_rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+    assert_eq!(total_rows, 2, "Should only return INT and INT UNSIGNED");
+    assert!(formatted.contains("INT UNSIGNED"));
+}
+
+#[tokio::test]
+async fn test_get_primary_keys() {
+    let addr = "0.0.0.0:50084";
+    start_test_server(
+        addr.to_string(),
+        FlightSqlService::new(create_test_session()),
+    )
+    .await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let flight_info = client
+        .get_primary_keys(CommandGetPrimaryKeys {
+            catalog: None,
+            db_schema: None,
+            table: "memberships".to_string(),
+        })
+        .await
+        .expect("GetPrimaryKeys should succeed");
+    let batches = fetch_batches(&mut client, flight_info).await;
+    let formatted = pretty_format_batches(&batches).unwrap().to_string();
+    let expected = [
+        "+--------------+----------------+-------------+-------------+----------+--------------+",
+        "| catalog_name | db_schema_name | table_name  | column_name | key_name | key_sequence |",
+        "+--------------+----------------+-------------+-------------+----------+--------------+",
+        "| datafusion   | public         | memberships | group_name  |          | 1            |",
+        "| datafusion   | public         | memberships | user_id     |          | 2            |",
+        "+--------------+----------------+-------------+-------------+----------+--------------+",
+    ];
+    assert_eq!(formatted, expected.join("\n"));
+
+    let flight_info = client
+        .get_primary_keys(CommandGetPrimaryKeys {
+            catalog: Some("datafusion".to_string()),
+            db_schema: Some("public".to_string()),
+            table: "users".to_string(),
+        })
+        .await
+        .expect("GetPrimaryKeys should succeed");
+    let batches = fetch_batches(&mut client, flight_info).await;
+    let total_rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+    assert_eq!(total_rows, 1, "users should have a single key column");
+
+    let flight_info = client
+        .get_primary_keys(CommandGetPrimaryKeys {
+            catalog: None,
+            db_schema: None,
+            table: "nonexistent_table".to_string(),
+        })
+        .await
+        .expect("GetPrimaryKeys should succeed");
+    let batches = fetch_batches(&mut client, flight_info).await;
+    let total_rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+    assert_eq!(total_rows, 0);
+}
+
+#[tokio::test]
+async fn test_get_foreign_keys() {
+    let addr = "0.0.0.0:50085";
+    let foreign_keys = MemoryForeignKeyProvider::new()
+        .with_table_metadata()
+        .with_foreign_key(
+            ForeignKey::new("memberships", ["user_id"], "users", ["id"])
+                .with_name("fk_memberships_user")
+                .with_delete_rule(UpdateDeleteRules::Cascade),
+        );
+    let service = FlightSqlService::new(create_test_session())
+        .with_foreign_key_provider(Box::new(foreign_keys));
+    start_test_server(addr.to_string(), service).await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let flight_info = client
+        .get_exported_keys(CommandGetExportedKeys {
+            catalog: None,
+            db_schema: None,
+            table: "users".to_string(),
+        })
+        .await
+        .expect("GetExportedKeys should succeed");
+    let batches = fetch_batches(&mut client, flight_info).await;
+    let formatted = pretty_format_batches(&batches).unwrap().to_string();
+    let expected = [
+        "+-----------------+-------------------+---------------+----------------+-----------------+-------------------+---------------+----------------+--------------+---------------------+-------------+-------------+-------------+",
+        "| pk_catalog_name | pk_db_schema_name | pk_table_name | pk_column_name | fk_catalog_name | fk_db_schema_name | fk_table_name | fk_column_name | key_sequence | fk_key_name         | pk_key_name | update_rule | delete_rule |",
+        "+-----------------+-------------------+---------------+----------------+-----------------+-------------------+---------------+----------------+--------------+---------------------+-------------+-------------+-------------+",
+        "| datafusion      | public            | users         | id             | datafusion      | public            | memberships   | user_id        | 1            | fk_memberships_user |             | 3           | 0           |",
+        "| datafusion      | public            | users         | id             | datafusion      | public            | orders        | user_id        | 1            |                     |             | 3           | 3           |",
+        "+-----------------+-------------------+---------------+----------------+-----------------+-------------------+---------------+----------------+--------------+---------------------+-------------+-------------+-------------+",
+    ];
+    assert_eq!(formatted, expected.join("\n"));
+
+    let flight_info = client
+        .get_imported_keys(CommandGetImportedKeys {
+            catalog: Some("datafusion".to_string()),
+            db_schema: Some("public".to_string()),
+            table: "orders".to_string(),
+        })
+        .await
+        .expect("GetImportedKeys should succeed");
+    let batches = fetch_batches(&mut client, flight_info).await;
+    let total_rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+    assert_eq!(total_rows, 1, "orders should reference users once");
+
+    let flight_info = client
+        .get_cross_reference(CommandGetCrossReference {
+            pk_catalog: None,
+            pk_db_schema: None,
+            pk_table: "users".to_string(),
+            fk_catalog: None,
+            fk_db_schema: None,
+            fk_table: "memberships".to_string(),
+        })
+        .await
+        .expect("GetCrossReference should succeed");
+    let batches = fetch_batches(&mut client, flight_info).await;
+    let formatted = pretty_format_batches(&batches).unwrap().to_string();
+    let total_rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+    assert_eq!(total_rows, 1);
+    assert!(formatted.contains("fk_memberships_user"));
+
+    let flight_info = client
+        .get_imported_keys(CommandGetImportedKeys {
+            catalog: None,
+            db_schema: None,
+            table: "users".to_string(),
+        })
+        .await
+        .expect("GetImportedKeys should succeed");
+    let batches = fetch_batches(&mut client, flight_info).await;
+    let total_rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+    assert_eq!(total_rows, 0, "users should not reference other tables");
+}
+
+#[tokio::test]
+async fn test_get_foreign_keys_skips_invalid_keys() {
+    let addr = "0.0.0.0:50088";
+    let ctx = SessionContext::new_with_state(create_test_session());
+    let notes_schema = Arc::new(Schema::new(vec![Field::new(
+        "user_id",
+        DataType::Int32,
+        false,
+    )
+    .with_metadata(HashMap::from([(
+        FOREIGN_KEY_METADATA_KEY.to_string(),
+        "users".to_string(),
+    )]))]));
+    let notes_table = MemTable::try_new(notes_schema, vec![vec![]]).unwrap();
+    ctx.register_table("notes", Arc::new(notes_table)).unwrap();
+
+    let mut mismatched = ForeignKey::new("memberships", ["user_id"], "users", ["id"]);
+    mismatched.fk_columns.push("group_name".to_string());
+    let foreign_keys = MemoryForeignKeyProvider::new()
+        .with_table_metadata()
+        .with_foreign_key(mismatched);
+    let service =
+        FlightSqlService::new(ctx.state()).with_foreign_key_provider(Box::new(foreign_keys));
+    start_test_server(addr.to_string(), service).await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    // Only the valid key of orders is served
+    let flight_info = client
+        .get_exported_keys(CommandGetExportedKeys {
+            catalog: None,
+            db_schema: None,
+            table: "users".to_string(),
+        })
+        .await
+        .expect("GetExportedKeys should succeed");
+    let batches = fetch_batches(&mut client, flight_info).await;
+    let formatted = pretty_format_batches(&batches).unwrap().to_string();
+    let total_rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+    assert_eq!(total_rows, 1, "{formatted}");
+    assert!(formatted.contains("orders"), "{formatted}");
+}
+
+#[test]
+#[should_panic(expected = "references 1 columns with 2 columns")]
+fn test_foreign_key_column_count() {
+    ForeignKey::new("memberships", ["user_id", "group_name"], "users", ["id"]);
+}
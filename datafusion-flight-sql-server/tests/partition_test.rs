@@ -0,0 +1,211 @@
+use std::sync::Arc;
+
+use arrow_flight::{sql::client::FlightSqlServiceClient, FlightInfo};
+use datafusion::arrow::{
+    array::{Int32Array, RecordBatch},
+    datatypes::{DataType, Field, Schema},
+};
+use datafusion::{
+    datasource::MemTable,
+    execution::context::{SessionContext, SessionState},
+    prelude::SessionConfig,
+    scalar::ScalarValue,
+};
+use datafusion_flight_sql_server::{config::FlightSqlServiceConfig, service::FlightSqlService};
+use futures::{future::try_join_all, TryStreamExt};
+use tokio::time::{sleep, Duration};
+use tonic::transport::{Channel, Endpoint};
+
+const PARTITIONS: usize = 4;
+
+fn create_test_session() -> SessionState {
+    let ctx =
+        SessionContext::new_with_config(SessionConfig::new().with_target_partitions(PARTITIONS));
+
+    let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+
+    let partitions = (0..PARTITIONS as i32)
+        .map(|partition| {
+            let ids = (0..10).map(|i| partition * 10 + i).collect::<Vec<_>>();
+            let batch = RecordBatch::try_new(schema.clone(), vec![Arc::new(Int32Array::from(ids))])
+                .unwrap();
+            vec![batch]
+        })
+        .collect();
+
+    let table = MemTable::try_new(schema, partitions).unwrap();
+    ctx.register_table("numbers", Arc::new(table)).unwrap();
+
+    ctx.state()
+}
+
+fn create_test_service(partitioned_endpoints: bool) -> FlightSqlService {
+    FlightSqlService::new(create_test_session()).with_config(
+        FlightSqlServiceConfig::new().with_partitioned_endpoints(partitioned_endpoints),
+    )
+}
+
+async fn start_test_server(addr: String, service: FlightSqlService) {
+    tokio::spawn(async move {
+        service
+            .serve(addr)
+            .await
+            .expect("Server should start successfully");
+    });
+
+    sleep(Duration::from_millis(500)).await;
+}
+
+async fn create_test_client(addr: &str) -> FlightSqlServiceClient<Channel> {
+    let endpoint = Endpoint::new(addr.to_string()).expect("Valid endpoint");
+    let channel = endpoint.connect().await.expect("Connection successful");
+    FlightSqlServiceClient::new(channel)
+}
+
+/// Fetches the endpoints of the FlightInfo in parallel and returns the ids
+/// of every endpoint, sorted.
+async fn fetch_ids(client: &FlightSqlServiceClient<Channel>, flight_info: FlightInfo) -> Vec<i32> {
+    let fetches = flight_info.endpoint.into_iter().map(|endpoint| {
+        let mut client = client.clone();
+        async move {
+            let ticket = endpoint.ticket.expect("Should have ticket");
+            client.do_get(ticket).await?.try_collect::<Vec<_>>().await
+        }
+    });
+    let batches: Vec<RecordBatch> = try_join_all(fetches)
+        .await
+        .expect("Every endpoint should be fetched")
+        .into_iter()
+        .flatten()
+        .collect();
+
+    let mut ids = vec![];
+    for batch in batches {
+        let column = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .expect("Should be an Int32 column");
+        ids.extend(column.values().iter().copied());
+    }
+    ids.sort();
+    ids
+}
+
+#[tokio::test]
+async fn test_partitioned_endpoints() {
+    let addr = "0.0.0.0:50191";
+    start_test_server(addr.to_string(), create_test_service(true)).await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let flight_info = client
+        .execute("SELECT id FROM numbers".to_string(), None)
+        .await
+        .expect("Query should succeed");
+    assert_eq!(flight_info.endpoint.len(), PARTITIONS);
+    let ids = fetch_ids(&client, flight_info).await;
+    assert_eq!(ids, (0..40).collect::<Vec<_>>());
+
+    let flight_info = client
+        .execute("SELECT id FROM numbers WHERE id % 2 = 0".to_string(), None)
+        .await
+        .expect("Query should succeed");
+    assert_eq!(flight_info.endpoint.len(), PARTITIONS);
+    let ids = fetch_ids(&client, flight_info).await;
+    assert_eq!(ids, (0..40).step_by(2).collect::<Vec<_>>());
+
+    // Plans merging their partitions have a single endpoint
+    let flight_info = client
+        .execute("SELECT id FROM numbers ORDER BY id".to_string(), None)
+        .await
+        .expect("Query should succeed");
+    assert_eq!(flight_info.endpoint.len(), 1);
+    let ids = fetch_ids(&client, flight_info).await;
+    assert_eq!(ids, (0..40).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn test_shared_inputs_single_endpoint() {
+    let addr = "0.0.0.0:50194";
+    start_test_server(addr.to_string(), create_test_service(true)).await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    // Aggregates repartition their input by group
+    let flight_info = client
+        .execute(
+            "SELECT min(id) FROM numbers GROUP BY id % 4".to_string(),
+            None,
+        )
+        .await
+        .expect("Query should succeed");
+    assert_eq!(flight_info.endpoint.len(), 1);
+    let ids = fetch_ids(&client, flight_info).await;
+    assert_eq!(ids, vec![0, 1, 2, 3]);
+
+    // Joins repartition or collect their inputs
+    let flight_info = client
+        .execute(
+            "SELECT a.id FROM numbers a JOIN numbers b ON a.id = b.id".to_string(),
+            None,
+        )
+        .await
+        .expect("Query should succeed");
+    assert_eq!(flight_info.endpoint.len(), 1);
+    let ids = fetch_ids(&client, flight_info).await;
+    assert_eq!(ids, (0..40).collect::<Vec<_>>());
+
+    let flight_info = client
+        .execute(
+            "SELECT a.id FROM numbers a CROSS JOIN (SELECT 1) b".to_string(),
+            None,
+        )
+        .await
+        .expect("Query should succeed");
+    assert_eq!(flight_info.endpoint.len(), 1);
+    let ids = fetch_ids(&client, flight_info).await;
+    assert_eq!(ids, (0..40).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn test_partitioned_prepared_statement() {
+    let addr = "0.0.0.0:50192";
+    start_test_server(addr.to_string(), create_test_service(true)).await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let mut prepared = client
+        .prepare("SELECT id FROM numbers WHERE id < $1".to_string(), None)
+        .await
+        .expect("Prepare should succeed");
+    let parameters = RecordBatch::try_from_iter(vec![(
+        "$1",
+        ScalarValue::Int32(Some(25)).to_array().unwrap(),
+    )])
+    .unwrap();
+    prepared
+        .set_parameters(parameters)
+        .expect("Parameters should be set");
+
+    let flight_info = prepared.execute().await.expect("Execute should succeed");
+    assert_eq!(flight_info.endpoint.len(), PARTITIONS);
+    let ids = fetch_ids(&client, flight_info).await;
+    assert_eq!(ids, (0..25).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn test_single_endpoint_by_default() {
+    let addr = "0.0.0.0:50193";
+    start_test_server(addr.to_string(), create_test_service(false)).await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let flight_info = client
+        .execute("SELECT id FROM numbers".to_string(), None)
+        .await
+        .expect("Query should succeed");
+    assert_eq!(flight_info.endpoint.len(), 1);
+    let ids = fetch_ids(&client, flight_info).await;
+    assert_eq!(ids, (0..40).collect::<Vec<_>>());
+}
@@ -0,0 +1,274 @@
+use std::sync::Arc;
+
+use arrow_flight::{
+    error::FlightError,
+    sql::{client::FlightSqlServiceClient, CommandStatementQuery},
+    FlightInfo, Ticket,
+};
+use datafusion::arrow::{
+    array::{ArrayRef, Int32Array, RecordBatch, StringArray},
+    util::pretty::pretty_format_batches,
+};
+use datafusion::{datasource::MemTable, execution::context::SessionContext, scalar::ScalarValue};
+use datafusion_flight_sql_server::{
+    config::FlightSqlServiceConfig, service::FlightSqlService, signing::TicketSigner,
+    state::CommandTicket,
+};
+use datafusion_substrait::logical_plan::producer::to_substrait_plan;
+use futures::TryStreamExt;
+use prost::Message;
+use tokio::time::{sleep, Duration};
+use tonic::{
+    transport::{Channel, Endpoint},
+    Code,
+};
+
+/// Registers the users table with the columns, replacing the previous one.
+fn register_users(ctx: &SessionContext, columns: Vec<(&str, ArrayRef)>) {
+    let batch = RecordBatch::try_from_iter(columns).unwrap();
+    let table = MemTable::try_new(batch.schema(), vec![vec![batch]]).unwrap();
+    ctx.deregister_table("users").unwrap();
+    ctx.register_table("users", Arc::new(table)).unwrap();
+}
+
+fn create_test_context() -> SessionContext {
+    let ctx = SessionContext::new();
+    register_users(
+        &ctx,
+        vec![
+            ("id", Arc::new(Int32Array::from(vec![1, 2, 3]))),
+            (
+                "name",
+                Arc::new(StringArray::from(vec!["Alice", "Bob", "Charlie"])),
+            ),
+        ],
+    );
+    ctx
+}
+
+fn create_test_service(ctx: &SessionContext) -> FlightSqlService {
+    FlightSqlService::new(ctx.state())
+        .with_config(FlightSqlServiceConfig::new().with_plan_in_ticket(true))
+        .with_ticket_signer(Arc::new(TicketSigner::new("k1", b"secret")))
+}
+
+async fn start_test_server(addr: String, service: FlightSqlService) {
+    tokio::spawn(async move {
+        service
+            .serve(addr)
+            .await
+            .expect("Server should start successfully");
+    });
+
+    sleep(Duration::from_millis(500)).await;
+}
+
+async fn create_test_client(addr: &str) -> FlightSqlServiceClient<Channel> {
+    let endpoint = Endpoint::new(addr.to_string()).expect("Valid endpoint");
+    let channel = endpoint.connect().await.expect("Connection successful");
+    FlightSqlServiceClient::new(channel)
+}
+
+fn ticket(flight_info: &FlightInfo) -> Ticket {
+    flight_info
+        .endpoint
+        .first()
+        .expect("Should have endpoint")
+        .ticket
+        .clone()
+        .expect("Should have ticket")
+}
+
+async fn fetch(
+    client: &mut FlightSqlServiceClient<Channel>,
+    flight_info: &FlightInfo,
+) -> Result<Vec<RecordBatch>, Box<dyn std::error::Error>> {
+    let stream = client.do_get(ticket(flight_info)).await?;
+    Ok(stream.try_collect().await?)
+}
+
+#[tokio::test]
+async fn test_plan_in_ticket() {
+    let addr = "0.0.0.0:50211";
+    let ctx = create_test_context();
+    start_test_server(addr.to_string(), create_test_service(&ctx)).await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let flight_info = client
+        .execute("SELECT name FROM users WHERE id > 1".to_string(), None)
+        .await
+        .expect("Query should succeed");
+    let batches = fetch(&mut client, &flight_info)
+        .await
+        .expect("DoGet should succeed");
+    let expected = [
+        "+---------+",
+        "| name    |",
+        "+---------+",
+        "| Bob     |",
+        "| Charlie |",
+        "+---------+",
+    ];
+    assert_eq!(
+        pretty_format_batches(&batches).unwrap().to_string(),
+        expected.join("\n")
+    );
+
+    let mut prepared = client
+        .prepare("SELECT name FROM users WHERE id = $1".to_string(), None)
+        .await
+        .expect("Prepare should succeed");
+    let parameters = RecordBatch::try_from_iter(vec![(
+        "$1",
+        ScalarValue::Int32(Some(2)).to_array().unwrap(),
+    )])
+    .unwrap();
+    prepared
+        .set_parameters(parameters)
+        .expect("Parameters should be set");
+    let flight_info = prepared.execute().await.expect("Execute should succeed");
+    let batches = fetch(&mut client, &flight_info)
+        .await
+        .expect("DoGet should succeed");
+    let expected = ["+------+", "| name |", "+------+", "| Bob  |", "+------+"];
+    assert_eq!(
+        pretty_format_batches(&batches).unwrap().to_string(),
+        expected.join("\n")
+    );
+}
+
+#[tokio::test]
+async fn test_plan_in_ticket_keeps_schema() {
+    let addr = "0.0.0.0:50212";
+    let ctx = create_test_context();
+    start_test_server(addr.to_string(), create_test_service(&ctx)).await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let flight_info = client
+        .execute("SELECT * FROM users".to_string(), None)
+        .await
+        .expect("Query should succeed");
+
+    // A column added after GetFlightInfo is not part of the results
+    register_users(
+        &ctx,
+        vec![
+            ("id", Arc::new(Int32Array::from(vec![4]))),
+            ("name", Arc::new(StringArray::from(vec!["Dave"]))),
+            (
+                "email",
+                Arc::new(StringArray::from(vec!["dave@example.com"])),
+            ),
+        ],
+    );
+
+    let batches = fetch(&mut client, &flight_info)
+        .await
+        .expect("DoGet should succeed");
+    let expected = [
+        "+----+------+",
+        "| id | name |",
+        "+----+------+",
+        "| 4  | Dave |",
+        "+----+------+",
+    ];
+    assert_eq!(
+        pretty_format_batches(&batches).unwrap().to_string(),
+        expected.join("\n")
+    );
+}
+
+#[tokio::test]
+async fn test_plan_in_ticket_schema_changed() {
+    let addr = "0.0.0.0:50213";
+    let ctx = create_test_context();
+    start_test_server(addr.to_string(), create_test_service(&ctx)).await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let flight_info = client
+        .execute("SELECT id FROM users".to_string(), None)
+        .await
+        .expect("Query should succeed");
+
+    // The type of a column changed after GetFlightInfo
+    register_users(
+        &ctx,
+        vec![
+            ("id", Arc::new(StringArray::from(vec!["4"]))),
+            ("name", Arc::new(StringArray::from(vec!["Dave"]))),
+        ],
+    );
+
+    let result = fetch(&mut client, &flight_info).await;
+    assert!(result.is_err(), "DoGet should fail, got {result:?}");
+}
+
+/// A ticket executing the plan of the SQL, as a client could forge it to
+/// skip the rewriting of its plan.
+async fn forged_plan_ticket(
+    ctx: &SessionContext,
+    sql: &str,
+    schema: prost::bytes::Bytes,
+) -> Ticket {
+    let plan = ctx.sql(sql).await.unwrap().into_unoptimized_plan();
+    let plan = to_substrait_plan(&plan, &ctx.state()).unwrap();
+    let command = arrow_flight::sql::Command::CommandStatementQuery(CommandStatementQuery {
+        query: sql.to_string(),
+        transaction_id: None,
+    });
+    Ticket {
+        ticket: CommandTicket::new(command)
+            .with_plan(plan.encode_to_vec().into(), schema)
+            .try_encode()
+            .unwrap(),
+    }
+}
+
+#[tokio::test]
+async fn test_forged_plan_ticket() {
+    let addr = "0.0.0.0:50214";
+    let ctx = create_test_context();
+    start_test_server(addr.to_string(), FlightSqlService::new(ctx.state())).await;
+    let signed = "0.0.0.0:50215";
+    start_test_server(signed.to_string(), create_test_service(&ctx)).await;
+    let unsigned = "0.0.0.0:50216";
+    start_test_server(
+        unsigned.to_string(),
+        FlightSqlService::new(ctx.state())
+            .with_config(FlightSqlServiceConfig::new().with_plan_in_ticket(true)),
+    )
+    .await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+    let flight_info = client
+        .execute("SELECT name FROM users".to_string(), None)
+        .await
+        .expect("Query should succeed");
+    let ticket = forged_plan_ticket(&ctx, "SELECT name FROM users", flight_info.schema).await;
+
+    // Services accept plans only when they opted in and sign their tickets
+    for addr in [addr, signed, unsigned] {
+        let mut client = create_test_client(&format!("http://{}", addr)).await;
+        let result = match client.do_get(ticket.clone()).await {
+            Ok(stream) => stream.try_collect::<Vec<_>>().await.map(|_| ()),
+            Err(err) => Err(err),
+        };
+        let Err(FlightError::Tonic(status)) = result else {
+            panic!("Expected a tonic status, got {result:?}");
+        };
+        assert_eq!(status.code(), Code::Unauthenticated, "{status}");
+    }
+
+    // Plans cannot travel in tickets without a TicketSigner
+    let mut client = create_test_client(&format!("http://{}", unsigned)).await;
+    let result = client
+        .execute("SELECT name FROM users".to_string(), None)
+        .await;
+    let Err(FlightError::Tonic(status)) = result else {
+        panic!("Expected a tonic status, got {result:?}");
+    };
+    assert_eq!(status.code(), Code::FailedPrecondition, "{status}");
+}
@@ -0,0 +1,270 @@
+use std::sync::Arc;
+
+use arrow_flight::{sql::client::FlightSqlServiceClient, FlightInfo};
+use datafusion::arrow::{
+    array::{Int32Array, RecordBatch, StringArray},
+    datatypes::{DataType, Field, Schema},
+    util::pretty::pretty_format_batches,
+};
+use datafusion::{
+    datasource::MemTable,
+    execution::context::{SessionContext, SessionState},
+    prelude::{col, lit},
+};
+use datafusion_flight_sql_server::{
+    auth::{Identity, MemoryAuthenticator},
+    rewrite::MemoryRowPolicy,
+    service::FlightSqlService,
+};
+use futures::TryStreamExt;
+use tokio::time::{sleep, Duration};
+use tonic::{
+    transport::{Channel, Endpoint},
+    Code,
+};
+
+fn create_test_session() -> SessionState {
+    let ctx = SessionContext::new();
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("tenant", DataType::Utf8, false),
+        Field::new("email", DataType::Utf8, false),
+    ]));
+
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2, 3])),
+            Arc::new(StringArray::from(vec!["a", "a", "b"])),
+            Arc::new(StringArray::from(vec![
+                "alice@a.com",
+                "adam@a.com",
+                "bob@b.com",
+            ])),
+        ],
+    )
+    .unwrap();
+
+    let table = MemTable::try_new(schema, vec![vec![batch]]).unwrap();
+    ctx.register_table("users", Arc::new(table)).unwrap();
+
+    ctx.state()
+}
+
+fn create_test_service() -> FlightSqlService {
+    let authenticator = MemoryAuthenticator::new()
+        .with_token(
+            "alice-token",
+            Identity::new("alice").with_attribute("tenant", "a"),
+        )
+        .with_token(
+            "bob-token",
+            Identity::new("bob")
+                .with_attribute("tenant", "b")
+                .with_attribute("role", "admin"),
+        );
+    let policy = MemoryRowPolicy::new()
+        .with_row_filter("users", |identity| {
+            let tenant = identity.and_then(|identity| identity.attribute("tenant"));
+            Some(col("tenant").eq(lit(tenant.unwrap_or_default())))
+        })
+        .with_column_mask("users", "email", |identity, _column| {
+            let admin =
+                identity.is_some_and(|identity| identity.attribute("role") == Some("admin"));
+            (!admin).then(|| lit("***"))
+        });
+    FlightSqlService::new(create_test_session())
+        .with_authenticator(Box::new(authenticator))
+        .with_plan_rewriter(Arc::new(policy))
+}
+
+async fn start_test_server(addr: String, service: FlightSqlService) {
+    tokio::spawn(async move {
+        service
+            .serve(addr)
+            .await
+            .expect("Server should start successfully");
+    });
+
+    sleep(Duration::from_millis(500)).await;
+}
+
+async fn create_test_client(addr: &str, token: &str) -> FlightSqlServiceClient<Channel> {
+    let endpoint = Endpoint::new(addr.to_string()).expect("Valid endpoint");
+    let channel = endpoint.connect().await.expect("Connection successful");
+    let mut client = FlightSqlServiceClient::new(channel);
+    client.set_token(token.to_string());
+    client
+}
+
+async fn fetch_batches(
+    client: &mut FlightSqlServiceClient<Channel>,
+    flight_info: FlightInfo,
+) -> Vec<RecordBatch> {
+    let ticket = flight_info
+        .endpoint
+        .first()
+        .expect("Should have endpoint")
+        .ticket
+        .clone()
+        .expect("Should have ticket");
+
+    client
+        .do_get(ticket)
+        .await
+        .expect("do_get should succeed")
+        .try_collect()
+        .await
+        .expect("Stream should work")
+}
+
+async fn query(client: &mut FlightSqlServiceClient<Channel>, sql: &str) -> String {
+    let flight_info = client
+        .execute(sql.to_string(), None)
+        .await
+        .expect("Query should succeed");
+    let batches = fetch_batches(client, flight_info).await;
+    pretty_format_batches(&batches).unwrap().to_string()
+}
+
+fn assert_permission_denied<T: std::fmt::Debug>(
+    result: Result<T, arrow_flight::error::FlightError>,
+) {
+    match result {
+        Err(arrow_flight::error::FlightError::Tonic(status)) => {
+            assert_eq!(status.code(), Code::PermissionDenied, "{status}")
+        }
+        other => panic!("Expected PermissionDenied, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_row_filter_and_column_mask() {
+    let addr = "0.0.0.0:50161";
+    start_test_server(addr.to_string(), create_test_service()).await;
+
+    let mut alice = create_test_client(&format!("http://{}", addr), "alice-token").await;
+    let mut bob = create_test_client(&format!("http://{}", addr), "bob-token").await;
+
+    let formatted = query(&mut alice, "SELECT * FROM users ORDER BY id").await;
+    let expected = [
+        "+----+--------+-------+",
+        "| id | tenant | email |",
+        "+----+--------+-------+",
+        "| 1  | a      | ***   |",
+        "| 2  | a      | ***   |",
+        "+----+--------+-------+",
+    ];
+    assert_eq!(formatted, expected.join("\n"));
+
+    let formatted = query(&mut bob, "SELECT * FROM users ORDER BY id").await;
+    let expected = [
+        "+----+--------+-----------+",
+        "| id | tenant | email     |",
+        "+----+--------+-----------+",
+        "| 3  | b      | bob@b.com |",
+        "+----+--------+-----------+",
+    ];
+    assert_eq!(formatted, expected.join("\n"));
+
+    // The masked column cannot be used to infer the original values
+    let formatted = query(
+        &mut alice,
+        "SELECT count(*) AS n FROM users u WHERE u.email LIKE 'alice%'",
+    )
+    .await;
+    assert!(formatted.contains("| 0 |"), "{formatted}");
+
+    // Subqueries are rewritten as well
+    let formatted = query(
+        &mut alice,
+        "SELECT count(*) AS n FROM (SELECT DISTINCT tenant FROM users) WHERE tenant IN (SELECT tenant FROM users)",
+    )
+    .await;
+    assert!(formatted.contains("| 1 |"), "{formatted}");
+
+    // The schema of prepared statements reflects the rewritten plan
+    let prepared = alice
+        .prepare("SELECT email FROM users".to_string(), None)
+        .await
+        .expect("Prepare should succeed");
+    let dataset_schema = prepared
+        .dataset_schema()
+        .expect("Should have dataset schema");
+    assert_eq!(dataset_schema.fields().len(), 1);
+    assert_eq!(dataset_schema.field(0).data_type(), &DataType::Utf8);
+}
+
+#[tokio::test]
+async fn test_row_filter_on_updates() {
+    let addr = "0.0.0.0:50162";
+    start_test_server(addr.to_string(), create_test_service()).await;
+
+    let mut alice = create_test_client(&format!("http://{}", addr), "alice-token").await;
+    let mut bob = create_test_client(&format!("http://{}", addr), "bob-token").await;
+
+    let rows = alice
+        .execute_update("UPDATE users SET email = 'hidden@a.com'".to_string(), None)
+        .await
+        .expect("UPDATE should succeed");
+    assert_eq!(rows, 2, "Only the rows of the tenant should be updated");
+
+    let rows = alice
+        .execute_update("DELETE FROM users WHERE id > 1".to_string(), None)
+        .await
+        .expect("DELETE should succeed");
+    assert_eq!(rows, 1, "Only the rows of the tenant should be deleted");
+
+    let formatted = query(&mut bob, "SELECT * FROM users ORDER BY id").await;
+    let expected = [
+        "+----+--------+-----------+",
+        "| id | tenant | email     |",
+        "+----+--------+-----------+",
+        "| 3  | b      | bob@b.com |",
+        "+----+--------+-----------+",
+    ];
+    assert_eq!(formatted, expected.join("\n"));
+}
+
+#[tokio::test]
+async fn test_column_mask_on_updates() {
+    let addr = "0.0.0.0:50163";
+    start_test_server(addr.to_string(), create_test_service()).await;
+
+    let mut alice = create_test_client(&format!("http://{}", addr), "alice-token").await;
+    let mut bob = create_test_client(&format!("http://{}", addr), "bob-token").await;
+
+    // Copying a masked column into a visible one would reveal it
+    assert_permission_denied(
+        alice
+            .execute_update("UPDATE users SET id = ascii(email)".to_string(), None)
+            .await,
+    );
+    // The number of deleted rows would tell whether the masked column matches
+    assert_permission_denied(
+        alice
+            .execute_update("DELETE FROM users WHERE email LIKE 'a%'".to_string(), None)
+            .await,
+    );
+    assert_permission_denied(
+        alice
+            .execute_update(
+                "DELETE FROM users WHERE id IN (SELECT id FROM users WHERE email LIKE 'a%')"
+                    .to_string(),
+                None,
+            )
+            .await,
+    );
+
+    let formatted = query(&mut alice, "SELECT id FROM users ORDER BY id").await;
+    let expected = ["+----+", "| id |", "+----+", "| 1  |", "| 2  |", "+----+"];
+    assert_eq!(formatted, expected.join("\n"));
+
+    // The column is not masked for bob
+    let rows = bob
+        .execute_update("DELETE FROM users WHERE email LIKE 'b%'".to_string(), None)
+        .await
+        .expect("DELETE should succeed");
+    assert_eq!(rows, 1);
+}
@@ -58,6 +58,7 @@ fn create_test_session() -> SessionState {
 async fn start_test_server(addr: String, state: SessionState) {
     let config = FlightSqlServiceConfig {
         schema_with_metadata: true,
+        ..Default::default()
     };
 
     let service = FlightSqlService::new(state).with_config(config);
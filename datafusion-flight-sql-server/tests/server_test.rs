@@ -0,0 +1,274 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use arrow_flight::{decode::FlightRecordBatchStream, sql::client::FlightSqlServiceClient};
+use datafusion::arrow::{
+    array::{Int32Array, RecordBatch, StringArray},
+    datatypes::{DataType, Field, Schema},
+};
+use datafusion::{
+    datasource::MemTable,
+    execution::context::{SessionContext, SessionState},
+};
+use datafusion_flight_sql_server::{server::FlightSqlServer, service::FlightSqlService};
+use futures::{StreamExt, TryStreamExt};
+use tokio::{
+    sync::oneshot,
+    task::JoinHandle,
+    time::{sleep, timeout, Duration},
+};
+use tonic::{
+    service::InterceptorLayer,
+    transport::{Channel, Endpoint, Server},
+    Request, Status,
+};
+
+/// Rows returned by the query streamed while the server shuts down, large
+/// enough not to fit in the HTTP/2 flow control windows.
+const ROWS: usize = 4_000_000;
+
+fn create_test_session() -> SessionState {
+    let ctx = SessionContext::new();
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2, 3])),
+            Arc::new(StringArray::from(vec!["Alice", "Bob", "Charlie"])),
+        ],
+    )
+    .unwrap();
+
+    let table = MemTable::try_new(schema, vec![vec![batch]]).unwrap();
+    ctx.register_table("users", Arc::new(table)).unwrap();
+
+    ctx.state()
+}
+
+/// Starts the server and returns the sender of its shutdown signal along
+/// with its task.
+async fn start_test_server(
+    addr: String,
+    server: FlightSqlServer,
+) -> (oneshot::Sender<()>, JoinHandle<()>) {
+    let (shutdown_tx, shutdown_rx) = oneshot::channel();
+    let handle = tokio::spawn(async move {
+        server
+            .serve_with_shutdown(addr, async {
+                shutdown_rx.await.ok();
+            })
+            .await
+            .expect("Server should start successfully");
+    });
+
+    sleep(Duration::from_millis(500)).await;
+    (shutdown_tx, handle)
+}
+
+async fn create_test_client(addr: &str) -> FlightSqlServiceClient<Channel> {
+    let endpoint = Endpoint::new(addr.to_string())
+        .expect("Valid endpoint")
+        .timeout(Duration::from_secs(2));
+    let channel = endpoint.connect().await.expect("Connection successful");
+    FlightSqlServiceClient::new(channel)
+}
+
+async fn do_get(
+    client: &mut FlightSqlServiceClient<Channel>,
+    sql: &str,
+) -> Result<FlightRecordBatchStream, arrow_flight::error::FlightError> {
+    let flight_info = client.execute(sql.to_string(), None).await?;
+    let ticket = flight_info
+        .endpoint
+        .first()
+        .expect("Should have endpoint")
+        .ticket
+        .clone()
+        .expect("Should have ticket");
+    client.do_get(ticket).await
+}
+
+#[tokio::test]
+async fn test_message_size_limits() {
+    let addr = "0.0.0.0:50181";
+    let (_shutdown_tx, _) = start_test_server(
+        addr.to_string(),
+        FlightSqlServer::new(FlightSqlService::new(create_test_session()))
+            .with_max_decoding_message_size(1024)
+            .with_max_encoding_message_size(16 * 1024),
+    )
+    .await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let batches: Vec<RecordBatch> = do_get(&mut client, "SELECT * FROM users")
+        .await
+        .expect("Small requests should succeed")
+        .try_collect()
+        .await
+        .expect("Small responses should succeed");
+    assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 3);
+
+    let sql = format!("SELECT '{}' AS padding", "x".repeat(2048));
+    let result = do_get(&mut client, &sql).await;
+    assert!(result.is_err(), "Requests over the limit should fail");
+
+    let result = match do_get(&mut client, "SELECT * FROM generate_series(1, 100000)").await {
+        Ok(stream) => stream.try_collect::<Vec<_>>().await.map(|_| ()),
+        Err(e) => Err(e),
+    };
+    assert!(result.is_err(), "Responses over the limit should fail");
+}
+
+#[tokio::test]
+async fn test_graceful_shutdown() {
+    let addr = "0.0.0.0:50182";
+    let (shutdown_tx, handle) = start_test_server(
+        addr.to_string(),
+        FlightSqlServer::new(FlightSqlService::new(create_test_session()))
+            .with_shutdown_timeout(Duration::from_secs(30)),
+    )
+    .await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+    let mut stream = do_get(
+        &mut client,
+        &format!("SELECT * FROM generate_series(1, {ROWS})"),
+    )
+    .await
+    .expect("Query should succeed");
+    let first = stream
+        .next()
+        .await
+        .expect("Should have a batch")
+        .expect("Batch should succeed");
+
+    shutdown_tx.send(()).unwrap();
+    sleep(Duration::from_millis(200)).await;
+
+    // The stream in flight is drained
+    let rest: Vec<RecordBatch> = stream
+        .try_collect()
+        .await
+        .expect("The stream in flight should complete");
+    let rows = first.num_rows() + rest.iter().map(|b| b.num_rows()).sum::<usize>();
+    assert_eq!(rows, ROWS);
+
+    timeout(Duration::from_secs(5), handle)
+        .await
+        .expect("Server should stop once the stream completes")
+        .unwrap();
+
+    // New requests are refused
+    let result = do_get(&mut client, "SELECT 1").await;
+    assert!(result.is_err(), "Requests after the shutdown should fail");
+}
+
+#[tokio::test]
+async fn test_shutdown_timeout() {
+    let addr = "0.0.0.0:50183";
+    let (shutdown_tx, handle) = start_test_server(
+        addr.to_string(),
+        FlightSqlServer::new(FlightSqlService::new(create_test_session()))
+            .with_shutdown_timeout(Duration::from_millis(200)),
+    )
+    .await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+    let mut stream = do_get(
+        &mut client,
+        &format!("SELECT * FROM generate_series(1, {ROWS})"),
+    )
+    .await
+    .expect("Query should succeed");
+    stream
+        .next()
+        .await
+        .expect("Should have a batch")
+        .expect("Batch should succeed");
+
+    // The stream is not read until the server stops
+    shutdown_tx.send(()).unwrap();
+    timeout(Duration::from_secs(5), handle)
+        .await
+        .expect("Server should stop at the shutdown timeout")
+        .unwrap();
+
+    let result = stream.try_collect::<Vec<_>>().await;
+    assert!(result.is_err(), "The stream in flight should be cancelled");
+}
+
+#[tokio::test]
+async fn test_into_service() {
+    let addr = "0.0.0.0:50184";
+    let requests = Arc::new(AtomicUsize::new(0));
+    let counter = requests.clone();
+    let service = FlightSqlServer::new(FlightSqlService::new(create_test_session()))
+        .with_max_decoding_message_size(1024)
+        .into_service();
+    tokio::spawn(async move {
+        Server::builder()
+            .layer(InterceptorLayer::new(move |request: Request<()>| {
+                counter.fetch_add(1, Ordering::SeqCst);
+                match request.metadata().get("x-api-key") {
+                    Some(key) if key == "secret" => Ok(request),
+                    _ => Err(Status::unauthenticated("Invalid API key")),
+                }
+            }))
+            .add_service(service)
+            .serve(addr.parse().unwrap())
+            .await
+            .expect("Server should start successfully");
+    });
+    sleep(Duration::from_millis(500)).await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+    let result = do_get(&mut client, "SELECT * FROM users").await;
+    assert!(result.is_err(), "The layer should reject the request");
+
+    client.set_header("x-api-key", "secret");
+    let batches: Vec<RecordBatch> = do_get(&mut client, "SELECT * FROM users")
+        .await
+        .expect("Query should succeed")
+        .try_collect()
+        .await
+        .expect("Stream should work");
+    assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 3);
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+    let sql = format!("SELECT '{}' AS padding", "x".repeat(2048));
+    let result = do_get(&mut client, &sql).await;
+    assert!(result.is_err(), "The message size limit should apply");
+}
+
+#[tokio::test]
+async fn test_into_router() {
+    let addr = "0.0.0.0:50185";
+    let router = FlightSqlServer::new(FlightSqlService::new(create_test_session()))
+        .with_tcp_nodelay(false)
+        .into_router()
+        .expect("Router should be built");
+    tokio::spawn(async move {
+        router
+            .serve(addr.parse().unwrap())
+            .await
+            .expect("Server should start successfully");
+    });
+    sleep(Duration::from_millis(500)).await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+    let batches: Vec<RecordBatch> = do_get(&mut client, "SELECT * FROM users")
+        .await
+        .expect("Query should succeed")
+        .try_collect()
+        .await
+        .expect("Stream should work");
+    assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 3);
+}
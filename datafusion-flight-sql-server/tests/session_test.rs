@@ -0,0 +1,269 @@
+use std::sync::Arc;
+
+use arrow_flight::{
+    flight_service_client::FlightServiceClient, sql::client::FlightSqlServiceClient, Action,
+};
+use datafusion::arrow::{
+    array::{Int32Array, RecordBatch, StringArray},
+    datatypes::{DataType, Field, Schema},
+};
+use datafusion::{
+    datasource::MemTable,
+    execution::context::{SessionContext, SessionState},
+};
+use datafusion_flight_sql_server::{
+    service::FlightSqlService,
+    session::{
+        close_session_result, session_option_value::OptionValue, set_session_options_result,
+        CloseSessionRequest, CloseSessionResult, GetSessionOptionsRequest, GetSessionOptionsResult,
+        SessionOptionValue, SetSessionOptionsRequest, SetSessionOptionsResult, SESSION_COOKIE,
+    },
+};
+use futures::StreamExt;
+use prost::{bytes::Bytes, Message};
+use tokio::time::{sleep, Duration};
+use tonic::{
+    transport::{Channel, Endpoint},
+    Code, Request, Status,
+};
+
+fn create_test_session() -> SessionState {
+    let ctx = SessionContext::new();
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2, 3])),
+            Arc::new(StringArray::from(vec!["Alice", "Bob", "Charlie"])),
+        ],
+    )
+    .unwrap();
+
+    let table = MemTable::try_new(schema, vec![vec![batch]]).unwrap();
+    ctx.register_table("users", Arc::new(table)).unwrap();
+
+    ctx.state()
+}
+
+async fn start_test_server(addr: String, service: FlightSqlService) {
+    tokio::spawn(async move {
+        service
+            .serve(addr)
+            .await
+            .expect("Server should start successfully");
+    });
+
+    sleep(Duration::from_millis(500)).await;
+}
+
+async fn create_test_client(addr: &str) -> FlightSqlServiceClient<Channel> {
+    let endpoint = Endpoint::new(addr.to_string()).expect("Valid endpoint");
+    let channel = endpoint.connect().await.expect("Connection successful");
+    FlightSqlServiceClient::new(channel)
+}
+
+/// Sends a session action with the session cookie, if any, and returns the
+/// session cookie set by the server, if any, along with the result.
+async fn session_action(
+    client: &mut FlightServiceClient<Channel>,
+    cookie: Option<&str>,
+    action_type: &str,
+    body: Vec<u8>,
+) -> Result<(Option<String>, Bytes), Status> {
+    let mut request = Request::new(Action {
+        r#type: action_type.to_string(),
+        body: body.into(),
+    });
+    if let Some(cookie) = cookie {
+        request
+            .metadata_mut()
+            .insert("cookie", cookie.parse().unwrap());
+    }
+    let response = client.do_action(request).await?;
+    let cookie = response
+        .metadata()
+        .get("set-cookie")
+        .map(|cookie| cookie.to_str().unwrap().to_string());
+    let result = response
+        .into_inner()
+        .next()
+        .await
+        .expect("Should have a result")?;
+    Ok((cookie, result.body))
+}
+
+async fn get_session_options(
+    client: &mut FlightServiceClient<Channel>,
+    cookie: Option<&str>,
+) -> Result<(Option<String>, GetSessionOptionsResult), Status> {
+    let (cookie, body) = session_action(
+        client,
+        cookie,
+        "GetSessionOptions",
+        GetSessionOptionsRequest {}.encode_to_vec(),
+    )
+    .await?;
+    Ok((cookie, GetSessionOptionsResult::decode(body).unwrap()))
+}
+
+fn string_option(result: &GetSessionOptionsResult, name: &str) -> Option<String> {
+    match &result.session_options.get(name)?.option_value {
+        Some(OptionValue::StringValue(value)) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+#[tokio::test]
+async fn test_session_options() {
+    let addr = "0.0.0.0:50131";
+    start_test_server(
+        addr.to_string(),
+        FlightSqlService::new(create_test_session()).with_sessions(Duration::from_secs(60), 2),
+    )
+    .await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+    let mut flight_client = client.inner().clone();
+
+    let (cookie, options) = get_session_options(&mut flight_client, None)
+        .await
+        .expect("GetSessionOptions should succeed");
+    let cookie = cookie.expect("A session should be created");
+    assert!(
+        cookie.starts_with(&format!("{SESSION_COOKIE}=")),
+        "{cookie}"
+    );
+    assert_eq!(
+        string_option(&options, "datafusion.execution.batch_size").as_deref(),
+        Some("8192")
+    );
+
+    let request = SetSessionOptionsRequest {
+        session_options: [
+            (
+                "datafusion.execution.batch_size",
+                OptionValue::Int64Value(1024),
+            ),
+            (
+                "datafusion.execution.coalesce_batches",
+                OptionValue::StringValue("maybe".to_string()),
+            ),
+            ("datafusion.missing", OptionValue::BoolValue(true)),
+        ]
+        .into_iter()
+        .map(|(name, value)| {
+            (
+                name.to_string(),
+                SessionOptionValue {
+                    option_value: Some(value),
+                },
+            )
+        })
+        .collect(),
+    };
+    let (new_cookie, body) = session_action(
+        &mut flight_client,
+        Some(&cookie),
+        "SetSessionOptions",
+        request.encode_to_vec(),
+    )
+    .await
+    .expect("SetSessionOptions should succeed");
+    assert_eq!(new_cookie, None, "The session should be reused");
+    let result = SetSessionOptionsResult::decode(body).unwrap();
+    assert_eq!(result.errors.len(), 2, "{result:?}");
+    assert_eq!(
+        result.errors["datafusion.missing"].value(),
+        set_session_options_result::ErrorValue::InvalidName
+    );
+    assert_eq!(
+        result.errors["datafusion.execution.coalesce_batches"].value(),
+        set_session_options_result::ErrorValue::InvalidValue
+    );
+
+    // SET statements are executed within the session as well
+    client.set_header("cookie", cookie.clone());
+    client
+        .execute_update(
+            "SET datafusion.execution.target_partitions = 3".to_string(),
+            None,
+        )
+        .await
+        .expect("SET should succeed");
+
+    let (_, options) = get_session_options(&mut flight_client, Some(&cookie))
+        .await
+        .expect("GetSessionOptions should succeed");
+    assert_eq!(
+        string_option(&options, "datafusion.execution.batch_size").as_deref(),
+        Some("1024")
+    );
+    assert_eq!(
+        string_option(&options, "datafusion.execution.target_partitions").as_deref(),
+        Some("3")
+    );
+
+    // Other sessions are not affected
+    let (other_cookie, options) = get_session_options(&mut flight_client, None)
+        .await
+        .expect("GetSessionOptions should succeed");
+    assert!(other_cookie.is_some());
+    assert_eq!(
+        string_option(&options, "datafusion.execution.batch_size").as_deref(),
+        Some("8192")
+    );
+
+    let result = get_session_options(&mut flight_client, None).await;
+    assert_eq!(
+        result.expect_err("The sessions should be capped").code(),
+        Code::ResourceExhausted
+    );
+
+    let (_, body) = session_action(
+        &mut flight_client,
+        Some(&cookie),
+        "CloseSession",
+        CloseSessionRequest {}.encode_to_vec(),
+    )
+    .await
+    .expect("CloseSession should succeed");
+    let result = CloseSessionResult::decode(body).unwrap();
+    assert_eq!(result.status(), close_session_result::Status::Closed);
+
+    let result = client.execute("SELECT 1".to_string(), None).await;
+    assert!(result.is_err(), "The session should be closed");
+}
+
+#[tokio::test]
+async fn test_session_expiry() {
+    let addr = "0.0.0.0:50132";
+    start_test_server(
+        addr.to_string(),
+        FlightSqlService::new(create_test_session()).with_sessions(Duration::from_millis(500), 10),
+    )
+    .await;
+
+    let client = create_test_client(&format!("http://{}", addr)).await;
+    let mut flight_client = client.inner().clone();
+
+    let (cookie, _) = get_session_options(&mut flight_client, None)
+        .await
+        .expect("GetSessionOptions should succeed");
+    let cookie = cookie.expect("A session should be created");
+
+    get_session_options(&mut flight_client, Some(&cookie))
+        .await
+        .expect("The session should be open");
+
+    sleep(Duration::from_millis(800)).await;
+    let result = get_session_options(&mut flight_client, Some(&cookie)).await;
+    assert_eq!(
+        result.expect_err("The session should expire").code(),
+        Code::NotFound
+    );
+}
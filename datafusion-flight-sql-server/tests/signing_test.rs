@@ -0,0 +1,378 @@
+use std::sync::Arc;
+
+use arrow_flight::{
+    error::FlightError,
+    sql::{
+        client::FlightSqlServiceClient, CommandPreparedStatementQuery, CommandStatementQuery,
+        ProstMessageExt,
+    },
+    FlightDescriptor, FlightInfo, Ticket,
+};
+use datafusion::arrow::{
+    array::{Int32Array, RecordBatch, StringArray},
+    datatypes::{DataType, Field, Schema},
+    util::pretty::pretty_format_batches,
+};
+use datafusion::{
+    datasource::MemTable,
+    execution::context::{SessionContext, SessionState},
+    scalar::ScalarValue,
+};
+use datafusion_flight_sql_server::{
+    auth::{Identity, MemoryAuthenticator},
+    service::FlightSqlService,
+    signing::TicketSigner,
+    state::{CommandTicket, QueryHandle},
+};
+use futures::TryStreamExt;
+use prost::Message;
+use tokio::time::{sleep, Duration};
+use tonic::{
+    transport::{Channel, Endpoint},
+    Code,
+};
+
+fn create_test_session() -> SessionState {
+    let ctx = SessionContext::new();
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2, 3])),
+            Arc::new(StringArray::from(vec!["Alice", "Bob", "Charlie"])),
+        ],
+    )
+    .unwrap();
+
+    let table = MemTable::try_new(schema, vec![vec![batch]]).unwrap();
+    ctx.register_table("users", Arc::new(table)).unwrap();
+
+    ctx.state()
+}
+
+fn create_test_service(ticket_signer: TicketSigner) -> FlightSqlService {
+    FlightSqlService::new(create_test_session()).with_ticket_signer(Arc::new(ticket_signer))
+}
+
+async fn start_test_server(addr: String, service: FlightSqlService) {
+    tokio::spawn(async move {
+        service
+            .serve(addr)
+            .await
+            .expect("Server should start successfully");
+    });
+
+    sleep(Duration::from_millis(500)).await;
+}
+
+async fn create_test_client(addr: &str) -> FlightSqlServiceClient<Channel> {
+    let endpoint = Endpoint::new(addr.to_string()).expect("Valid endpoint");
+    let channel = endpoint.connect().await.expect("Connection successful");
+    FlightSqlServiceClient::new(channel)
+}
+
+fn ticket(flight_info: &FlightInfo) -> Ticket {
+    flight_info
+        .endpoint
+        .first()
+        .expect("Should have endpoint")
+        .ticket
+        .clone()
+        .expect("Should have ticket")
+}
+
+async fn fetch(
+    client: &mut FlightSqlServiceClient<Channel>,
+    ticket: Ticket,
+) -> Result<String, FlightError> {
+    let batches: Vec<RecordBatch> = client.do_get(ticket).await?.try_collect().await?;
+    Ok(pretty_format_batches(&batches).unwrap().to_string())
+}
+
+fn assert_unauthenticated<T: std::fmt::Debug>(result: Result<T, FlightError>) {
+    match result {
+        Err(FlightError::Tonic(status)) => {
+            assert_eq!(status.code(), Code::Unauthenticated, "{status}")
+        }
+        other => panic!("Expected Unauthenticated, got {other:?}"),
+    }
+}
+
+/// A ticket running the SQL, as a client could forge it.
+fn forged_ticket(sql: &str) -> Ticket {
+    let command = arrow_flight::sql::Command::CommandStatementQuery(CommandStatementQuery {
+        query: sql.to_string(),
+        transaction_id: None,
+    });
+    Ticket {
+        ticket: CommandTicket::new(command).try_encode().unwrap(),
+    }
+}
+
+#[tokio::test]
+async fn test_signed_tickets() {
+    let addr = "0.0.0.0:50231";
+    start_test_server(
+        addr.to_string(),
+        create_test_service(TicketSigner::new("k1", b"secret")),
+    )
+    .await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let flight_info = client
+        .execute("SELECT name FROM users WHERE id = 1".to_string(), None)
+        .await
+        .expect("Query should succeed");
+    let result = fetch(&mut client, ticket(&flight_info))
+        .await
+        .expect("DoGet should succeed");
+    let expected = [
+        "+-------+",
+        "| name  |",
+        "+-------+",
+        "| Alice |",
+        "+-------+",
+    ];
+    assert_eq!(result, expected.join("\n"));
+
+    // Tickets must be signed by the service
+    assert_unauthenticated(fetch(&mut client, forged_ticket("SELECT * FROM users")).await);
+
+    // Tampered tickets fail verification
+    let mut tampered = ticket(&flight_info).ticket.to_vec();
+    let position = tampered
+        .windows(b"id = 1".len())
+        .position(|window| window == b"id = 1")
+        .expect("Signed tickets should contain the query");
+    tampered[position + 5] = b'2';
+    assert_unauthenticated(
+        fetch(
+            &mut client,
+            Ticket {
+                ticket: tampered.into(),
+            },
+        )
+        .await,
+    );
+}
+
+#[tokio::test]
+async fn test_signed_prepared_statements() {
+    let addr = "0.0.0.0:50232";
+    start_test_server(
+        addr.to_string(),
+        create_test_service(TicketSigner::new("k1", b"secret")),
+    )
+    .await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let mut prepared = client
+        .prepare("SELECT name FROM users WHERE id = $1".to_string(), None)
+        .await
+        .expect("Prepare should succeed");
+    let parameters = RecordBatch::try_from_iter(vec![(
+        "$1",
+        ScalarValue::Int32(Some(2)).to_array().unwrap(),
+    )])
+    .unwrap();
+    prepared
+        .set_parameters(parameters)
+        .expect("Parameters should be set");
+    let flight_info = prepared.execute().await.expect("Execute should succeed");
+    let result = fetch(&mut client, ticket(&flight_info))
+        .await
+        .expect("DoGet should succeed");
+    let expected = ["+------+", "| name |", "+------+", "| Bob  |", "+------+"];
+    assert_eq!(result, expected.join("\n"));
+
+    // Prepared statement handles must be signed by the service
+    let command = CommandPreparedStatementQuery {
+        prepared_statement_handle: QueryHandle::new("SELECT * FROM users".to_string(), None)
+            .encode(),
+    };
+    let descriptor = FlightDescriptor::new_cmd(command.as_any().encode_to_vec());
+    let status = client
+        .inner_mut()
+        .get_flight_info(descriptor)
+        .await
+        .expect_err("A forged handle should be rejected");
+    assert_eq!(status.code(), Code::Unauthenticated, "{status}");
+}
+
+#[tokio::test]
+async fn test_encrypted_tickets() {
+    let addr = "0.0.0.0:50233";
+    start_test_server(
+        addr.to_string(),
+        create_test_service(TicketSigner::new("k1", b"secret").with_encryption(true)),
+    )
+    .await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let flight_info = client
+        .execute("SELECT name FROM users WHERE id = 3".to_string(), None)
+        .await
+        .expect("Query should succeed");
+    let ticket = ticket(&flight_info);
+    assert!(
+        !ticket
+            .ticket
+            .windows(b"users".len())
+            .any(|window| window == b"users"),
+        "Encrypted tickets should not contain the query"
+    );
+    let result = fetch(&mut client, ticket)
+        .await
+        .expect("DoGet should succeed");
+    let expected = [
+        "+---------+",
+        "| name    |",
+        "+---------+",
+        "| Charlie |",
+        "+---------+",
+    ];
+    assert_eq!(result, expected.join("\n"));
+}
+
+#[tokio::test]
+async fn test_key_rotation() {
+    let previous = "0.0.0.0:50234";
+    start_test_server(
+        previous.to_string(),
+        create_test_service(TicketSigner::new("k1", b"previous secret")),
+    )
+    .await;
+    let rotated = "0.0.0.0:50235";
+    start_test_server(
+        rotated.to_string(),
+        create_test_service(
+            TicketSigner::new("k2", b"next secret").with_verification_key("k1", b"previous secret"),
+        ),
+    )
+    .await;
+    let retired = "0.0.0.0:50236";
+    start_test_server(
+        retired.to_string(),
+        create_test_service(TicketSigner::new("k2", b"next secret")),
+    )
+    .await;
+
+    let mut previous_client = create_test_client(&format!("http://{}", previous)).await;
+    let mut rotated_client = create_test_client(&format!("http://{}", rotated)).await;
+    let mut retired_client = create_test_client(&format!("http://{}", retired)).await;
+
+    // Tickets signed with the previous key are still verified after rotation
+    let flight_info = previous_client
+        .execute("SELECT count(*) AS count FROM users".to_string(), None)
+        .await
+        .expect("Query should succeed");
+    fetch(&mut rotated_client, ticket(&flight_info))
+        .await
+        .expect("DoGet should succeed");
+
+    // Once the previous key is retired, its tickets are rejected
+    assert_unauthenticated(fetch(&mut retired_client, ticket(&flight_info)).await);
+
+    // Tickets signed with the next key are verified by every rotated service
+    let flight_info = rotated_client
+        .execute("SELECT count(*) AS count FROM users".to_string(), None)
+        .await
+        .expect("Query should succeed");
+    fetch(&mut retired_client, ticket(&flight_info))
+        .await
+        .expect("DoGet should succeed");
+    assert_unauthenticated(fetch(&mut previous_client, ticket(&flight_info)).await);
+}
+
+#[tokio::test]
+async fn test_encryption_downgrade() {
+    let encrypting = "0.0.0.0:50237";
+    start_test_server(
+        encrypting.to_string(),
+        create_test_service(TicketSigner::new("k1", b"secret").with_encryption(true)),
+    )
+    .await;
+    let signing = "0.0.0.0:50238";
+    start_test_server(
+        signing.to_string(),
+        create_test_service(TicketSigner::new("k1", b"secret")),
+    )
+    .await;
+
+    let mut encrypting_client = create_test_client(&format!("http://{}", encrypting)).await;
+    let mut signing_client = create_test_client(&format!("http://{}", signing)).await;
+
+    // Services only accept the messages of the mode they are configured with
+    let flight_info = signing_client
+        .execute("SELECT name FROM users".to_string(), None)
+        .await
+        .expect("Query should succeed");
+    assert_unauthenticated(fetch(&mut encrypting_client, ticket(&flight_info)).await);
+
+    let flight_info = encrypting_client
+        .execute("SELECT name FROM users".to_string(), None)
+        .await
+        .expect("Query should succeed");
+    assert_unauthenticated(fetch(&mut signing_client, ticket(&flight_info)).await);
+}
+
+#[tokio::test]
+async fn test_expired_tickets() {
+    let addr = "0.0.0.0:50239";
+    start_test_server(
+        addr.to_string(),
+        create_test_service(TicketSigner::new("k1", b"secret").with_ttl(Duration::from_secs(1))),
+    )
+    .await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let flight_info = client
+        .execute("SELECT name FROM users".to_string(), None)
+        .await
+        .expect("Query should succeed");
+    fetch(&mut client, ticket(&flight_info))
+        .await
+        .expect("DoGet should succeed");
+
+    sleep(Duration::from_millis(2100)).await;
+    assert_unauthenticated(fetch(&mut client, ticket(&flight_info)).await);
+}
+
+#[tokio::test]
+async fn test_tickets_bound_to_identity() {
+    let addr = "0.0.0.0:50240";
+    let authenticator = MemoryAuthenticator::new()
+        .with_token("token-a", Identity::new("alice"))
+        .with_token("token-b", Identity::new("bob"));
+    start_test_server(
+        addr.to_string(),
+        create_test_service(TicketSigner::new("k1", b"secret"))
+            .with_authenticator(Box::new(authenticator)),
+    )
+    .await;
+
+    let mut alice = create_test_client(&format!("http://{}", addr)).await;
+    alice.set_token("token-a".to_string());
+    let mut bob = create_test_client(&format!("http://{}", addr)).await;
+    bob.set_token("token-b".to_string());
+
+    let flight_info = alice
+        .execute("SELECT name FROM users".to_string(), None)
+        .await
+        .expect("Query should succeed");
+    fetch(&mut alice, ticket(&flight_info))
+        .await
+        .expect("DoGet should succeed");
+
+    // Tickets issued to a client cannot be used by another one
+    assert_unauthenticated(fetch(&mut bob, ticket(&flight_info)).await);
+}
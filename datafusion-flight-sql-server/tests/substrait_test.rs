@@ -0,0 +1,375 @@
+use std::sync::Arc;
+
+use arrow_flight::{
+    sql::{
+        client::FlightSqlServiceClient, server::FlightSqlService as _,
+        ActionCreatePreparedSubstraitPlanRequest, CommandPreparedStatementQuery,
+        CommandStatementSubstraitPlan, DoPutUpdateResult, ProstMessageExt, SubstraitPlan,
+    },
+    Action, FlightData, FlightDescriptor, FlightInfo,
+};
+use datafusion::arrow::{
+    array::{Int32Array, RecordBatch, StringArray},
+    datatypes::{DataType, Field, Schema},
+    util::pretty::pretty_format_batches,
+};
+use datafusion::{
+    datasource::MemTable,
+    execution::context::{SQLOptions, SessionContext, SessionState},
+};
+use datafusion_flight_sql_server::service::FlightSqlService;
+use datafusion_substrait::{
+    logical_plan::producer::to_substrait_plan,
+    serializer::serialize_bytes,
+    substrait::proto::{
+        ddl_rel::{self, DdlObject, DdlOp},
+        plan_rel,
+        rel::RelType,
+        write_rel::{self, WriteOp},
+        DdlRel, NamedObjectWrite, Plan, PlanRel, Rel, RelRoot, WriteRel,
+    },
+};
+use futures::{stream, TryStreamExt};
+use prost::Message;
+use tokio::time::{sleep, Duration};
+use tonic::{
+    transport::{Channel, Endpoint},
+    Code, Request,
+};
+
+fn create_test_session() -> SessionState {
+    let ctx = SessionContext::new();
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2, 3])),
+            Arc::new(StringArray::from(vec!["Alice", "Bob", "Charlie"])),
+        ],
+    )
+    .unwrap();
+
+    let table = MemTable::try_new(schema, vec![vec![batch]]).unwrap();
+    ctx.register_table("users", Arc::new(table)).unwrap();
+
+    ctx.state()
+}
+
+async fn start_test_server(addr: String, service: FlightSqlService) {
+    tokio::spawn(async move {
+        service
+            .serve(addr)
+            .await
+            .expect("Server should start successfully");
+    });
+
+    sleep(Duration::from_millis(500)).await;
+}
+
+async fn create_test_client(addr: &str) -> FlightSqlServiceClient<Channel> {
+    let endpoint = Endpoint::new(addr.to_string()).expect("Valid endpoint");
+    let channel = endpoint.connect().await.expect("Connection successful");
+    FlightSqlServiceClient::new(channel)
+}
+
+async fn fetch_batches(
+    client: &mut FlightSqlServiceClient<Channel>,
+    flight_info: FlightInfo,
+) -> Vec<RecordBatch> {
+    let ticket = flight_info
+        .endpoint
+        .first()
+        .expect("Should have endpoint")
+        .ticket
+        .clone()
+        .expect("Should have ticket");
+
+    client
+        .do_get(ticket)
+        .await
+        .expect("do_get should succeed")
+        .try_collect()
+        .await
+        .expect("Stream should work")
+}
+
+async fn substrait_plan(state: &SessionState, sql: &str) -> SubstraitPlan {
+    let ctx = SessionContext::new_with_state(state.clone());
+    let plan = serialize_bytes(sql, &ctx)
+        .await
+        .expect("SQL should convert to Substrait");
+    SubstraitPlan {
+        plan: plan.into(),
+        version: "0.1".to_string(),
+    }
+}
+
+/// Wraps the Substrait plan of the SQL query into a WriteRel on `table`.
+async fn write_plan(state: &SessionState, op: WriteOp, table: &str, sql: &str) -> SubstraitPlan {
+    let ctx = SessionContext::new_with_state(state.clone());
+    let input = ctx.sql(sql).await.unwrap().into_optimized_plan().unwrap();
+    let mut plan = to_substrait_plan(&input, &ctx.state()).expect("Plan should convert");
+    let Some(plan_rel::RelType::Root(root)) = plan.relations[0].rel_type.take() else {
+        panic!("Expected a root relation");
+    };
+    let write = WriteRel {
+        op: op as i32,
+        input: root.input.map(Box::new),
+        write_type: Some(write_rel::WriteType::NamedTable(NamedObjectWrite {
+            names: vec![table.to_string()],
+            advanced_extension: None,
+        })),
+        ..Default::default()
+    };
+    substrait_bytes(plan.as_ref().clone(), RelType::Write(Box::new(write)))
+}
+
+/// Returns the Substrait plan made of the extensions of `plan` and `rel`.
+fn substrait_bytes(plan: Plan, rel: RelType) -> SubstraitPlan {
+    let plan = Plan {
+        relations: vec![PlanRel {
+            rel_type: Some(plan_rel::RelType::Root(RelRoot {
+                input: Some(Rel {
+                    rel_type: Some(rel),
+                }),
+                names: vec![],
+            })),
+        }],
+        ..plan
+    };
+    SubstraitPlan {
+        plan: plan.encode_to_vec().into(),
+        version: "0.1".to_string(),
+    }
+}
+
+async fn execute_substrait_update(
+    client: &mut FlightSqlServiceClient<Channel>,
+    plan: SubstraitPlan,
+) -> Result<i64, tonic::Status> {
+    let cmd = CommandStatementSubstraitPlan {
+        plan: Some(plan),
+        transaction_id: None,
+    };
+    let flight_data = FlightData {
+        flight_descriptor: Some(FlightDescriptor::new_cmd(cmd.as_any().encode_to_vec())),
+        ..Default::default()
+    };
+    let mut response = client
+        .inner_mut()
+        .do_put(stream::iter(vec![flight_data]))
+        .await?
+        .into_inner();
+    let result = response.message().await?.expect("Should have a result");
+    let result = DoPutUpdateResult::decode(&*result.app_metadata).unwrap();
+    Ok(result.record_count)
+}
+
+async fn query(client: &mut FlightSqlServiceClient<Channel>, sql: &str) -> String {
+    let flight_info = client
+        .execute(sql.to_string(), None)
+        .await
+        .expect("Query should succeed");
+    let batches = fetch_batches(client, flight_info).await;
+    pretty_format_batches(&batches).unwrap().to_string()
+}
+
+#[tokio::test]
+async fn test_prepared_substrait_plan() {
+    let addr = "0.0.0.0:50121";
+    let state = create_test_session();
+    start_test_server(addr.to_string(), FlightSqlService::new(state.clone())).await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    // arrow-flight drops the result of the CreatePreparedSubstraitPlan action,
+    // so the statement is prepared by calling the service directly
+    let service = FlightSqlService::new(state.clone());
+    let prepared = service
+        .do_action_create_prepared_substrait_plan(
+            ActionCreatePreparedSubstraitPlanRequest {
+                plan: Some(substrait_plan(&state, "SELECT name FROM users WHERE id > 1").await),
+                transaction_id: None,
+            },
+            Request::new(Action::default()),
+        )
+        .await
+        .expect("Preparing should succeed");
+    assert!(!prepared.dataset_schema.is_empty());
+
+    let cmd = CommandPreparedStatementQuery {
+        prepared_statement_handle: prepared.prepared_statement_handle,
+    };
+    let flight_info = client
+        .inner_mut()
+        .get_flight_info(FlightDescriptor::new_cmd(cmd.as_any().encode_to_vec()))
+        .await
+        .expect("GetFlightInfo should succeed")
+        .into_inner();
+    let batches = fetch_batches(&mut client, flight_info).await;
+    let formatted = pretty_format_batches(&batches).unwrap().to_string();
+    let expected = [
+        "+---------+",
+        "| name    |",
+        "+---------+",
+        "| Bob     |",
+        "| Charlie |",
+        "+---------+",
+    ];
+    assert_eq!(formatted, expected.join("\n"));
+}
+
+#[tokio::test]
+async fn test_substrait_update() {
+    let addr = "0.0.0.0:50122";
+    let state = create_test_session();
+    start_test_server(addr.to_string(), FlightSqlService::new(state.clone())).await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let plan = write_plan(
+        &state,
+        WriteOp::Insert,
+        "users",
+        "SELECT CAST(id + 3 AS INT) AS id, name FROM users WHERE id < 3",
+    )
+    .await;
+    let count = execute_substrait_update(&mut client, plan)
+        .await
+        .expect("Substrait INSERT should succeed");
+    assert_eq!(count, 2);
+
+    let plan = write_plan(
+        &state,
+        WriteOp::Delete,
+        "users",
+        "SELECT * FROM users WHERE id = 2",
+    )
+    .await;
+    let count = execute_substrait_update(&mut client, plan)
+        .await
+        .expect("Substrait DELETE should succeed");
+    assert_eq!(count, 1);
+
+    let formatted = query(&mut client, "SELECT * FROM users ORDER BY id").await;
+    let expected = [
+        "+----+---------+",
+        "| id | name    |",
+        "+----+---------+",
+        "| 1  | Alice   |",
+        "| 3  | Charlie |",
+        "| 4  | Alice   |",
+        "| 5  | Bob     |",
+        "+----+---------+",
+    ];
+    assert_eq!(formatted, expected.join("\n"));
+
+    // A DdlRel creating a table with the schema of the users table
+    let ctx = SessionContext::new_with_state(state.clone());
+    let users = ctx.sql("SELECT * FROM users").await.unwrap();
+    let users = to_substrait_plan(&users.into_optimized_plan().unwrap(), &ctx.state()).unwrap();
+    let Some(plan_rel::RelType::Root(root)) = &users.relations[0].rel_type else {
+        panic!("Expected a root relation");
+    };
+    let Some(RelType::Read(read)) = root.input.as_ref().and_then(|rel| rel.rel_type.clone()) else {
+        panic!("Expected a read relation");
+    };
+    let ddl = DdlRel {
+        object: DdlObject::Table as i32,
+        op: DdlOp::Create as i32,
+        table_schema: read.base_schema,
+        write_type: Some(ddl_rel::WriteType::NamedObject(NamedObjectWrite {
+            names: vec!["archived_users".to_string()],
+            advanced_extension: None,
+        })),
+        ..Default::default()
+    };
+    let plan = substrait_bytes(users.as_ref().clone(), RelType::Ddl(Box::new(ddl)));
+    let count = execute_substrait_update(&mut client, plan)
+        .await
+        .expect("Substrait CREATE TABLE should succeed");
+    assert_eq!(count, 0);
+
+    let formatted = query(&mut client, "SELECT count(*) AS n FROM archived_users").await;
+    assert!(formatted.contains("| 0 |"), "{formatted}");
+
+    let plan = write_plan(&state, WriteOp::Insert, "missing", "SELECT * FROM users").await;
+    let result = execute_substrait_update(&mut client, plan).await;
+    assert!(result.is_err(), "INSERT into a missing table should fail");
+}
+
+/// Asserts the request failed because the SQLOptions do not allow DML.
+fn assert_dml_rejected<T: std::fmt::Debug>(result: Result<T, tonic::Status>, message: &str) {
+    match result {
+        Err(status) => {
+            assert_eq!(status.code(), Code::Internal, "{status}");
+            assert!(status.message().contains("DML not supported"), "{status}");
+        }
+        Ok(result) => panic!("{message}, got {result:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_substrait_sql_options() {
+    let addr = "0.0.0.0:50123";
+    let state = create_test_session();
+    let read_only = || {
+        FlightSqlService::new(state.clone()).with_sql_options(
+            SQLOptions::new()
+                .with_allow_ddl(false)
+                .with_allow_dml(false),
+        )
+    };
+    start_test_server(addr.to_string(), read_only()).await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let insert = write_plan(&state, WriteOp::Insert, "users", "SELECT * FROM users").await;
+
+    let result = execute_substrait_update(&mut client, insert.clone()).await;
+    assert_dml_rejected(result, "Substrait INSERT should be rejected");
+
+    let cmd = CommandStatementSubstraitPlan {
+        plan: Some(insert.clone()),
+        transaction_id: None,
+    };
+    let result = client
+        .inner_mut()
+        .get_flight_info(FlightDescriptor::new_cmd(cmd.as_any().encode_to_vec()))
+        .await;
+    assert_dml_rejected(result, "Substrait INSERT should not be planned");
+
+    let result = read_only()
+        .do_action_create_prepared_substrait_plan(
+            ActionCreatePreparedSubstraitPlanRequest {
+                plan: Some(insert),
+                transaction_id: None,
+            },
+            Request::new(Action::default()),
+        )
+        .await;
+    assert_dml_rejected(result, "Substrait INSERT should not be prepared");
+
+    let formatted = query(&mut client, "SELECT count(*) AS n FROM users").await;
+    assert!(formatted.contains("| 3 |"), "{formatted}");
+
+    // Queries are still allowed
+    let cmd = CommandStatementSubstraitPlan {
+        plan: Some(substrait_plan(&state, "SELECT name FROM users WHERE id = 1").await),
+        transaction_id: None,
+    };
+    let flight_info = client
+        .inner_mut()
+        .get_flight_info(FlightDescriptor::new_cmd(cmd.as_any().encode_to_vec()))
+        .await
+        .expect("Substrait query should be planned")
+        .into_inner();
+    let batches = fetch_batches(&mut client, flight_info).await;
+    let formatted = pretty_format_batches(&batches).unwrap().to_string();
+    assert!(formatted.contains("| Alice |"), "{formatted}");
+}
@@ -0,0 +1,219 @@
+use std::sync::{Arc, Mutex};
+
+use arrow_flight::sql::client::FlightSqlServiceClient;
+use async_trait::async_trait;
+use datafusion::arrow::{
+    array::{Int32Array, RecordBatch, StringArray},
+    datatypes::{DataType, Field, Schema},
+    util::pretty::pretty_format_batches,
+};
+use datafusion::{
+    datasource::MemTable,
+    execution::context::{SessionContext, SessionState},
+};
+use datafusion_flight_sql_server::{service::FlightSqlService, session::SessionStateProvider};
+use futures::TryStreamExt;
+use rcgen::{BasicConstraints, CertificateParams, IsCa, Issuer, KeyPair};
+use tokio::time::{sleep, Duration};
+use tonic::{
+    transport::{Certificate, Channel, ClientTlsConfig, Endpoint, Identity, ServerTlsConfig},
+    Request, Status,
+};
+
+fn create_test_session() -> SessionState {
+    let ctx = SessionContext::new();
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2, 3])),
+            Arc::new(StringArray::from(vec!["Alice", "Bob", "Charlie"])),
+        ],
+    )
+    .unwrap();
+
+    let table = MemTable::try_new(schema, vec![vec![batch]]).unwrap();
+    ctx.register_table("users", Arc::new(table)).unwrap();
+
+    ctx.state()
+}
+
+/// A certificate authority, and the certificates and keys it issued to the
+/// server and to a client, as PEM.
+struct Certificates {
+    ca: String,
+    server: (String, String),
+    client: (String, String),
+    client_der: Vec<u8>,
+}
+
+fn generate_certificates() -> Certificates {
+    let ca_key = KeyPair::generate().unwrap();
+    let mut ca_params = CertificateParams::new(vec![]).unwrap();
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let ca = ca_params.self_signed(&ca_key).unwrap();
+    let issuer = Issuer::new(ca_params, ca_key);
+
+    let server_key = KeyPair::generate().unwrap();
+    let server = CertificateParams::new(vec!["localhost".to_string()])
+        .unwrap()
+        .signed_by(&server_key, &issuer)
+        .unwrap();
+
+    let client_key = KeyPair::generate().unwrap();
+    let client = CertificateParams::new(vec!["client".to_string()])
+        .unwrap()
+        .signed_by(&client_key, &issuer)
+        .unwrap();
+
+    Certificates {
+        ca: ca.pem(),
+        server: (server.pem(), server_key.serialize_pem()),
+        client: (client.pem(), client_key.serialize_pem()),
+        client_der: client.der().to_vec(),
+    }
+}
+
+/// Records the certificates of the clients.
+#[derive(Clone, Default)]
+struct PeerCertsSessionStateProvider {
+    peer_certs: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+#[async_trait]
+impl SessionStateProvider for PeerCertsSessionStateProvider {
+    async fn new_context(&self, request: &Request<()>) -> Result<SessionState, Status> {
+        let certs = request
+            .peer_certs()
+            .ok_or_else(|| Status::unauthenticated("Expected client certificates"))?;
+        self.peer_certs
+            .lock()
+            .unwrap()
+            .extend(certs.iter().map(|cert| cert.to_vec()));
+        Ok(create_test_session())
+    }
+}
+
+async fn start_test_server(addr: String, service: FlightSqlService) {
+    tokio::spawn(async move {
+        service
+            .serve(addr)
+            .await
+            .expect("Server should start successfully");
+    });
+
+    sleep(Duration::from_millis(500)).await;
+}
+
+async fn create_test_client(
+    addr: &str,
+    tls: Option<ClientTlsConfig>,
+) -> Result<FlightSqlServiceClient<Channel>, tonic::transport::Error> {
+    let mut endpoint = Endpoint::new(addr.to_string()).expect("Valid endpoint");
+    if let Some(tls) = tls {
+        endpoint = endpoint.tls_config(tls)?;
+    }
+    let channel = endpoint.connect().await?;
+    Ok(FlightSqlServiceClient::new(channel))
+}
+
+async fn query(
+    client: &mut FlightSqlServiceClient<Channel>,
+    sql: &str,
+) -> Result<String, arrow_flight::error::FlightError> {
+    let flight_info = client.execute(sql.to_string(), None).await?;
+    let ticket = flight_info
+        .endpoint
+        .first()
+        .expect("Should have endpoint")
+        .ticket
+        .clone()
+        .expect("Should have ticket");
+    let batches: Vec<RecordBatch> = client.do_get(ticket).await?.try_collect().await?;
+    Ok(pretty_format_batches(&batches).unwrap().to_string())
+}
+
+#[tokio::test]
+async fn test_tls() {
+    let certificates = generate_certificates();
+    let addr = "0.0.0.0:50171";
+    let (cert, key) = &certificates.server;
+    start_test_server(
+        addr.to_string(),
+        FlightSqlService::new(create_test_session())
+            .with_tls(ServerTlsConfig::new().identity(Identity::from_pem(cert, key))),
+    )
+    .await;
+
+    let tls = ClientTlsConfig::new()
+        .ca_certificate(Certificate::from_pem(&certificates.ca))
+        .domain_name("localhost");
+    let mut client = create_test_client("https://localhost:50171", Some(tls))
+        .await
+        .expect("TLS connection should succeed");
+    let formatted = query(&mut client, "SELECT count(*) AS n FROM users")
+        .await
+        .expect("Query should succeed");
+    assert!(formatted.contains("| 3 |"), "{formatted}");
+
+    // Plaintext connections are refused
+    let refused = match create_test_client("http://localhost:50171", None).await {
+        Ok(mut client) => query(&mut client, "SELECT 1").await.is_err(),
+        Err(_) => true,
+    };
+    assert!(refused, "Plaintext requests should fail");
+}
+
+#[tokio::test]
+async fn test_mutual_tls() {
+    let certificates = generate_certificates();
+    let addr = "0.0.0.0:50172";
+    let provider = PeerCertsSessionStateProvider::default();
+    let (cert, key) = &certificates.server;
+    start_test_server(
+        addr.to_string(),
+        FlightSqlService::new_with_provider(Box::new(provider.clone())).with_tls(
+            ServerTlsConfig::new()
+                .identity(Identity::from_pem(cert, key))
+                .client_ca_root(Certificate::from_pem(&certificates.ca)),
+        ),
+    )
+    .await;
+
+    let (client_cert, client_key) = &certificates.client;
+    let tls = ClientTlsConfig::new()
+        .ca_certificate(Certificate::from_pem(&certificates.ca))
+        .identity(Identity::from_pem(client_cert, client_key))
+        .domain_name("localhost");
+    let mut client = create_test_client("https://localhost:50172", Some(tls))
+        .await
+        .expect("mTLS connection should succeed");
+    let formatted = query(&mut client, "SELECT count(*) AS n FROM users")
+        .await
+        .expect("Query should succeed");
+    assert!(formatted.contains("| 3 |"), "{formatted}");
+
+    let peer_certs = provider.peer_certs.lock().unwrap().clone();
+    assert!(!peer_certs.is_empty());
+    assert!(
+        peer_certs
+            .iter()
+            .all(|cert| *cert == certificates.client_der),
+        "The provider should get the client certificate"
+    );
+
+    // Clients without a certificate are refused
+    let tls = ClientTlsConfig::new()
+        .ca_certificate(Certificate::from_pem(&certificates.ca))
+        .domain_name("localhost");
+    let refused = match create_test_client("https://localhost:50172", Some(tls)).await {
+        Ok(mut client) => query(&mut client, "SELECT 1").await.is_err(),
+        Err(_) => true,
+    };
+    assert!(refused, "Clients without certificates should fail");
+}
@@ -0,0 +1,381 @@
+use std::sync::Arc;
+
+use arrow_flight::{
+    error::FlightError,
+    sql::{
+        client::FlightSqlServiceClient, ActionBeginSavepointRequest, ActionBeginSavepointResult,
+        ActionEndSavepointRequest, Any, EndTransaction, ProstMessageExt,
+    },
+    Action,
+};
+use datafusion::arrow::{
+    array::{Int32Array, RecordBatch, StringArray},
+    datatypes::{DataType, Field, Schema},
+    util::pretty::pretty_format_batches,
+};
+use datafusion::{
+    datasource::MemTable,
+    execution::context::{SessionContext, SessionState},
+};
+use datafusion_flight_sql_server::{
+    auth::{Identity, MemoryAuthenticator},
+    service::FlightSqlService,
+    transaction::ReadOnlyTransactionManager,
+};
+use futures::TryStreamExt;
+use prost::{bytes::Bytes, Message};
+use tokio::time::{sleep, Duration};
+use tonic::{
+    transport::{Channel, Endpoint},
+    Code,
+};
+
+fn create_test_session() -> SessionState {
+    let ctx = SessionContext::new();
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2, 3])),
+            Arc::new(StringArray::from(vec!["Alice", "Bob", "Charlie"])),
+        ],
+    )
+    .unwrap();
+
+    let table = MemTable::try_new(schema, vec![vec![batch]]).unwrap();
+    ctx.register_table("users", Arc::new(table)).unwrap();
+
+    ctx.state()
+}
+
+async fn start_test_server(addr: String, service: FlightSqlService) {
+    tokio::spawn(async move {
+        service
+            .serve(addr)
+            .await
+            .expect("Server should start successfully");
+    });
+
+    sleep(Duration::from_millis(500)).await;
+}
+
+async fn create_test_client(addr: &str) -> FlightSqlServiceClient<Channel> {
+    let endpoint = Endpoint::new(addr.to_string()).expect("Valid endpoint");
+    let channel = endpoint.connect().await.expect("Connection successful");
+    FlightSqlServiceClient::new(channel)
+}
+
+async fn query(
+    client: &mut FlightSqlServiceClient<Channel>,
+    sql: &str,
+    transaction_id: Option<Bytes>,
+) -> String {
+    let flight_info = client
+        .execute(sql.to_string(), transaction_id)
+        .await
+        .expect("Query should succeed");
+    let ticket = flight_info
+        .endpoint
+        .first()
+        .expect("Should have endpoint")
+        .ticket
+        .clone()
+        .expect("Should have ticket");
+    let batches: Vec<RecordBatch> = client
+        .do_get(ticket)
+        .await
+        .expect("do_get should succeed")
+        .try_collect()
+        .await
+        .expect("Stream should work");
+    pretty_format_batches(&batches).unwrap().to_string()
+}
+
+async fn begin_savepoint(
+    client: &mut FlightSqlServiceClient<Channel>,
+    transaction_id: Bytes,
+    name: &str,
+) -> Bytes {
+    let request = ActionBeginSavepointRequest {
+        transaction_id,
+        name: name.to_string(),
+    };
+    let action = Action {
+        r#type: "BeginSavepoint".to_string(),
+        body: request.as_any().encode_to_vec().into(),
+    };
+    let result = client
+        .do_action(action)
+        .await
+        .expect("BeginSavepoint should succeed")
+        .message()
+        .await
+        .expect("Stream should work")
+        .expect("Should have a result");
+    let result: ActionBeginSavepointResult = Any::decode(result.body)
+        .unwrap()
+        .unpack()
+        .unwrap()
+        .expect("Should be a BeginSavepoint result");
+    result.savepoint_id
+}
+
+// action is 1 to release the savepoint and 2 to roll back to it
+async fn end_savepoint(
+    client: &mut FlightSqlServiceClient<Channel>,
+    savepoint_id: Bytes,
+    action: i32,
+) -> Result<(), arrow_flight::error::FlightError> {
+    let request = ActionEndSavepointRequest {
+        savepoint_id,
+        action,
+    };
+    let action = Action {
+        r#type: "EndSavepoint".to_string(),
+        body: request.as_any().encode_to_vec().into(),
+    };
+    client.do_action(action).await.map(|_| ())
+}
+
+#[tokio::test]
+async fn test_read_only_transaction() {
+    let addr = "0.0.0.0:50101";
+    start_test_server(
+        addr.to_string(),
+        FlightSqlService::new(create_test_session()),
+    )
+    .await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let transaction_id = client
+        .begin_transaction()
+        .await
+        .expect("BeginTransaction should succeed");
+
+    let formatted = query(
+        &mut client,
+        "SELECT name FROM users WHERE id = 1",
+        Some(transaction_id.clone()),
+    )
+    .await;
+    assert!(formatted.contains("Alice"), "{formatted}");
+
+    let result = client
+        .execute_update(
+            "INSERT INTO users VALUES (4, 'Dave')".to_string(),
+            Some(transaction_id.clone()),
+        )
+        .await;
+    assert!(result.is_err(), "Transactions should be read-only");
+
+    client
+        .end_transaction(transaction_id.clone(), EndTransaction::Commit)
+        .await
+        .expect("EndTransaction should succeed");
+
+    let result = client
+        .execute("SELECT 1".to_string(), Some(transaction_id.clone()))
+        .await;
+    assert!(result.is_err(), "The transaction should be closed");
+
+    let result = client
+        .end_transaction(transaction_id, EndTransaction::Rollback)
+        .await;
+    assert!(result.is_err(), "The transaction should be closed");
+
+    let count = client
+        .execute_update("INSERT INTO users VALUES (4, 'Dave')".to_string(), None)
+        .await
+        .expect("INSERT outside of a transaction should succeed");
+    assert_eq!(count, 1);
+}
+
+#[tokio::test]
+async fn test_savepoints() {
+    let addr = "0.0.0.0:50102";
+    start_test_server(
+        addr.to_string(),
+        FlightSqlService::new(create_test_session()),
+    )
+    .await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let transaction_id = client
+        .begin_transaction()
+        .await
+        .expect("BeginTransaction should succeed");
+    let first = begin_savepoint(&mut client, transaction_id.clone(), "first").await;
+    let second = begin_savepoint(&mut client, transaction_id.clone(), "second").await;
+    assert_ne!(first, second);
+
+    // Rolling back to a savepoint releases the later ones only
+    end_savepoint(&mut client, first.clone(), 2)
+        .await
+        .expect("Rolling back should succeed");
+    assert!(end_savepoint(&mut client, second, 1).await.is_err());
+
+    end_savepoint(&mut client, first.clone(), 1)
+        .await
+        .expect("Releasing should succeed");
+    assert!(end_savepoint(&mut client, first, 1).await.is_err());
+
+    // Savepoints do not outlive their transaction
+    let third = begin_savepoint(&mut client, transaction_id.clone(), "third").await;
+    client
+        .end_transaction(transaction_id.clone(), EndTransaction::Rollback)
+        .await
+        .expect("EndTransaction should succeed");
+    assert!(end_savepoint(&mut client, third, 1).await.is_err());
+
+    let request = ActionBeginSavepointRequest {
+        transaction_id,
+        name: "closed".to_string(),
+    };
+    let action = Action {
+        r#type: "BeginSavepoint".to_string(),
+        body: request.as_any().encode_to_vec().into(),
+    };
+    assert!(
+        client.do_action(action).await.is_err(),
+        "The transaction should be closed"
+    );
+}
+
+#[tokio::test]
+async fn test_prepared_statements_in_transaction() {
+    let addr = "0.0.0.0:50103";
+    start_test_server(
+        addr.to_string(),
+        FlightSqlService::new(create_test_session()),
+    )
+    .await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let transaction_id = client
+        .begin_transaction()
+        .await
+        .expect("BeginTransaction should succeed");
+
+    let result = client
+        .prepare(
+            "INSERT INTO users VALUES (4, 'Dave')".to_string(),
+            Some(transaction_id.clone()),
+        )
+        .await;
+    assert!(result.is_err(), "Prepared DML should be read-only");
+
+    let mut prepared = client
+        .prepare(
+            "SELECT name FROM users WHERE id = 1".to_string(),
+            Some(transaction_id.clone()),
+        )
+        .await
+        .expect("Prepare should succeed");
+    prepared
+        .execute()
+        .await
+        .expect("Execute should succeed within the transaction");
+
+    client
+        .end_transaction(transaction_id, EndTransaction::Commit)
+        .await
+        .expect("EndTransaction should succeed");
+
+    // The prepared statement is bound to its transaction
+    let result = prepared.execute().await;
+    assert!(result.is_err(), "The transaction should be closed");
+}
+
+#[tokio::test]
+async fn test_transaction_owner() {
+    let addr = "0.0.0.0:50104";
+    let authenticator = MemoryAuthenticator::new()
+        .with_token("alice", Identity::new("alice"))
+        .with_token("bob", Identity::new("bob"));
+    start_test_server(
+        addr.to_string(),
+        FlightSqlService::new(create_test_session()).with_authenticator(Box::new(authenticator)),
+    )
+    .await;
+
+    let mut alice = create_test_client(&format!("http://{}", addr)).await;
+    alice.set_token("alice".to_string());
+    let mut bob = create_test_client(&format!("http://{}", addr)).await;
+    bob.set_token("bob".to_string());
+
+    let transaction_id = alice
+        .begin_transaction()
+        .await
+        .expect("BeginTransaction should succeed");
+
+    // Only the identity which began the transaction can use or end it
+    match bob
+        .execute("SELECT 1".to_string(), Some(transaction_id.clone()))
+        .await
+    {
+        Err(FlightError::Tonic(status)) => {
+            assert_eq!(status.code(), Code::PermissionDenied, "{status}")
+        }
+        result => panic!("Using another user's transaction should fail, got {result:?}"),
+    }
+    match bob
+        .end_transaction(transaction_id.clone(), EndTransaction::Commit)
+        .await
+    {
+        Err(FlightError::Tonic(status)) => {
+            assert_eq!(status.code(), Code::PermissionDenied, "{status}")
+        }
+        result => panic!("Ending another user's transaction should fail, got {result:?}"),
+    }
+
+    query(&mut alice, "SELECT 1", Some(transaction_id.clone())).await;
+    alice
+        .end_transaction(transaction_id, EndTransaction::Commit)
+        .await
+        .expect("EndTransaction should succeed");
+}
+
+#[tokio::test]
+async fn test_transaction_idle_timeout() {
+    let addr = "0.0.0.0:50105";
+    let transactions =
+        ReadOnlyTransactionManager::new().with_idle_timeout(Duration::from_millis(500));
+    start_test_server(
+        addr.to_string(),
+        FlightSqlService::new(create_test_session())
+            .with_transaction_manager(Arc::new(transactions)),
+    )
+    .await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let transaction_id = client
+        .begin_transaction()
+        .await
+        .expect("BeginTransaction should succeed");
+
+    // Using the transaction keeps it open
+    for _ in 0..3 {
+        sleep(Duration::from_millis(300)).await;
+        query(&mut client, "SELECT 1", Some(transaction_id.clone())).await;
+    }
+
+    sleep(Duration::from_millis(700)).await;
+    match client
+        .end_transaction(transaction_id, EndTransaction::Commit)
+        .await
+    {
+        Err(FlightError::Tonic(status)) => {
+            assert_eq!(status.code(), Code::NotFound, "{status}")
+        }
+        result => panic!("The idle transaction should be ended, got {result:?}"),
+    }
+}
@@ -0,0 +1,439 @@
+use std::sync::Arc;
+
+use arrow_flight::{
+    encode::FlightDataEncoderBuilder,
+    error::FlightError,
+    sql::{
+        client::FlightSqlServiceClient, CommandPreparedStatementUpdate, CommandStatementIngest,
+        DoPutUpdateResult, ProstMessageExt, TableDefinitionOptions, TableExistsOption,
+        TableNotExistOption,
+    },
+    FlightDescriptor,
+};
+use datafusion::arrow::{
+    array::{Int32Array, RecordBatch, StringArray},
+    datatypes::{DataType, Field, Schema},
+    util::pretty::pretty_format_batches,
+};
+use datafusion::{
+    catalog::{CatalogProvider, MemoryCatalogProvider, MemorySchemaProvider},
+    datasource::{MemTable, TableType},
+    execution::context::{SessionContext, SessionState},
+};
+use datafusion_flight_sql_server::{service::FlightSqlService, state::QueryHandle};
+use futures::{StreamExt, TryStreamExt};
+use prost::Message;
+use tokio::time::{sleep, Duration};
+use tonic::{
+    transport::{Channel, Endpoint},
+    Code,
+};
+
+fn create_test_session() -> SessionState {
+    let ctx = SessionContext::new();
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("name", DataType::Utf8, false),
+    ]));
+
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(Int32Array::from(vec![1, 2, 3])),
+            Arc::new(StringArray::from(vec!["Alice", "Bob", "Charlie"])),
+        ],
+    )
+    .unwrap();
+
+    let table = MemTable::try_new(schema, vec![vec![batch]]).unwrap();
+    ctx.register_table("users", Arc::new(table)).unwrap();
+
+    ctx.state()
+}
+
+async fn start_test_server(addr: String, service: FlightSqlService) {
+    tokio::spawn(async move {
+        service
+            .serve(addr)
+            .await
+            .expect("Server should start successfully");
+    });
+
+    sleep(Duration::from_millis(500)).await;
+}
+
+async fn create_test_client(addr: &str) -> FlightSqlServiceClient<Channel> {
+    let endpoint = Endpoint::new(addr.to_string()).expect("Valid endpoint");
+    let channel = endpoint.connect().await.expect("Connection successful");
+    FlightSqlServiceClient::new(channel)
+}
+
+async fn query(client: &mut FlightSqlServiceClient<Channel>, sql: &str) -> String {
+    let flight_info = client
+        .execute(sql.to_string(), None)
+        .await
+        .expect("Query should succeed");
+    let ticket = flight_info
+        .endpoint
+        .first()
+        .expect("Should have endpoint")
+        .ticket
+        .clone()
+        .expect("Should have ticket");
+    let batches: Vec<RecordBatch> = client
+        .do_get(ticket)
+        .await
+        .expect("do_get should succeed")
+        .try_collect()
+        .await
+        .expect("Stream should work");
+    pretty_format_batches(&batches).unwrap().to_string()
+}
+
+#[tokio::test]
+async fn test_statement_update() {
+    let addr = "0.0.0.0:50091";
+    start_test_server(
+        addr.to_string(),
+        FlightSqlService::new(create_test_session()),
+    )
+    .await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let count = client
+        .execute_update(
+            "INSERT INTO users VALUES (4, 'Dave'), (5, 'Eve')".to_string(),
+            None,
+        )
+        .await
+        .expect("INSERT should succeed");
+    assert_eq!(count, 2);
+
+    let formatted = query(&mut client, "SELECT count(*) AS n FROM users").await;
+    assert!(formatted.contains("| 5 |"), "{formatted}");
+
+    let count = client
+        .execute_update(
+            "CREATE TABLE groups (id INT, name VARCHAR)".to_string(),
+            None,
+        )
+        .await
+        .expect("CREATE TABLE should succeed");
+    assert_eq!(count, 0);
+
+    let count = client
+        .execute_update(
+            "INSERT INTO groups SELECT id, name FROM users WHERE id < 3".to_string(),
+            None,
+        )
+        .await
+        .expect("INSERT ... SELECT should succeed");
+    assert_eq!(count, 2);
+
+    let count = client
+        .execute_update(
+            "SET datafusion.execution.batch_size = 1024".to_string(),
+            None,
+        )
+        .await
+        .expect("SET should succeed");
+    assert_eq!(count, 0);
+
+    let result = client
+        .execute_update("INSERT INTO missing VALUES (1)".to_string(), None)
+        .await;
+    assert!(result.is_err(), "INSERT into a missing table should fail");
+}
+
+#[tokio::test]
+async fn test_prepared_statement_update() {
+    let addr = "0.0.0.0:50092";
+    start_test_server(
+        addr.to_string(),
+        FlightSqlService::new(create_test_session()),
+    )
+    .await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    // Parameters bound before executing, as done by the Rust client
+    let mut prepared = client
+        .prepare("INSERT INTO users VALUES ($1, $2)".to_string(), None)
+        .await
+        .expect("Prepare should succeed");
+    let params = RecordBatch::try_new(
+        Arc::new(Schema::new(vec![
+            Field::new("$1", DataType::Int32, false),
+            Field::new("$2", DataType::Utf8, false),
+        ])),
+        vec![
+            Arc::new(Int32Array::from(vec![4])),
+            Arc::new(StringArray::from(vec!["Dave"])),
+        ],
+    )
+    .unwrap();
+    prepared.set_parameters(params).unwrap();
+    let count = prepared
+        .execute_update()
+        .await
+        .expect("Prepared INSERT should succeed");
+    assert_eq!(count, 1);
+
+    // Parameters sent along with the update, one execution per row
+    let cmd = CommandPreparedStatementUpdate {
+        prepared_statement_handle: QueryHandle::new(
+            "INSERT INTO users VALUES ($1, $2)".to_string(),
+            None,
+        )
+        .encode(),
+    };
+    let params = RecordBatch::try_new(
+        Arc::new(Schema::new(vec![
+            Field::new("$1", DataType::Int32, false),
+            Field::new("$2", DataType::Utf8, false),
+        ])),
+        vec![
+            Arc::new(Int32Array::from(vec![5, 6])),
+            Arc::new(StringArray::from(vec!["Eve", "Frank"])),
+        ],
+    )
+    .unwrap();
+    let flight_data = FlightDataEncoderBuilder::new()
+        .with_flight_descriptor(Some(FlightDescriptor::new_cmd(
+            cmd.as_any().encode_to_vec(),
+        )))
+        .build(futures::stream::iter([Ok(params)]))
+        .map(|data| data.unwrap());
+    let result = client
+        .do_put(flight_data)
+        .await
+        .expect("do_put should succeed")
+        .message()
+        .await
+        .expect("Stream should work")
+        .expect("Should have a result");
+    let result = DoPutUpdateResult::decode(result.app_metadata).unwrap();
+    assert_eq!(result.record_count, 2);
+
+    let formatted = query(
+        &mut client,
+        "SELECT name FROM users WHERE id > 3 ORDER BY id",
+    )
+    .await;
+    for name in ["Dave", "Eve", "Frank"] {
+        assert!(formatted.contains(name), "{formatted}");
+    }
+}
+
+fn ingest_command(
+    table: &str,
+    if_not_exist: TableNotExistOption,
+    if_exists: TableExistsOption,
+) -> CommandStatementIngest {
+    CommandStatementIngest {
+        table_definition_options: Some(TableDefinitionOptions {
+            if_not_exist: if_not_exist.into(),
+            if_exists: if_exists.into(),
+        }),
+        table: table.to_string(),
+        schema: None,
+        catalog: None,
+        temporary: false,
+        transaction_id: None,
+        options: Default::default(),
+    }
+}
+
+#[tokio::test]
+async fn test_statement_ingest() {
+    let addr = "0.0.0.0:50093";
+    start_test_server(
+        addr.to_string(),
+        FlightSqlService::new(create_test_session()),
+    )
+    .await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let batch = RecordBatch::try_new(
+        Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, false),
+        ])),
+        vec![
+            Arc::new(Int32Array::from(vec![4, 5])),
+            Arc::new(StringArray::from(vec!["Dave", "Eve"])),
+        ],
+    )
+    .unwrap();
+
+    let count = client
+        .execute_ingest(
+            ingest_command(
+                "users",
+                TableNotExistOption::Fail,
+                TableExistsOption::Append,
+            ),
+            futures::stream::iter([Ok(batch.clone()), Ok(batch.clone())]),
+        )
+        .await
+        .expect("Appending should succeed");
+    assert_eq!(count, 4);
+    let formatted = query(&mut client, "SELECT count(*) AS n FROM users").await;
+    assert!(formatted.contains("| 7 |"), "{formatted}");
+
+    let count = client
+        .execute_ingest(
+            ingest_command(
+                "new_users",
+                TableNotExistOption::Create,
+                TableExistsOption::Fail,
+            ),
+            futures::stream::iter([Ok(batch.clone())]),
+        )
+        .await
+        .expect("Creating should succeed");
+    assert_eq!(count, 2);
+    let formatted = query(&mut client, "SELECT name FROM new_users ORDER BY id").await;
+    assert!(formatted.contains("Eve"), "{formatted}");
+
+    let result = client
+        .execute_ingest(
+            ingest_command(
+                "new_users",
+                TableNotExistOption::Create,
+                TableExistsOption::Fail,
+            ),
+            futures::stream::iter([Ok(batch.clone())]),
+        )
+        .await;
+    assert!(
+        result.is_err(),
+        "Ingesting into an existing table should fail"
+    );
+
+    let result = client
+        .execute_ingest(
+            ingest_command(
+                "missing",
+                TableNotExistOption::Fail,
+                TableExistsOption::Append,
+            ),
+            futures::stream::iter([Ok(batch.clone())]),
+        )
+        .await;
+    assert!(
+        result.is_err(),
+        "Ingesting into a missing table should fail"
+    );
+
+    let temporary = CommandStatementIngest {
+        temporary: true,
+        ..ingest_command(
+            "temporary_users",
+            TableNotExistOption::Create,
+            TableExistsOption::Fail,
+        )
+    };
+    match client
+        .execute_ingest(temporary, futures::stream::iter([Ok(batch.clone())]))
+        .await
+    {
+        Err(FlightError::Tonic(status)) => {
+            assert_eq!(status.code(), Code::Unimplemented, "{status}")
+        }
+        result => panic!("Ingesting into a temporary table should fail, got {result:?}"),
+    }
+
+    let count = client
+        .execute_ingest(
+            ingest_command(
+                "users",
+                TableNotExistOption::Fail,
+                TableExistsOption::Replace,
+            ),
+            futures::stream::iter([Ok(batch)]),
+        )
+        .await
+        .expect("Replacing should succeed");
+    assert_eq!(count, 2);
+    let formatted = query(&mut client, "SELECT count(*) AS n FROM users").await;
+    assert!(formatted.contains("| 2 |"), "{formatted}");
+}
+
+#[tokio::test]
+async fn test_statement_ingest_target() {
+    let addr = "0.0.0.0:50094";
+    let ctx = SessionContext::new_with_state(create_test_session());
+    let catalog = MemoryCatalogProvider::new();
+    catalog
+        .register_schema("public", Arc::new(MemorySchemaProvider::new()))
+        .unwrap();
+    ctx.register_catalog("other", Arc::new(catalog));
+    ctx.sql("CREATE VIEW user_names AS SELECT id, name FROM users")
+        .await
+        .unwrap();
+    start_test_server(addr.to_string(), FlightSqlService::new(ctx.state())).await;
+
+    let mut client = create_test_client(&format!("http://{}", addr)).await;
+
+    let batch = RecordBatch::try_new(
+        Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, false),
+        ])),
+        vec![
+            Arc::new(Int32Array::from(vec![4, 5])),
+            Arc::new(StringArray::from(vec!["Dave", "Eve"])),
+        ],
+    )
+    .unwrap();
+
+    // A catalog without a schema names the default schema of that catalog
+    let command = CommandStatementIngest {
+        catalog: Some("other".to_string()),
+        ..ingest_command(
+            "catalog_users",
+            TableNotExistOption::Create,
+            TableExistsOption::Fail,
+        )
+    };
+    client
+        .execute_ingest(command, futures::stream::iter([Ok(batch.clone())]))
+        .await
+        .expect("Creating should succeed");
+    let formatted = query(
+        &mut client,
+        "SELECT count(*) AS n FROM other.public.catalog_users",
+    )
+    .await;
+    assert!(formatted.contains("| 2 |"), "{formatted}");
+    assert!(
+        client
+            .execute("SELECT * FROM catalog_users".to_string(), None)
+            .await
+            .is_err(),
+        "The table should not be created in the default catalog"
+    );
+
+    // Replacing keeps the TableProvider, so a view cannot be replaced
+    let result = client
+        .execute_ingest(
+            ingest_command(
+                "user_names",
+                TableNotExistOption::Fail,
+                TableExistsOption::Replace,
+            ),
+            futures::stream::iter([Ok(batch)]),
+        )
+        .await;
+    assert!(result.is_err(), "Replacing a view should fail");
+    let formatted = query(&mut client, "SELECT count(*) AS n FROM user_names").await;
+    assert!(formatted.contains("| 3 |"), "{formatted}");
+    assert_eq!(
+        ctx.table_provider("user_names").await.unwrap().table_type(),
+        TableType::View
+    );
+}